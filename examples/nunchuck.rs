@@ -10,8 +10,8 @@
 //
 // NOTE: This code is provided as an example.  Driver developers are encouraged
 // to use the embedded-hal traits if possible rather than coupling directly
-// to this library.  An implementation of the embedded-hal I2C traits based
-// on this library may be found in the embedded-hal-linux project.
+// to this library.  With the `embedded-hal` feature enabled, `LinuxI2CBus`
+// and `LinuxI2CDevice` implement the embedded-hal 1.0 I2C traits directly.
 
 extern crate docopt;
 extern crate i2cdev;
@@ -57,6 +57,7 @@ mod nunchuck {
     // TODO: Move Nunchuck code out to be an actual sensor and add tests
 
     #[derive(Debug)]
+    #[allow(dead_code)] // fields are only consumed through Debug
     pub struct NunchuckReading {
         joystick_x: u8,
         joystick_y: u8,
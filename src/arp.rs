@@ -0,0 +1,581 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! SMBus 2.0 Address Resolution Protocol
+//!
+//! ARP-capable devices answer at the SMBus Device Default Address (0x61)
+//! until the ARP master assigns them an address.  Each is identified by
+//! its 128-bit Unique Device Identifier (UDID); when several devices
+//! answer Get UDID at once, the one with the lowest UDID wins arbitration.
+//! All ARP commands carry a PEC, computed in software.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::smbus::PecError<i2cdev::linux::LinuxI2CError>> {
+//! use i2cdev::arp::{AddressAllocator, ArpMaster};
+//! use i2cdev::linux::LinuxI2CBus;
+//!
+//! let bus = LinuxI2CBus::new("/dev/i2c-1")?;
+//! let mut arp = ArpMaster::new(bus);
+//! let mut allocator = AddressAllocator::new().with_used(vec![0x48, 0x50]);
+//! for device in arp.enumerate(&mut allocator)? {
+//!     println!("{:04x}:{:04x} at 0x{:02x}",
+//!              device.udid.vendor_id, device.udid.device_id,
+//!              device.address.unwrap());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use byteorder::{BigEndian, ByteOrder};
+use core::I2CTransfer;
+use smbus::{is_nack, PecDevice, PecError};
+use std::io;
+
+/// SMBus Device Default Address
+pub const SMBUS_DEVICE_DEFAULT_ADDRESS: u16 = 0x61;
+
+const PREPARE_TO_ARP: u8 = 0x01;
+const RESET_DEVICE: u8 = 0x02;
+const GET_UDID: u8 = 0x03;
+const ASSIGN_ADDRESS: u8 = 0x04;
+
+/// Byte count of the Get UDID and Assign Address blocks
+const UDID_BLOCK_LEN: u8 = 17;
+
+/// Upper bound on the devices resolved by `ArpMaster::enumerate`
+const MAX_DEVICES: usize = 0x80;
+
+/// Address type from the UDID device capabilities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressType {
+    /// The device has a fixed address
+    Fixed,
+    /// The assigned address survives a power cycle
+    DynamicPersistent,
+    /// The assigned address is lost on power loss or reset
+    DynamicVolatile,
+    /// The UDID is randomly generated
+    RandomNumber,
+}
+
+/// SMBus Unique Device Identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Udid {
+    /// Device capabilities: address type and PEC support
+    pub capabilities: u8,
+    /// UDID version and silicon revision
+    pub version: u8,
+    /// Vendor ID, as assigned by the PCI SIG
+    pub vendor_id: u16,
+    /// Device ID, assigned by the vendor
+    pub device_id: u16,
+    /// Interfaces and protocols supported by the device
+    pub interface: u16,
+    /// Subsystem vendor ID, zero if unused
+    pub subsystem_vendor_id: u16,
+    /// Subsystem device ID, zero if unused
+    pub subsystem_device_id: u16,
+    /// Vendor-specific ID, distinguishing devices of the same type
+    pub vendor_specific_id: u32,
+}
+
+impl Udid {
+    /// Parse a UDID in wire order, most significant byte first
+    pub fn from_bytes(bytes: &[u8; 16]) -> Udid {
+        Udid {
+            capabilities: bytes[0],
+            version: bytes[1],
+            vendor_id: BigEndian::read_u16(&bytes[2..]),
+            device_id: BigEndian::read_u16(&bytes[4..]),
+            interface: BigEndian::read_u16(&bytes[6..]),
+            subsystem_vendor_id: BigEndian::read_u16(&bytes[8..]),
+            subsystem_device_id: BigEndian::read_u16(&bytes[10..]),
+            vendor_specific_id: BigEndian::read_u32(&bytes[12..]),
+        }
+    }
+
+    /// The UDID in wire order, most significant byte first
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[0] = self.capabilities;
+        bytes[1] = self.version;
+        BigEndian::write_u16(&mut bytes[2..], self.vendor_id);
+        BigEndian::write_u16(&mut bytes[4..], self.device_id);
+        BigEndian::write_u16(&mut bytes[6..], self.interface);
+        BigEndian::write_u16(&mut bytes[8..], self.subsystem_vendor_id);
+        BigEndian::write_u16(&mut bytes[10..], self.subsystem_device_id);
+        BigEndian::write_u32(&mut bytes[12..], self.vendor_specific_id);
+        bytes
+    }
+
+    /// How the device's address is assigned
+    pub fn address_type(&self) -> AddressType {
+        match self.capabilities >> 6 {
+            0 => AddressType::Fixed,
+            1 => AddressType::DynamicPersistent,
+            2 => AddressType::DynamicVolatile,
+            _ => AddressType::RandomNumber,
+        }
+    }
+
+    /// Whether the device supports PEC outside of ARP
+    pub fn pec_supported(&self) -> bool {
+        self.capabilities & 1 != 0
+    }
+
+    /// UDID version, 1 for SMBus 2.0
+    pub fn udid_version(&self) -> u8 {
+        (self.version >> 3) & 0x07
+    }
+
+    /// Silicon revision of the device
+    pub fn silicon_revision(&self) -> u8 {
+        self.version & 0x07
+    }
+}
+
+/// A device answering Get UDID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArpDevice {
+    /// Identifier of the device
+    pub udid: Udid,
+    /// Current 7-bit address of the device, if it has a valid one
+    pub address: Option<u16>,
+}
+
+/// Tracks the 7-bit addresses in use on a bus
+///
+/// The addresses reserved by the SMBus specification are never handed
+/// out.  Addresses of devices outside ARP, as found by
+/// `LinuxI2CBus::scan`, should be marked used before enumerating.
+#[derive(Debug, Clone)]
+pub struct AddressAllocator {
+    used: u128,
+}
+
+impl Default for AddressAllocator {
+    fn default() -> Self {
+        AddressAllocator::new()
+    }
+}
+
+impl AddressAllocator {
+    /// Create an allocator with only the reserved addresses in use
+    pub fn new() -> AddressAllocator {
+        let reserved = [
+            0x08, // SMBus host
+            0x0C, // Alert Response Address
+            0x28, // ACCESS.bus host
+            0x2C, 0x2D, 0x2E, 0x2F, // reserved by previous versions
+            0x37, // ACCESS.bus default address
+            0x48, 0x49, 0x4A, 0x4B, // prototypes
+            0x61, // SMBus Device Default Address
+        ];
+        let mut allocator = AddressAllocator { used: 0 };
+        for address in (0x00..0x08)
+            .chain(0x78..0x80)
+            .chain(reserved.iter().cloned())
+        {
+            allocator.reserve(address);
+        }
+        allocator
+    }
+
+    /// Mark `addresses` as in use
+    pub fn with_used<I: IntoIterator<Item = u16>>(mut self, addresses: I) -> Self {
+        for address in addresses {
+            self.reserve(address);
+        }
+        self
+    }
+
+    /// Mark `address` as in use
+    pub fn reserve(&mut self, address: u16) {
+        if address < 0x80 {
+            self.used |= 1 << address;
+        }
+    }
+
+    /// Mark `address` as free again
+    pub fn release(&mut self, address: u16) {
+        if address < 0x80 {
+            self.used &= !(1 << address);
+        }
+    }
+
+    /// Whether `address` is a 7-bit address not in use
+    pub fn is_free(&self, address: u16) -> bool {
+        address < 0x80 && self.used & (1 << address) == 0
+    }
+
+    /// Reserve and return the lowest free address
+    pub fn allocate(&mut self) -> Option<u16> {
+        let address = (0..0x80).find(|&address| self.is_free(address))?;
+        self.reserve(address);
+        Some(address)
+    }
+}
+
+/// SMBus ARP master
+pub struct ArpMaster<T> {
+    device: PecDevice<T>,
+}
+
+impl<T> ArpMaster<T> {
+    /// Run ARP on `bus`
+    pub fn new(bus: T) -> ArpMaster<T> {
+        ArpMaster {
+            device: PecDevice::new(bus, SMBUS_DEVICE_DEFAULT_ADDRESS),
+        }
+    }
+
+    /// Get a reference to the underlying bus
+    pub fn inner(&self) -> &T {
+        self.device.inner()
+    }
+
+    /// Get a mutable reference to the underlying bus
+    pub fn inner_mut(&mut self) -> &mut T {
+        self.device.inner_mut()
+    }
+
+    /// Unwrap the underlying bus
+    pub fn into_inner(self) -> T {
+        self.device.into_inner()
+    }
+}
+
+impl<T, E> ArpMaster<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: From<io::Error> + Into<io::Error>,
+{
+    /// Clear the Address Resolved flag of every ARP-capable device
+    ///
+    /// Afterwards, all devices answer Get UDID until they are assigned an
+    /// address, including those assigned one before.
+    pub fn prepare_to_arp(&mut self) -> Result<(), PecError<E>> {
+        self.device.write_pec(&[PREPARE_TO_ARP])
+    }
+
+    /// Reset every ARP-capable device
+    ///
+    /// Devices with a volatile address lose it.
+    pub fn reset_device(&mut self) -> Result<(), PecError<E>> {
+        self.device.write_pec(&[RESET_DEVICE])
+    }
+
+    /// Read the UDID of the unresolved device winning arbitration
+    ///
+    /// Returns `None` when no device is left to resolve.
+    pub fn get_udid(&mut self) -> Result<Option<ArpDevice>, PecError<E>> {
+        let reply = match self
+            .device
+            .read_pec(&[GET_UDID], UDID_BLOCK_LEN as usize + 1)
+        {
+            Ok(reply) => reply,
+            Err(PecError::Bus(e)) => {
+                let e: io::Error = e.into();
+                if is_nack(&e) {
+                    return Ok(None);
+                }
+                return Err(PecError::Bus(e.into()));
+            }
+            Err(e) => return Err(e),
+        };
+        if reply[0] != UDID_BLOCK_LEN {
+            return Err(PecError::Bus(
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected byte count in Get UDID reply",
+                )
+                .into(),
+            ));
+        }
+        let mut udid = [0; 16];
+        udid.copy_from_slice(&reply[1..17]);
+        // the address is valid when its least significant bit is set
+        let address = match reply[17] {
+            0xFF => None,
+            byte if byte & 1 != 0 => Some(u16::from(byte >> 1)),
+            _ => None,
+        };
+        Ok(Some(ArpDevice {
+            udid: Udid::from_bytes(&udid),
+            address,
+        }))
+    }
+
+    /// Assign `address` to the device identified by `udid`
+    ///
+    /// The device also sets its Address Resolved flag, so it no longer
+    /// answers Get UDID.
+    pub fn assign_address(&mut self, udid: &Udid, address: u16) -> Result<(), PecError<E>> {
+        if address >= 0x80 {
+            return Err(PecError::Bus(
+                io::Error::new(io::ErrorKind::InvalidInput, "not a 7-bit address").into(),
+            ));
+        }
+        let mut block = Vec::with_capacity(UDID_BLOCK_LEN as usize + 2);
+        block.push(ASSIGN_ADDRESS);
+        block.push(UDID_BLOCK_LEN);
+        block.extend_from_slice(&udid.to_bytes());
+        block.push((address << 1) as u8);
+        self.device.write_pec(&block)
+    }
+
+    /// Resolve every ARP-capable device on the bus
+    ///
+    /// Sends Prepare to ARP, then assigns addresses until no device
+    /// answers Get UDID.  Fixed-address devices, and devices whose current
+    /// address is still free, keep their address; the others get the
+    /// lowest free address of `allocator`.
+    pub fn enumerate(
+        &mut self,
+        allocator: &mut AddressAllocator,
+    ) -> Result<Vec<ArpDevice>, PecError<E>> {
+        self.prepare_to_arp()?;
+        let mut devices = Vec::new();
+        while devices.len() < MAX_DEVICES {
+            let device = match self.get_udid()? {
+                Some(device) => device,
+                None => break,
+            };
+            let address = match device.address {
+                Some(address)
+                    if device.udid.address_type() == AddressType::Fixed
+                        || allocator.is_free(address) =>
+                {
+                    allocator.reserve(address);
+                    address
+                }
+                _ => allocator.allocate().ok_or_else(|| {
+                    PecError::Bus(
+                        io::Error::new(io::ErrorKind::AddrNotAvailable, "no free SMBus address")
+                            .into(),
+                    )
+                })?,
+            };
+            self.assign_address(&device.udid, address)?;
+            devices.push(ArpDevice {
+                udid: device.udid,
+                address: Some(address),
+            });
+        }
+        Ok(devices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc;
+    use mock::{I2CResult, MockI2CBus, MockI2CTarget};
+    use smbus::pec;
+
+    struct SimulatedDevice {
+        udid: [u8; 16],
+        address: Option<u8>,
+        resolved: bool,
+    }
+
+    /// ARP-capable devices sharing the Device Default Address
+    struct ArpTargets {
+        devices: Vec<SimulatedDevice>,
+        get_udid: bool,
+    }
+
+    impl ArpTargets {
+        fn check_pec(data: &[u8]) -> I2CResult<()> {
+            let mut covered = vec![0xC2];
+            covered.extend_from_slice(&data[..data.len() - 1]);
+            if pec(&covered) != data[data.len() - 1] {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "bad PEC"));
+            }
+            Ok(())
+        }
+
+        /// The unresolved device with the lowest UDID wins arbitration
+        fn winner(&mut self) -> Option<&mut SimulatedDevice> {
+            self.devices
+                .iter_mut()
+                .filter(|device| !device.resolved)
+                .min_by_key(|device| device.udid)
+        }
+    }
+
+    impl MockI2CTarget for ArpTargets {
+        fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+            self.get_udid = false;
+            match data[0] {
+                GET_UDID => {
+                    if self.winner().is_none() {
+                        return Err(io::Error::from_raw_os_error(libc::ENXIO));
+                    }
+                    self.get_udid = true;
+                }
+                PREPARE_TO_ARP => {
+                    Self::check_pec(data)?;
+                    for device in &mut self.devices {
+                        device.resolved = false;
+                    }
+                }
+                RESET_DEVICE => {
+                    Self::check_pec(data)?;
+                    for device in &mut self.devices {
+                        device.resolved = false;
+                        if device.udid[0] >> 6 == 2 {
+                            device.address = None;
+                        }
+                    }
+                }
+                ASSIGN_ADDRESS => {
+                    Self::check_pec(data)?;
+                    assert_eq!(data[1], UDID_BLOCK_LEN);
+                    for device in &mut self.devices {
+                        if device.udid[..] == data[2..18] {
+                            device.address = Some(data[18] >> 1);
+                            device.resolved = true;
+                        }
+                    }
+                }
+                _ => return Err(io::Error::from_raw_os_error(libc::ENXIO)),
+            }
+            Ok(())
+        }
+
+        fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+            assert!(self.get_udid);
+            let mut reply = vec![UDID_BLOCK_LEN];
+            {
+                let device = self.winner().unwrap();
+                reply.extend_from_slice(&device.udid);
+                reply.push(match device.address {
+                    Some(address) => address << 1 | 1,
+                    None => 0xFF,
+                });
+            }
+            let mut covered = vec![0xC2, GET_UDID, 0xC3];
+            covered.extend_from_slice(&reply);
+            reply.push(pec(&covered));
+            data.copy_from_slice(&reply[..data.len()]);
+            Ok(())
+        }
+    }
+
+    fn udid(capabilities: u8, vendor_specific_id: u32) -> Udid {
+        Udid {
+            capabilities,
+            version: 0x08,
+            vendor_id: 0x8086,
+            device_id: 0x1234,
+            interface: 0x0004,
+            subsystem_vendor_id: 0,
+            subsystem_device_id: 0,
+            vendor_specific_id,
+        }
+    }
+
+    fn arp_bus(devices: &[(Udid, Option<u8>)]) -> MockI2CBus {
+        let targets = ArpTargets {
+            devices: devices
+                .iter()
+                .map(|&(udid, address)| SimulatedDevice {
+                    udid: udid.to_bytes(),
+                    address,
+                    resolved: false,
+                })
+                .collect(),
+            get_udid: false,
+        };
+        let mut bus = MockI2CBus::new();
+        bus.add_target(SMBUS_DEVICE_DEFAULT_ADDRESS, targets);
+        bus
+    }
+
+    #[test]
+    fn udid_round_trip() {
+        let bytes = [
+            0x81, 0x0A, 0x80, 0x86, 0x12, 0x34, 0x00, 0x04, 0x10, 0xDE, 0x56, 0x78, 0xDE, 0xAD,
+            0xBE, 0xEF,
+        ];
+        let udid = Udid::from_bytes(&bytes);
+        assert_eq!(udid.address_type(), AddressType::DynamicVolatile);
+        assert!(udid.pec_supported());
+        assert_eq!(udid.udid_version(), 1);
+        assert_eq!(udid.silicon_revision(), 2);
+        assert_eq!(udid.vendor_id, 0x8086);
+        assert_eq!(udid.device_id, 0x1234);
+        assert_eq!(udid.subsystem_vendor_id, 0x10DE);
+        assert_eq!(udid.vendor_specific_id, 0xDEADBEEF);
+        assert_eq!(udid.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn allocator_skips_reserved_and_used() {
+        let mut allocator = AddressAllocator::new().with_used(vec![0x09, 0x0A]);
+        assert!(!allocator.is_free(0x61));
+        assert!(!allocator.is_free(0x7F));
+        assert!(!allocator.is_free(0x80));
+        assert_eq!(allocator.allocate(), Some(0x0B));
+        assert_eq!(allocator.allocate(), Some(0x0D));
+        allocator.release(0x0B);
+        assert_eq!(allocator.allocate(), Some(0x0B));
+
+        let mut allocator = AddressAllocator::new();
+        let mut count = 0;
+        while allocator.allocate().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 0x70 - 13);
+    }
+
+    #[test]
+    fn get_udid_and_assign_address() {
+        let first = udid(0x81, 1);
+        let mut arp = ArpMaster::new(arp_bus(&[(udid(0x81, 2), None), (first, None)]));
+        arp.prepare_to_arp().unwrap();
+        let device = arp.get_udid().unwrap().unwrap();
+        assert_eq!(device.udid, first);
+        assert_eq!(device.address, None);
+
+        arp.assign_address(&first, 0x20).unwrap();
+        let device = arp.get_udid().unwrap().unwrap();
+        assert_eq!(device.udid.vendor_specific_id, 2);
+        arp.assign_address(&device.udid, 0x21).unwrap();
+        assert_eq!(arp.get_udid().unwrap(), None);
+
+        // the first device reports its address once unresolved again
+        arp.prepare_to_arp().unwrap();
+        let device = arp.get_udid().unwrap().unwrap();
+        assert_eq!(device.address, Some(0x20));
+
+        // and loses it on reset, being dynamic and volatile
+        arp.reset_device().unwrap();
+        assert_eq!(arp.get_udid().unwrap().unwrap().address, None);
+    }
+
+    #[test]
+    fn enumerate_avoids_used_addresses() {
+        let fixed = udid(0x01, 3);
+        let keeps = udid(0x41, 2);
+        let mut arp = ArpMaster::new(arp_bus(&[
+            (udid(0x81, 1), Some(0x10)),
+            (keeps, Some(0x30)),
+            (fixed, Some(0x50)),
+        ]));
+        let mut allocator = AddressAllocator::new().with_used(vec![0x09, 0x10, 0x50]);
+        let devices = arp.enumerate(&mut allocator).unwrap();
+        let addresses: Vec<_> = devices.iter().map(|d| d.address.unwrap()).collect();
+        // arbitration orders devices by UDID, capabilities byte first
+        assert_eq!(addresses, vec![0x50, 0x30, 0x0A]);
+        assert!(!allocator.is_free(0x0A));
+        assert!(!allocator.is_free(0x30));
+        assert_eq!(arp.get_udid().unwrap(), None);
+    }
+}
@@ -0,0 +1,698 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Asynchronous I2C access
+//!
+//! The ioctls behind the Linux i2c-dev interface block, so calling them
+//! from an async executor stalls every task scheduled on the same thread.
+//! `AsyncI2C` moves a device or bus onto a dedicated worker thread and
+//! exposes it through the `AsyncI2CDevice` and `AsyncI2CTransfer` traits,
+//! whose futures work with any executor, tokio included.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::linux::LinuxI2CError> {
+//! use i2cdev::asynch::{AsyncI2C, AsyncI2CDevice};
+//! use i2cdev::linux::LinuxI2CDevice;
+//!
+//! let mut dev = AsyncI2C::new(LinuxI2CDevice::new("/dev/i2c-1", 0x52)?);
+//! let read = dev.smbus_read_byte_data(0x00);
+//! // `read` can now be awaited from any async context
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Cancellation
+//!
+//! An operation is handed to the worker the first time its future is
+//! polled, and from then on it runs to completion on the worker.  Dropping
+//! a future before that point cancels the operation; dropping it later
+//! only discards the result.  Either way an operation is never interrupted
+//! half-way, and data is only copied into the caller's buffers by a future
+//! which completes.
+
+use core::{I2CDevice, I2CMessage, I2CTransfer};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use linux::{LinuxI2CBus, LinuxI2CDevice, LinuxI2CError, LinuxI2CMessage};
+use mock::{MockI2CBus, MockI2CDevice, MockI2CMessage};
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+/// Boxed future returned by the async I2C traits
+pub type I2CFuture<'a, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'a>>;
+
+/// Asynchronous counterpart of `I2CDevice`
+pub trait AsyncI2CDevice {
+    /// Error type
+    type Error: Error;
+
+    /// Read data from the device to fill the provided slice
+    fn read<'a>(&'a mut self, data: &'a mut [u8]) -> I2CFuture<'a, (), Self::Error>;
+
+    /// Write the provided buffer to the device
+    fn write<'a>(&'a mut self, data: &'a [u8]) -> I2CFuture<'a, (), Self::Error>;
+
+    /// This sends a single bit to the device, at the place of the Rd/Wr bit
+    fn smbus_write_quick(&mut self, bit: bool) -> I2CFuture<'_, (), Self::Error>;
+
+    /// Read a single byte from a device, without specifying a device register
+    fn smbus_read_byte(&mut self) -> I2CFuture<'_, u8, Self::Error>;
+
+    /// Write a single byte to a device, without specifying a device register
+    fn smbus_write_byte(&mut self, value: u8) -> I2CFuture<'_, (), Self::Error>;
+
+    /// Read a single byte from a device, from a designated register
+    fn smbus_read_byte_data(&mut self, register: u8) -> I2CFuture<'_, u8, Self::Error>;
+
+    /// Write a single byte to a specific register on a device
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> I2CFuture<'_, (), Self::Error>;
+
+    /// Read 2 bytes from a given register on a device (lsb first)
+    fn smbus_read_word_data(&mut self, register: u8) -> I2CFuture<'_, u16, Self::Error>;
+
+    /// Write 2 bytes to a given register on a device (lsb first)
+    fn smbus_write_word_data(&mut self, register: u8, value: u16)
+        -> I2CFuture<'_, (), Self::Error>;
+
+    /// Select a register, send 16 bits of data to it, and read 16 bits of data
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> I2CFuture<'_, u16, Self::Error>;
+
+    /// Read a block of up to 32 bytes from a device
+    fn smbus_read_block_data(&mut self, register: u8) -> I2CFuture<'_, Vec<u8>, Self::Error>;
+
+    /// Read a block of up to 32 bytes from a device via read_i2c_block_data
+    fn smbus_read_i2c_block_data(
+        &mut self,
+        register: u8,
+        len: u8,
+    ) -> I2CFuture<'_, Vec<u8>, Self::Error>;
+
+    /// Write a block of up to 32 bytes to a device
+    fn smbus_write_block_data<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, (), Self::Error>;
+
+    /// Write a block of up to 32 bytes to a device via write_i2c_block_data
+    fn smbus_write_i2c_block_data<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, (), Self::Error>;
+
+    /// Select a register, send 1 to 31 bytes of data to it, and reads
+    /// 1 to 31 bytes of data from it.
+    fn smbus_process_block<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, Vec<u8>, Self::Error>;
+}
+
+/// Asynchronous counterpart of `I2CTransfer`
+pub trait AsyncI2CTransfer {
+    /// I2C transfer error type
+    type Error: Error;
+
+    /// Performs multiple serially chained I2C read/write transactions.  On
+    /// success the return code is the number of successfully executed
+    /// transactions
+    fn transfer<'a>(
+        &'a mut self,
+        msgs: &'a mut [AsyncI2CMessage<'a>],
+    ) -> I2CFuture<'a, u32, Self::Error>;
+}
+
+#[derive(Debug)]
+pub(crate) enum AsyncMessageType<'a> {
+    Write(&'a [u8]),
+    Read(&'a mut [u8]),
+}
+
+/// Read/Write I2C message for `AsyncI2CTransfer`
+#[derive(Debug)]
+pub struct AsyncI2CMessage<'a> {
+    pub(crate) addr: u16,
+    pub(crate) msg_type: AsyncMessageType<'a>,
+}
+
+impl<'a> I2CMessage<'a> for AsyncI2CMessage<'a> {
+    fn read(data: &'a mut [u8]) -> Self {
+        AsyncI2CMessage {
+            addr: 0, // will be filled later
+            msg_type: AsyncMessageType::Read(data),
+        }
+    }
+
+    fn write(data: &'a [u8]) -> Self {
+        AsyncI2CMessage {
+            addr: 0, // will be filled later
+            msg_type: AsyncMessageType::Write(data),
+        }
+    }
+
+    fn with_address(self, slave_address: u16) -> Self {
+        AsyncI2CMessage {
+            addr: slave_address,
+            msg_type: self.msg_type,
+        }
+    }
+}
+
+/// Result slot shared between a `WorkerFuture` and its job
+struct Slot<R> {
+    value: Option<R>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+/// Completes a `WorkerFuture`; closes it without a value when dropped
+/// unused, e.g. because the worker panicked
+struct Completer<R> {
+    slot: Arc<Mutex<Slot<R>>>,
+}
+
+impl<R> Completer<R> {
+    fn complete(self, value: R) {
+        self.slot.lock().unwrap().value = Some(value);
+    }
+}
+
+impl<R> Drop for Completer<R> {
+    fn drop(&mut self) {
+        let waker = match self.slot.lock() {
+            Ok(mut slot) => {
+                slot.closed = true;
+                slot.waker.take()
+            }
+            Err(_) => None,
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+type Job<T> = Box<dyn FnOnce(&mut T) + Send>;
+
+/// Future for an operation run on the worker of an `AsyncI2C`
+pub struct WorkerFuture<R> {
+    start: Option<Box<dyn FnOnce() + Send>>,
+    slot: Arc<Mutex<Slot<R>>>,
+}
+
+impl<R> Future for WorkerFuture<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<R> {
+        let this = self.get_mut();
+        if let Some(start) = this.start.take() {
+            start();
+        }
+        let mut slot = this.slot.lock().unwrap();
+        if let Some(value) = slot.value.take() {
+            return Poll::Ready(value);
+        }
+        if slot.closed {
+            panic!("I2C worker thread exited without completing the operation");
+        }
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Future applying a final step to the result of a `WorkerFuture`
+struct Finish<R, F> {
+    job: WorkerFuture<R>,
+    finish: Option<F>,
+}
+
+impl<R, O, F> Future for Finish<R, F>
+where
+    F: FnOnce(R) -> O + Unpin,
+{
+    type Output = O;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<O> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.job).poll(cx) {
+            Poll::Ready(value) => {
+                let finish = this.finish.take().expect("future polled after completion");
+                Poll::Ready(finish(value))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future which is immediately ready
+pub(crate) struct Ready<T>(pub(crate) Option<T>);
+
+impl<T: Unpin> Future for Ready<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<T> {
+        Poll::Ready(
+            self.get_mut()
+                .0
+                .take()
+                .expect("future polled after completion"),
+        )
+    }
+}
+
+/// I2C device or bus driven by a dedicated worker thread
+///
+/// Every operation is executed on the worker, one at a time and in the
+/// order the futures are first polled.  Dropping the `AsyncI2C` stops the
+/// worker once the operations already handed to it have completed.
+pub struct AsyncI2C<T> {
+    sender: Sender<Job<T>>,
+    worker: JoinHandle<T>,
+}
+
+impl<T: Send + 'static> AsyncI2C<T> {
+    /// Move `inner` onto a new worker thread
+    pub fn new(inner: T) -> AsyncI2C<T> {
+        let (sender, receiver) = mpsc::channel::<Job<T>>();
+        let worker = thread::Builder::new()
+            .name("i2c-worker".to_string())
+            .spawn(move || {
+                let mut inner = inner;
+                for job in receiver {
+                    job(&mut inner);
+                }
+                inner
+            })
+            .expect("failed to spawn I2C worker thread");
+        AsyncI2C { sender, worker }
+    }
+
+    /// Run an arbitrary closure with the wrapped device on the worker
+    ///
+    /// This gives access to functionality not covered by the async traits,
+    /// such as `LinuxI2CDevice::set_slave_address`.
+    pub fn run<R, F>(&self, f: F) -> WorkerFuture<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut T) -> R + Send + 'static,
+    {
+        let slot = Arc::new(Mutex::new(Slot {
+            value: None,
+            closed: false,
+            waker: None,
+        }));
+        let completer = Completer { slot: slot.clone() };
+        let job: Job<T> = Box::new(move |inner: &mut T| completer.complete(f(inner)));
+        let sender = self.sender.clone();
+        WorkerFuture {
+            // if the worker is gone the job is dropped, which closes the slot
+            start: Some(Box::new(move || drop(sender.send(job)))),
+            slot,
+        }
+    }
+
+    /// Stop the worker and return the wrapped device
+    ///
+    /// This blocks until the operations already handed to the worker have
+    /// completed.
+    pub fn into_inner(self) -> T {
+        drop(self.sender);
+        self.worker.join().expect("I2C worker thread panicked")
+    }
+
+    fn call<'a, R, E, F>(&'a mut self, f: F) -> I2CFuture<'a, R, E>
+    where
+        R: Send + 'static,
+        E: Send + 'static,
+        F: FnOnce(&mut T) -> Result<R, E> + Send + 'static,
+    {
+        Box::pin(self.run(f))
+    }
+}
+
+impl<T> AsyncI2CDevice for AsyncI2C<T>
+where
+    T: I2CDevice + Send + 'static,
+    T::Error: Send + 'static,
+{
+    type Error = T::Error;
+
+    fn read<'a>(&'a mut self, data: &'a mut [u8]) -> I2CFuture<'a, (), Self::Error> {
+        let mut buf = vec![0; data.len()];
+        let job = self.run(move |dev: &mut T| dev.read(&mut buf).map(|_| buf));
+        Box::pin(Finish {
+            job,
+            finish: Some(move |result: Result<Vec<u8>, T::Error>| {
+                result.map(|buf| data.copy_from_slice(&buf))
+            }),
+        })
+    }
+
+    fn write<'a>(&'a mut self, data: &'a [u8]) -> I2CFuture<'a, (), Self::Error> {
+        let data = data.to_vec();
+        self.call(move |dev| dev.write(&data))
+    }
+
+    fn smbus_write_quick(&mut self, bit: bool) -> I2CFuture<'_, (), Self::Error> {
+        self.call(move |dev| dev.smbus_write_quick(bit))
+    }
+
+    fn smbus_read_byte(&mut self) -> I2CFuture<'_, u8, Self::Error> {
+        self.call(|dev| dev.smbus_read_byte())
+    }
+
+    fn smbus_write_byte(&mut self, value: u8) -> I2CFuture<'_, (), Self::Error> {
+        self.call(move |dev| dev.smbus_write_byte(value))
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> I2CFuture<'_, u8, Self::Error> {
+        self.call(move |dev| dev.smbus_read_byte_data(register))
+    }
+
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> I2CFuture<'_, (), Self::Error> {
+        self.call(move |dev| dev.smbus_write_byte_data(register, value))
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> I2CFuture<'_, u16, Self::Error> {
+        self.call(move |dev| dev.smbus_read_word_data(register))
+    }
+
+    fn smbus_write_word_data(
+        &mut self,
+        register: u8,
+        value: u16,
+    ) -> I2CFuture<'_, (), Self::Error> {
+        self.call(move |dev| dev.smbus_write_word_data(register, value))
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> I2CFuture<'_, u16, Self::Error> {
+        self.call(move |dev| dev.smbus_process_word(register, value))
+    }
+
+    fn smbus_read_block_data(&mut self, register: u8) -> I2CFuture<'_, Vec<u8>, Self::Error> {
+        self.call(move |dev| dev.smbus_read_block_data(register))
+    }
+
+    fn smbus_read_i2c_block_data(
+        &mut self,
+        register: u8,
+        len: u8,
+    ) -> I2CFuture<'_, Vec<u8>, Self::Error> {
+        self.call(move |dev| dev.smbus_read_i2c_block_data(register, len))
+    }
+
+    fn smbus_write_block_data<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, (), Self::Error> {
+        let values = values.to_vec();
+        self.call(move |dev| dev.smbus_write_block_data(register, &values))
+    }
+
+    fn smbus_write_i2c_block_data<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, (), Self::Error> {
+        let values = values.to_vec();
+        self.call(move |dev| dev.smbus_write_i2c_block_data(register, &values))
+    }
+
+    fn smbus_process_block<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, Vec<u8>, Self::Error> {
+        let values = values.to_vec();
+        self.call(move |dev| dev.smbus_process_block(register, &values))
+    }
+}
+
+/// Owned copy of a message: address, read flag and data
+type OwnedMessage = (u16, bool, Vec<u8>);
+
+/// Copy messages so they can be sent to the worker
+fn own_messages(msgs: &[AsyncI2CMessage]) -> Vec<OwnedMessage> {
+    msgs.iter()
+        .map(|msg| match msg.msg_type {
+            AsyncMessageType::Read(ref data) => (msg.addr, true, vec![0; data.len()]),
+            AsyncMessageType::Write(data) => (msg.addr, false, data.to_vec()),
+        })
+        .collect()
+}
+
+/// Borrow owned messages as messages of a synchronous `I2CTransfer`
+fn borrow_messages<'x, M: I2CMessage<'x>>(owned: &'x mut [OwnedMessage]) -> Vec<M> {
+    owned
+        .iter_mut()
+        .map(|&mut (addr, is_read, ref mut buf)| {
+            if is_read { M::read(buf) } else { M::write(buf) }.with_address(addr)
+        })
+        .collect()
+}
+
+/// Copy the data read by the worker back into the caller's messages
+fn finish_transfer<'a, E>(
+    job: WorkerFuture<Result<(u32, Vec<OwnedMessage>), E>>,
+    msgs: &'a mut [AsyncI2CMessage<'a>],
+) -> I2CFuture<'a, u32, E>
+where
+    E: Send + 'static,
+{
+    Box::pin(Finish {
+        job,
+        finish: Some(move |result: Result<(u32, Vec<OwnedMessage>), E>| {
+            result.map(|(count, owned)| {
+                for (msg, (_, _, buf)) in msgs.iter_mut().zip(owned) {
+                    if let AsyncMessageType::Read(ref mut data) = msg.msg_type {
+                        data.copy_from_slice(&buf);
+                    }
+                }
+                count
+            })
+        }),
+    })
+}
+
+// `AsyncI2CTransfer` is implemented per device type rather than generically, as
+// messages borrowed from a buffer local to the worker can only be named for
+// concrete types.
+macro_rules! async_transfer {
+    ($device:ty, $message:ident, $error:ty) => {
+        impl AsyncI2CTransfer for AsyncI2C<$device> {
+            type Error = $error;
+
+            fn transfer<'a>(
+                &'a mut self,
+                msgs: &'a mut [AsyncI2CMessage<'a>],
+            ) -> I2CFuture<'a, u32, Self::Error> {
+                let mut owned = own_messages(msgs);
+                let job = self.run(move |dev: &mut $device| {
+                    let count = {
+                        let mut messages: Vec<$message> = borrow_messages(&mut owned);
+                        I2CTransfer::transfer(dev, &mut messages)?
+                    };
+                    Ok((count, owned))
+                });
+                finish_transfer(job, msgs)
+            }
+        }
+    };
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+async_transfer!(LinuxI2CBus, LinuxI2CMessage, LinuxI2CError);
+#[cfg(any(target_os = "linux", target_os = "android"))]
+async_transfer!(LinuxI2CDevice, LinuxI2CMessage, LinuxI2CError);
+async_transfer!(MockI2CBus, MockI2CMessage, io::Error);
+async_transfer!(MockI2CDevice, MockI2CMessage, io::Error);
+
+/// The operations run through the embedded-hal `I2c` implementation of the
+/// wrapped type, so the `embedded-hal` feature is needed as well
+#[cfg(all(feature = "embedded-hal", feature = "embedded-hal-async"))]
+mod hal {
+    use super::AsyncI2C;
+    use embedded_hal::i2c::{self, AddressMode, Operation};
+    use embedded_hal_async::i2c as async_i2c;
+    use std::future::Future;
+
+    impl<T: i2c::ErrorType> async_i2c::ErrorType for AsyncI2C<T> {
+        type Error = T::Error;
+    }
+
+    impl<A, T> async_i2c::I2c<A> for AsyncI2C<T>
+    where
+        A: AddressMode + Send + 'static,
+        T: i2c::I2c<A> + Send + 'static,
+        T::Error: Send + 'static,
+    {
+        fn transaction(
+            &mut self,
+            address: A,
+            operations: &mut [Operation],
+        ) -> impl Future<Output = Result<(), T::Error>> {
+            let mut owned: Vec<(bool, Vec<u8>)> = operations
+                .iter()
+                .map(|op| match *op {
+                    Operation::Read(ref buf) => (true, vec![0; buf.len()]),
+                    Operation::Write(buf) => (false, buf.to_vec()),
+                })
+                .collect();
+            let job = self.run(move |dev: &mut T| {
+                {
+                    let mut ops: Vec<Operation> = owned
+                        .iter_mut()
+                        .map(|&mut (is_read, ref mut buf)| {
+                            if is_read {
+                                Operation::Read(buf)
+                            } else {
+                                Operation::Write(buf)
+                            }
+                        })
+                        .collect();
+                    dev.transaction(address, &mut ops)?;
+                }
+                Ok(owned)
+            });
+            super::Finish {
+                job,
+                finish: Some(move |result: Result<Vec<(bool, Vec<u8>)>, T::Error>| {
+                    result.map(|owned| {
+                        for (op, (_, data)) in operations.iter_mut().zip(owned) {
+                            if let Operation::Read(ref mut buf) = *op {
+                                buf.copy_from_slice(&data);
+                            }
+                        }
+                    })
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::mem;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::{RawWaker, RawWakerVTable};
+    use std::thread::Thread;
+
+    struct ThreadWaker {
+        thread: Thread,
+        woken: AtomicBool,
+    }
+
+    impl ThreadWaker {
+        fn wake(&self) {
+            self.woken.store(true, Ordering::SeqCst);
+            self.thread.unpark();
+        }
+    }
+
+    // built by hand rather than with `std::task::Wake`, which is newer
+    // than the minimum supported Rust version
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone_waker, wake, wake_by_ref, drop_waker);
+
+    fn raw_waker(waker: Arc<ThreadWaker>) -> RawWaker {
+        RawWaker::new(Arc::into_raw(waker) as *const (), &VTABLE)
+    }
+
+    unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
+        let waker = Arc::from_raw(ptr as *const ThreadWaker);
+        let clone = waker.clone();
+        mem::forget(waker);
+        raw_waker(clone)
+    }
+
+    unsafe fn wake(ptr: *const ()) {
+        Arc::from_raw(ptr as *const ThreadWaker).wake();
+    }
+
+    unsafe fn wake_by_ref(ptr: *const ()) {
+        (*(ptr as *const ThreadWaker)).wake();
+    }
+
+    unsafe fn drop_waker(ptr: *const ()) {
+        drop(Arc::from_raw(ptr as *const ThreadWaker));
+    }
+
+    /// Minimal executor driving a single future on the current thread
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Arc::new(ThreadWaker {
+            thread: thread::current(),
+            woken: AtomicBool::new(false),
+        });
+        let task_waker = unsafe { Waker::from_raw(raw_waker(waker.clone())) };
+        let mut cx = Context::from_waker(&task_waker);
+        loop {
+            if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+                return value;
+            }
+            while !waker.woken.swap(false, Ordering::SeqCst) {
+                thread::park();
+            }
+        }
+    }
+
+    #[test]
+    fn worker_runs_device_operations() {
+        let mut dev = AsyncI2C::new(MockI2CDevice::new());
+        block_on(dev.smbus_write_block_data(0x10, &[1, 2, 3])).unwrap();
+        assert_eq!(
+            block_on(dev.smbus_read_block_data(0x10)).unwrap(),
+            vec![1, 2, 3]
+        );
+
+        let mut buf = [0; 2];
+        block_on(AsyncI2CDevice::write(&mut dev, &[0x11])).unwrap();
+        block_on(AsyncI2CDevice::read(&mut dev, &mut buf)).unwrap();
+        assert_eq!(buf, [1, 2]);
+
+        let inner = dev.into_inner();
+        assert_eq!(inner.regmap.read_regs(0x10, 4), vec![3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn worker_runs_transfers() {
+        let mut target = MockI2CDevice::new();
+        target.regmap.write_regs(0x00, &[0xAA, 0xBB]);
+        let mut bus = MockI2CBus::new();
+        bus.add_target(0x20, target);
+        let mut bus = AsyncI2C::new(bus);
+
+        let mut data = [0; 2];
+        {
+            let mut msgs = [
+                AsyncI2CMessage::write(&[0x00]).with_address(0x20),
+                AsyncI2CMessage::read(&mut data).with_address(0x20),
+            ];
+            assert_eq!(block_on(bus.transfer(&mut msgs)).unwrap(), 2);
+        }
+        assert_eq!(data, [0xAA, 0xBB]);
+
+        let mut msgs = [AsyncI2CMessage::write(&[0x00]).with_address(0x21)];
+        assert!(block_on(bus.transfer(&mut msgs)).is_err());
+    }
+
+    #[test]
+    fn dropped_future_never_starts() {
+        let mut dev = AsyncI2C::new(MockI2CDevice::new());
+        drop(dev.smbus_write_byte_data(0x00, 0xFF));
+        assert_eq!(block_on(dev.smbus_read_byte_data(0x00)).unwrap(), 0x00);
+    }
+}
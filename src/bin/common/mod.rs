@@ -0,0 +1,265 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Helpers shared by the i2c-tools style binaries.  Not every binary uses
+// every helper.
+#![allow(dead_code)]
+
+use i2cdev::linux::{self, LinuxI2CDevice};
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process;
+
+/// Print an error message and terminate with a failure exit code
+pub fn exit_with<E: Display>(message: E) -> ! {
+    eprintln!("Error: {}", message);
+    process::exit(1)
+}
+
+/// Parse a number the way strtol(3) with base 0 does for the common cases:
+/// `0x`-prefixed hexadecimal, `0`-prefixed octal or decimal
+pub fn parse_number(s: &str) -> Result<u32, String> {
+    let result = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16)
+    } else if s.len() > 1 && s.starts_with('0') {
+        u32::from_str_radix(&s[1..], 8)
+    } else {
+        s.parse()
+    };
+    result.map_err(|_| format!("invalid number {:?}", s))
+}
+
+/// Parse a number that must fit in a byte
+pub fn parse_byte(s: &str) -> Result<u8, String> {
+    let value = parse_number(s)?;
+    if value > 0xFF {
+        return Err(format!("value {} out of range (0x00-0xff)", s));
+    }
+    Ok(value as u8)
+}
+
+/// Parse a 7-bit slave address
+pub fn parse_address(s: &str) -> Result<u16, String> {
+    let address = parse_number(s)?;
+    if address > 0x7F {
+        return Err(format!("chip address {} out of range (0x00-0x7f)", s));
+    }
+    Ok(address as u16)
+}
+
+/// Resolve a bus given as a number, a device path or an adapter name
+pub fn bus_path(s: &str) -> Result<PathBuf, String> {
+    if let Ok(number) = parse_number(s) {
+        return Ok(PathBuf::from(format!("/dev/i2c-{}", number)));
+    }
+    if s.starts_with('/') {
+        return Ok(PathBuf::from(s));
+    }
+    let adapters = linux::adapters().map_err(|e| format!("could not list adapters: {}", e))?;
+    adapters
+        .into_iter()
+        .find(|adapter| adapter.name == s)
+        .map(|adapter| adapter.path)
+        .ok_or_else(|| format!("no I2C adapter named {:?}", s))
+}
+
+/// Open a device, optionally even if a kernel driver has claimed it
+pub fn open_device(bus: &str, address: u16, force: bool) -> Result<LinuxI2CDevice, String> {
+    let path = bus_path(bus)?;
+    let device = if force {
+        unsafe { LinuxI2CDevice::force_new(&path, address) }
+    } else {
+        LinuxI2CDevice::new(&path, address)
+    };
+    device.map_err(|e| {
+        format!(
+            "could not open {} at 0x{:02x}: {}",
+            path.display(),
+            address,
+            e
+        )
+    })
+}
+
+/// Ask the user to confirm a potentially dangerous operation
+pub fn confirm(warning: &str) -> bool {
+    eprintln!("WARNING! {}", warning);
+    eprint!("Continue? [Y/n] ");
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    let answer = answer.trim();
+    answer.is_empty() || answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+}
+
+/// Printable representation of a byte for the ASCII column of a dump
+pub fn printable(byte: u8) -> char {
+    if byte == 0x00 || byte == 0xFF {
+        '.'
+    } else if !(0x20..0x7F).contains(&byte) {
+        '?'
+    } else {
+        byte as char
+    }
+}
+
+/// One message of an `i2ctransfer` command line
+#[derive(Debug, PartialEq)]
+pub struct MessageSpec {
+    /// Whether this is a read (as opposed to a write) message
+    pub read: bool,
+    /// Target slave address
+    pub address: u16,
+    /// Bytes to write, or a zeroed buffer of the requested read length
+    pub data: Vec<u8>,
+}
+
+fn parse_descriptor(arg: &str, last_address: Option<u16>) -> Result<(bool, usize, u16), String> {
+    let read = match arg.chars().next() {
+        Some('r') => true,
+        Some('w') => false,
+        _ => return Err(format!("invalid message descriptor {:?}", arg)),
+    };
+    let mut parts = arg[1..].splitn(2, '@');
+    let len = parts.next().unwrap_or("");
+    let len: usize = len
+        .parse()
+        .map_err(|_| format!("invalid length in {:?}", arg))?;
+    if len > 0xFFFF {
+        return Err(format!("message too long in {:?}", arg));
+    }
+    let address = match parts.next() {
+        Some(address) => parse_address(address)?,
+        None => last_address.ok_or_else(|| format!("no address given for {:?}", arg))?,
+    };
+    Ok((read, len, address))
+}
+
+/// Parse the message description syntax used by `i2ctransfer`
+///
+/// Each message starts with `{r|w}LENGTH[@ADDRESS]`, where the address
+/// may be omitted after the first message to reuse the previous one.  A
+/// write is followed by LENGTH data bytes.  The last data byte given may
+/// carry a suffix that fills the rest of the message: `=` repeats the value,
+/// `+` and `-` increment or decrement it, and `p` uses it as the seed of a
+/// pseudo-random sequence.
+pub fn parse_messages<S: AsRef<str>>(args: &[S]) -> Result<Vec<MessageSpec>, String> {
+    let mut messages = Vec::new();
+    let mut last_address = None;
+    let mut args = args.iter().map(AsRef::as_ref);
+
+    while let Some(arg) = args.next() {
+        let (read, len, address) = parse_descriptor(arg, last_address)?;
+        last_address = Some(address);
+
+        if read {
+            messages.push(MessageSpec {
+                read,
+                address,
+                data: vec![0; len],
+            });
+            continue;
+        }
+
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing data for {:?}", arg))?;
+            let (digits, suffix) = match value.chars().last() {
+                Some(c @ '=') | Some(c @ '+') | Some(c @ '-') | Some(c @ 'p') => {
+                    (&value[..value.len() - 1], Some(c))
+                }
+                _ => (value, None),
+            };
+            let mut byte = parse_byte(digits)?;
+            match suffix {
+                None => data.push(byte),
+                Some(suffix) => {
+                    while data.len() < len {
+                        data.push(byte);
+                        byte = match suffix {
+                            '+' => byte.wrapping_add(1),
+                            '-' => byte.wrapping_sub(1),
+                            'p' => (byte >> 1) ^ if byte & 1 != 0 { 0xB8 } else { 0x00 },
+                            _ => byte,
+                        };
+                    }
+                }
+            }
+        }
+        messages.push(MessageSpec {
+            read,
+            address,
+            data,
+        });
+    }
+
+    if messages.is_empty() {
+        return Err("no messages given".to_string());
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_number("0x50"), Ok(0x50));
+        assert_eq!(parse_number("010"), Ok(8));
+        assert_eq!(parse_number("10"), Ok(10));
+        assert_eq!(parse_number("0"), Ok(0));
+        assert!(parse_number("zz").is_err());
+        assert!(parse_address("0x80").is_err());
+        assert!(parse_byte("0x100").is_err());
+    }
+
+    #[test]
+    fn write_then_read() {
+        let messages = parse_messages(&["w2@0x50", "0x00", "0x10", "r4"]).unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                MessageSpec {
+                    read: false,
+                    address: 0x50,
+                    data: vec![0x00, 0x10],
+                },
+                MessageSpec {
+                    read: true,
+                    address: 0x50,
+                    data: vec![0; 4],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fill_suffixes() {
+        let messages =
+            parse_messages(&["w4@0x20", "0x10+", "w3", "0x01", "0xff-", "w2@0x21", "5="]).unwrap();
+        assert_eq!(messages[0].data, vec![0x10, 0x11, 0x12, 0x13]);
+        assert_eq!(messages[1].address, 0x20);
+        assert_eq!(messages[1].data, vec![0x01, 0xFF, 0xFE]);
+        assert_eq!(messages[2].address, 0x21);
+        assert_eq!(messages[2].data, vec![5, 5]);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse_messages(&["r1"]).is_err());
+        assert!(parse_messages(&["w2@0x50", "0x00"]).is_err());
+        assert!(parse_messages(&["x1@0x50"]).is_err());
+        assert!(parse_messages::<&str>(&[]).is_err());
+    }
+}
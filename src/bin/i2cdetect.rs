@@ -0,0 +1,167 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate docopt;
+extern crate i2cdev;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod common;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use common::*;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use i2cdev::linux::{self, I2CAddressStatus, I2CFunctions, I2CScanMode, LinuxI2CBus};
+
+use docopt::Docopt;
+use std::env::args;
+
+const USAGE: &str = "
+Detect devices on an I2C bus.
+
+Usage:
+  i2cdetect [-y] [-a] [-q | -r] <bus> [<first> <last>]
+  i2cdetect -F <bus>
+  i2cdetect -l
+  i2cdetect (-h | --help)
+  i2cdetect --version
+
+Options:
+  -y           Disable interactive mode.
+  -a           Probe all addresses, including reserved ones.
+  -q           Probe using SMBus quick write.
+  -r           Probe using SMBus receive byte.
+  -F           Display the functionality of the adapter.
+  -l           List installed adapters.
+  -h --help    Show this help text.
+  --version    Show version.
+
+<bus> may be a bus number, a /dev path or an adapter name.
+";
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn main() {}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn list_adapters() {
+    let adapters = linux::adapters().unwrap_or_else(|e| exit_with(e));
+    for adapter in adapters {
+        let kind = if adapter.is_mux_channel {
+            "i2c-mux"
+        } else {
+            "i2c"
+        };
+        println!(
+            "i2c-{}\t{:<9}\t{:<32}\tI2C adapter",
+            adapter.number, kind, adapter.name
+        );
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn print_functionality(funcs: I2CFunctions) {
+    let rows = [
+        ("I2C", funcs.supports_i2c()),
+        ("SMBus Quick Command", funcs.supports_smbus_quick()),
+        ("SMBus Send Byte", funcs.supports_smbus_write_byte()),
+        ("SMBus Receive Byte", funcs.supports_smbus_read_byte()),
+        ("SMBus Write Byte", funcs.supports_smbus_write_byte_data()),
+        ("SMBus Read Byte", funcs.supports_smbus_read_byte_data()),
+        ("SMBus Write Word", funcs.supports_smbus_write_word_data()),
+        ("SMBus Read Word", funcs.supports_smbus_read_word_data()),
+        ("SMBus Process Call", funcs.supports_smbus_process_call()),
+        ("SMBus Block Write", funcs.supports_smbus_block_write()),
+        ("SMBus Block Read", funcs.supports_smbus_block_read()),
+        (
+            "SMBus Block Process Call",
+            funcs.supports_smbus_block_process_call(),
+        ),
+        ("SMBus PEC", funcs.supports_smbus_pec()),
+        ("I2C Block Write", funcs.supports_smbus_i2c_block_write()),
+        ("I2C Block Read", funcs.supports_smbus_i2c_block_read()),
+    ];
+    println!("Functionalities implemented by the adapter:");
+    for (name, supported) in &rows {
+        println!("{:<32}{}", name, if *supported { "yes" } else { "no" });
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn main() {
+    let args = Docopt::new(USAGE)
+        .and_then(|d| d.argv(args()).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    if args.get_bool("-l") {
+        list_adapters();
+        return;
+    }
+
+    let path = bus_path(args.get_str("<bus>")).unwrap_or_else(|e| exit_with(e));
+    let mut bus = LinuxI2CBus::new(&path).unwrap_or_else(|e| exit_with(e));
+
+    if args.get_bool("-F") {
+        print_functionality(bus.functionality().unwrap_or_else(|e| exit_with(e)));
+        return;
+    }
+
+    let (mut first, mut last) = if args.get_bool("-a") {
+        (0x00, 0x7F)
+    } else {
+        (0x08, 0x77)
+    };
+    if !args.get_str("<first>").is_empty() {
+        first = parse_address(args.get_str("<first>")).unwrap_or_else(|e| exit_with(e));
+        last = parse_address(args.get_str("<last>")).unwrap_or_else(|e| exit_with(e));
+        if last < first {
+            exit_with("last address is lower than first address");
+        }
+    }
+
+    let mode = if args.get_bool("-q") {
+        I2CScanMode::QuickWrite
+    } else if args.get_bool("-r") {
+        I2CScanMode::ReadByte
+    } else {
+        I2CScanMode::Auto
+    };
+
+    if !args.get_bool("-y") {
+        let warning = format!(
+            "This program will probe file {}, address range 0x{:02x}-0x{:02x}.\n\
+             This can confuse your I2C bus, cause data loss and worse!",
+            path.display(),
+            first,
+            last
+        );
+        if !confirm(&warning) {
+            exit_with("aborting on user request");
+        }
+    }
+
+    let results = bus
+        .scan_range(mode, first, last)
+        .unwrap_or_else(|e| exit_with(e));
+
+    println!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f");
+    for row in (0..0x80).step_by(16) {
+        print!("{:02x}:", row);
+        for address in row..row + 16 {
+            let status = results
+                .iter()
+                .find(|&&(a, _)| a == address)
+                .map(|&(_, status)| status);
+            match status {
+                Some(I2CAddressStatus::Present) => print!(" {:02x}", address),
+                Some(I2CAddressStatus::Absent) => print!(" --"),
+                Some(I2CAddressStatus::Busy) => print!(" UU"),
+                Some(I2CAddressStatus::Skipped) | None => print!("   "),
+            }
+        }
+        println!();
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate docopt;
+extern crate i2cdev;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod common;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use common::*;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use i2cdev::core::I2CDevice;
+
+use docopt::Docopt;
+use std::env::args;
+
+const USAGE: &str = "
+Dump the registers of an I2C device.
+
+Usage:
+  i2cdump [-f] [-y] [-r <range>] <bus> <chip> [<mode>]
+  i2cdump (-h | --help)
+  i2cdump --version
+
+Options:
+  -f           Force access even if a driver has claimed the device.
+  -y           Disable interactive mode.
+  -r <range>   Limit the dump to registers FIRST-LAST.
+  -h --help    Show this help text.
+  --version    Show version.
+
+<mode> is one of:
+  b    byte data (default)
+  w    word data
+  c    consecutive byte reads
+  s    SMBus block data
+  i    I2C block data
+";
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn main() {}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn parse_range(range: &str) -> Result<(u8, u8), String> {
+    let mut parts = range.splitn(2, '-');
+    let first = parse_byte(parts.next().unwrap_or(""))?;
+    let last = match parts.next() {
+        Some(last) => parse_byte(last)?,
+        None => return Err(format!("invalid range {:?}", range)),
+    };
+    if last < first {
+        return Err(format!("invalid range {:?}", range));
+    }
+    Ok((first, last))
+}
+
+/// Store a block read from `start`, dropping the bytes past `last`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn store_block(data: &mut [Option<u8>], start: u8, last: u8, block: &[u8]) {
+    let end = last as usize + 1;
+    for (slot, byte) in data[start as usize..end].iter_mut().zip(block) {
+        *slot = Some(*byte);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn print_bytes(data: &[Option<u8>], first: u8, last: u8) {
+    println!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f    0123456789abcdef");
+    for row in (0..data.len()).step_by(16) {
+        if row + 15 < first as usize || row > last as usize {
+            continue;
+        }
+        let mut ascii = String::new();
+        print!("{:02x}:", row);
+        for (register, value) in data.iter().enumerate().skip(row).take(16) {
+            if register < first as usize || register > last as usize {
+                print!("   ");
+                ascii.push(' ');
+                continue;
+            }
+            match *value {
+                Some(byte) => {
+                    print!(" {:02x}", byte);
+                    ascii.push(printable(byte));
+                }
+                None => {
+                    print!(" XX");
+                    ascii.push('X');
+                }
+            }
+        }
+        println!("    {}", ascii);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn main() {
+    let args = Docopt::new(USAGE)
+        .and_then(|d| d.argv(args()).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let address = parse_address(args.get_str("<chip>")).unwrap_or_else(|e| exit_with(e));
+    let mode = match args.get_str("<mode>") {
+        "" => "b",
+        mode @ "b" | mode @ "w" | mode @ "c" | mode @ "s" | mode @ "i" => mode,
+        mode => exit_with(format!("invalid mode {:?}", mode)),
+    };
+    let (first, last) = match args.get_str("-r") {
+        "" => (0x00, 0xFF),
+        range => parse_range(range).unwrap_or_else(|e| exit_with(e)),
+    };
+
+    let mut dev = open_device(args.get_str("<bus>"), address, args.get_bool("-f"))
+        .unwrap_or_else(|e| exit_with(e));
+
+    if !args.get_bool("-y") {
+        let warning = format!(
+            "This program will read from chip address 0x{:02x} in mode {}.\n\
+             This can confuse your I2C bus, cause data loss and worse!",
+            address, mode
+        );
+        if !confirm(&warning) {
+            exit_with("aborting on user request");
+        }
+    }
+
+    if mode == "w" {
+        println!("     0,8  1,9  2,a  3,b  4,c  5,d  6,e  7,f");
+        for row in (0..0x100_usize).step_by(8) {
+            if row + 7 < first as usize || row > last as usize {
+                continue;
+            }
+            print!("{:02x}:", row);
+            for register in row..row + 8 {
+                if register < first as usize || register > last as usize {
+                    print!("     ");
+                    continue;
+                }
+                match dev.smbus_read_word_data(register as u8) {
+                    Ok(word) => print!(" {:04x}", word),
+                    Err(_) => print!(" XXXX"),
+                }
+            }
+            println!();
+        }
+        return;
+    }
+
+    let mut data: Vec<Option<u8>> = vec![None; 0x100];
+    match mode {
+        "b" => {
+            for register in first..=last {
+                data[register as usize] = dev.smbus_read_byte_data(register).ok();
+            }
+        }
+        "c" => {
+            if let Err(e) = dev.smbus_write_byte(first) {
+                exit_with(format!("could not set start address: {}", e));
+            }
+            for register in first..=last {
+                data[register as usize] = dev.smbus_read_byte().ok();
+            }
+        }
+        "s" => {
+            let block = dev
+                .smbus_read_block_data(first)
+                .unwrap_or_else(|e| exit_with(format!("block read failed: {}", e)));
+            store_block(&mut data, first, last, &block);
+        }
+        _ => {
+            let mut register = first as usize;
+            while register <= last as usize {
+                let len = (last as usize + 1 - register).min(32) as u8;
+                if let Ok(block) = dev.smbus_read_i2c_block_data(register as u8, len) {
+                    store_block(&mut data, register as u8, last, &block);
+                }
+                register += len as usize;
+            }
+        }
+    }
+    let last = if mode == "s" {
+        data.iter().rposition(Option::is_some).unwrap_or(0) as u8
+    } else {
+        last
+    };
+    print_bytes(&data, first, last);
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_near_the_top() {
+        let mut data = vec![None; 0x100];
+        let block: Vec<u8> = (0..32).collect();
+        store_block(&mut data, 0xF0, 0xFF, &block);
+        assert_eq!(data[0xF0], Some(0));
+        assert_eq!(data[0xFF], Some(15));
+        assert_eq!(data[0xEF], None);
+    }
+
+    #[test]
+    fn block_clamped_to_range() {
+        let mut data = vec![None; 0x100];
+        store_block(&mut data, 0x10, 0x13, &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            &data[0x10..0x15],
+            &[Some(1), Some(2), Some(3), Some(4), None]
+        );
+    }
+}
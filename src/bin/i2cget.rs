@@ -0,0 +1,118 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate docopt;
+extern crate i2cdev;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod common;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use common::*;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use i2cdev::core::I2CDevice;
+
+use docopt::Docopt;
+use std::env::args;
+
+const USAGE: &str = "
+Read a register from an I2C device.
+
+Usage:
+  i2cget [-f] [-y] <bus> <chip> [<data-address> [<mode> [<length>]]]
+  i2cget (-h | --help)
+  i2cget --version
+
+Options:
+  -f           Force access even if a driver has claimed the device.
+  -y           Disable interactive mode.
+  -h --help    Show this help text.
+  --version    Show version.
+
+<mode> is one of:
+  b    read byte data (default)
+  w    read word data
+  c    write byte, then read byte
+  s    SMBus block data
+  i    I2C block data, <length> bytes (default 32)
+
+Without <data-address>, a single byte is received from the device.
+";
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn main() {}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn print_block(block: &[u8]) {
+    let bytes: Vec<String> = block.iter().map(|b| format!("0x{:02x}", b)).collect();
+    println!("{}", bytes.join(" "));
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn main() {
+    let args = Docopt::new(USAGE)
+        .and_then(|d| d.argv(args()).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let address = parse_address(args.get_str("<chip>")).unwrap_or_else(|e| exit_with(e));
+    let register = match args.get_str("<data-address>") {
+        "" => None,
+        register => Some(parse_byte(register).unwrap_or_else(|e| exit_with(e))),
+    };
+    let mode = match args.get_str("<mode>") {
+        "" => "b",
+        mode @ "b" | mode @ "w" | mode @ "c" | mode @ "s" | mode @ "i" => mode,
+        mode => exit_with(format!("invalid mode {:?}", mode)),
+    };
+    let length = match args.get_str("<length>") {
+        "" => 32,
+        length => match parse_byte(length) {
+            Ok(length) if (1..=32).contains(&length) => length,
+            _ => exit_with("length must be between 1 and 32"),
+        },
+    };
+
+    let mut dev = open_device(args.get_str("<bus>"), address, args.get_bool("-f"))
+        .unwrap_or_else(|e| exit_with(e));
+
+    if !args.get_bool("-y") {
+        let warning = format!(
+            "This program will read from chip address 0x{:02x}.\n\
+             This can confuse your I2C bus, cause data loss and worse!",
+            address
+        );
+        if !confirm(&warning) {
+            exit_with("aborting on user request");
+        }
+    }
+
+    let result = match register {
+        None => dev.smbus_read_byte().map(|b| println!("0x{:02x}", b)),
+        Some(register) => match mode {
+            "b" => dev
+                .smbus_read_byte_data(register)
+                .map(|b| println!("0x{:02x}", b)),
+            "w" => dev
+                .smbus_read_word_data(register)
+                .map(|w| println!("0x{:04x}", w)),
+            "c" => dev
+                .smbus_write_byte(register)
+                .and_then(|_| dev.smbus_read_byte())
+                .map(|b| println!("0x{:02x}", b)),
+            "s" => dev
+                .smbus_read_block_data(register)
+                .map(|block| print_block(&block)),
+            _ => dev
+                .smbus_read_i2c_block_data(register, length)
+                .map(|block| print_block(&block)),
+        },
+    };
+    if let Err(e) = result {
+        exit_with(format!("read failed: {}", e));
+    }
+}
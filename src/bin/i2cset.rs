@@ -0,0 +1,143 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate docopt;
+extern crate i2cdev;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod common;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use common::*;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use i2cdev::core::I2CDevice;
+
+use docopt::Docopt;
+use std::env::args;
+
+const USAGE: &str = "
+Write a register of an I2C device.
+
+Usage:
+  i2cset [-f] [-y] [-r] <bus> <chip> <data-address> [<value>...]
+  i2cset (-h | --help)
+  i2cset --version
+
+Options:
+  -f           Force access even if a driver has claimed the device.
+  -y           Disable interactive mode.
+  -r           Read back the value and compare it with the one written.
+  -h --help    Show this help text.
+  --version    Show version.
+
+The last <value> may be a mode, one of:
+  c    send <data-address> as a single byte (the default without <value>)
+  b    write byte data (the default with a single <value>)
+  w    write word data
+  s    SMBus block data
+  i    I2C block data
+";
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn main() {}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn main() {
+    let args = Docopt::new(USAGE)
+        .and_then(|d| d.argv(args()).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let address = parse_address(args.get_str("<chip>")).unwrap_or_else(|e| exit_with(e));
+    let register = parse_byte(args.get_str("<data-address>")).unwrap_or_else(|e| exit_with(e));
+
+    let mut values = args.get_vec("<value>");
+    let mode = match values.last() {
+        Some(&mode @ "c") | Some(&mode @ "b") | Some(&mode @ "w") | Some(&mode @ "s")
+        | Some(&mode @ "i") => {
+            values.pop();
+            mode
+        }
+        Some(_) => "b",
+        None => "c",
+    };
+    match (mode, values.len()) {
+        ("c", 0) | ("b", 1) | ("w", 1) => (),
+        ("s", n) | ("i", n) if (1..=32).contains(&n) => (),
+        ("s", _) | ("i", _) => exit_with("block modes take between 1 and 32 values"),
+        _ => exit_with(format!("wrong number of values for mode {}", mode)),
+    }
+    let word = if mode == "w" {
+        match parse_number(values[0]) {
+            Ok(word) if word <= 0xFFFF => Some(word as u16),
+            _ => exit_with(format!("invalid word value {:?}", values[0])),
+        }
+    } else {
+        None
+    };
+    let bytes: Vec<u8> = if mode == "w" {
+        Vec::new()
+    } else {
+        values
+            .iter()
+            .map(|v| parse_byte(v).unwrap_or_else(|e| exit_with(e)))
+            .collect()
+    };
+
+    let mut dev = open_device(args.get_str("<bus>"), address, args.get_bool("-f"))
+        .unwrap_or_else(|e| exit_with(e));
+
+    if !args.get_bool("-y") {
+        let warning = format!(
+            "This program will write to chip address 0x{:02x}, data address 0x{:02x}.\n\
+             This can confuse your I2C bus, cause data loss and worse!",
+            address, register
+        );
+        if !confirm(&warning) {
+            exit_with("aborting on user request");
+        }
+    }
+
+    let result = match mode {
+        "c" => dev.smbus_write_byte(register),
+        "b" => dev.smbus_write_byte_data(register, bytes[0]),
+        "w" => dev.smbus_write_word_data(register, word.unwrap()),
+        "s" => dev.smbus_write_block_data(register, &bytes),
+        _ => dev.smbus_write_i2c_block_data(register, &bytes),
+    };
+    if let Err(e) = result {
+        exit_with(format!("write failed: {}", e));
+    }
+
+    if !args.get_bool("-r") {
+        return;
+    }
+    match mode {
+        "b" => match dev.smbus_read_byte_data(register) {
+            Ok(read) if read == bytes[0] => {
+                println!("Value 0x{:02x} written, readback matched", read)
+            }
+            Ok(read) => println!(
+                "Warning - data mismatch - wrote 0x{:02x}, read back 0x{:02x}",
+                bytes[0], read
+            ),
+            Err(e) => exit_with(format!("readback failed: {}", e)),
+        },
+        "w" => match dev.smbus_read_word_data(register) {
+            Ok(read) if Some(read) == word => {
+                println!("Value 0x{:04x} written, readback matched", read)
+            }
+            Ok(read) => println!(
+                "Warning - data mismatch - wrote 0x{:04x}, read back 0x{:04x}",
+                word.unwrap(),
+                read
+            ),
+            Err(e) => exit_with(format!("readback failed: {}", e)),
+        },
+        _ => eprintln!("Readback is not supported in mode {}", mode),
+    }
+}
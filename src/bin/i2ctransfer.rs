@@ -0,0 +1,110 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate docopt;
+extern crate i2cdev;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod common;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use common::*;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use i2cdev::core::{I2CMessage, I2CTransfer};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use i2cdev::linux::{LinuxI2CBus, LinuxI2CMessage};
+
+use docopt::Docopt;
+use std::env::args;
+
+const USAGE: &str = "
+Send a sequence of I2C messages in a single combined transfer.
+
+Usage:
+  i2ctransfer [-y] [-v] <bus> <desc>...
+  i2ctransfer (-h | --help)
+  i2ctransfer --version
+
+Options:
+  -y           Disable interactive mode.
+  -v           Print all messages, not just the data read.
+  -h --help    Show this help text.
+  --version    Show version.
+
+Each message is described by {r|w}LENGTH[@ADDRESS], followed by LENGTH data
+bytes for writes.  The address may be omitted to reuse the previous one.
+The last data byte of a write may carry a suffix to fill the rest of the
+message: '=' repeats it, '+' and '-' count up or down, 'p' generates a
+pseudo-random sequence seeded with it.
+
+Example: i2ctransfer 1 w2@0x50 0x00 0x10 r4
+";
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn main() {}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn format_bytes(data: &[u8]) -> String {
+    let bytes: Vec<String> = data.iter().map(|b| format!("0x{:02x}", b)).collect();
+    bytes.join(" ")
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn main() {
+    let args = Docopt::new(USAGE)
+        .and_then(|d| d.argv(args()).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let mut specs = parse_messages(&args.get_vec("<desc>")).unwrap_or_else(|e| exit_with(e));
+    let path = bus_path(args.get_str("<bus>")).unwrap_or_else(|e| exit_with(e));
+    let mut bus = LinuxI2CBus::new(&path).unwrap_or_else(|e| exit_with(e));
+
+    if !args.get_bool("-y") {
+        let warning = format!(
+            "This program will send {} message(s) on {}.\n\
+             This can confuse your I2C bus, cause data loss and worse!",
+            specs.len(),
+            path.display()
+        );
+        if !confirm(&warning) {
+            exit_with("aborting on user request");
+        }
+    }
+
+    {
+        let mut msgs: Vec<LinuxI2CMessage> = specs
+            .iter_mut()
+            .map(|spec| {
+                if spec.read {
+                    LinuxI2CMessage::read(&mut spec.data).with_address(spec.address)
+                } else {
+                    LinuxI2CMessage::write(&spec.data).with_address(spec.address)
+                }
+            })
+            .collect();
+        if let Err(e) = bus.transfer(&mut msgs) {
+            exit_with(format!("sending messages failed: {}", e));
+        }
+    }
+
+    for (i, spec) in specs.iter().enumerate() {
+        if args.get_bool("-v") {
+            let direction = if spec.read { 'r' } else { 'w' };
+            println!(
+                "msg {}: addr 0x{:02x}, {}, len {}, buf {}",
+                i,
+                spec.address,
+                direction,
+                spec.data.len(),
+                format_bytes(&spec.data)
+            );
+        } else if spec.read {
+            println!("{}", format_bytes(&spec.data));
+        }
+    }
+}
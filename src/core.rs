@@ -8,6 +8,42 @@
 
 use byteorder::{ByteOrder, LittleEndian};
 use std::error::Error;
+use std::io;
+
+/// Error for arguments the device or format cannot represent
+pub(crate) fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg.to_string())
+}
+
+/// Error for malformed data read from a device or storage
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Largest block allowed by the SMBus block commands
+pub(crate) const SMBUS_BLOCK_MAX: usize = 32;
+
+/// Check that `len` bytes fit in an SMBus block
+pub(crate) fn check_block_len(len: usize) -> io::Result<()> {
+    if len > SMBUS_BLOCK_MAX {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("SMBus block of {} bytes exceeds {}", len, SMBUS_BLOCK_MAX),
+        ));
+    }
+    Ok(())
+}
+
+/// Check the count byte received at the start of an SMBus block
+pub(crate) fn check_block_count(count: usize) -> io::Result<()> {
+    if count > SMBUS_BLOCK_MAX {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("SMBus block count {} exceeds {}", count, SMBUS_BLOCK_MAX),
+        ));
+    }
+    Ok(())
+}
 
 /// Interface to an I2C Slave Device from an I2C Master
 ///
@@ -117,6 +153,41 @@ pub trait I2CDevice {
     fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> Result<Vec<u8>, Self::Error>;
 }
 
+/// Interface to an I2C Slave Device with 16-bit register addresses
+///
+/// The SMBus methods of `I2CDevice` select a register with a single
+/// command byte.  Larger devices, such as EEPROMs, camera sensors and many
+/// PMICs, instead expect a 16-bit register address sent most significant
+/// byte first.
+pub trait I2CRegister16: I2CDevice {
+    /// Read consecutive registers starting at `register` to fill `data`
+    ///
+    /// The register address is written and the data read back in a single
+    /// transaction, with a repeated start in between.
+    fn read_block16(&mut self, register: u16, data: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Write consecutive registers starting at `register`
+    fn write_block16(&mut self, register: u16, values: &[u8]) -> Result<(), Self::Error> {
+        let mut buf = Vec::with_capacity(values.len() + 2);
+        buf.push((register >> 8) as u8);
+        buf.push(register as u8);
+        buf.extend_from_slice(values);
+        self.write(&buf)
+    }
+
+    /// Read a single register
+    fn read_reg16(&mut self, register: u16) -> Result<u8, Self::Error> {
+        let mut buf = [0];
+        self.read_block16(register, &mut buf)?;
+        Ok(buf[0])
+    }
+
+    /// Write a single register
+    fn write_reg16(&mut self, register: u16, value: u8) -> Result<(), Self::Error> {
+        self.write_block16(register, &[value])
+    }
+}
+
 /// Interface to an I2C Bus from an I2C Master
 ///
 /// This is used when the client wants to interact directly with the bus
@@ -147,4 +218,17 @@ pub trait I2CMessage<'a> {
 
     /// Write data to device
     fn write(data: &'a [u8]) -> Self;
+
+    /// Set the target device address for the message
+    ///
+    /// Devices which are bound to a single address may overwrite this
+    /// when the message is transferred.  The default implementation
+    /// ignores the address, which suits such devices; messages of buses
+    /// addressing several devices should override it.
+    fn with_address(self, _slave_address: u16) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
 }
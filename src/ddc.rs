@@ -0,0 +1,1413 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Display Data Channel
+//!
+//! Displays expose their EDID, a description of their identity and
+//! capabilities, as 128 byte blocks at address 0x50 of the DDC bus.  GPU
+//! drivers usually register the DDC bus of each connector as an I2C
+//! adapter.  Blocks beyond the second are reached by first writing the
+//! segment pointer at 0x30, as described by E-DDC.
+//!
+//! Displays supporting DDC/CI also accept commands at address 0x37, most
+//! notably to read and set the VCP features defined by MCCS, such as
+//! brightness or the input source.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::linux::LinuxI2CError> {
+//! use i2cdev::ddc::{DdcCi, Edid, VCP_BRIGHTNESS};
+//! use i2cdev::linux::LinuxI2CBus;
+//!
+//! let mut bus = LinuxI2CBus::new("/dev/i2c-4")?;
+//! let edid = Edid::read(&mut bus)?;
+//! println!("{} {:04x} {}", edid.manufacturer, edid.product_code,
+//!          edid.display_name.as_ref().map_or("", |name| name.as_str()));
+//! if let Some(timing) = edid.preferred_timing() {
+//!     println!("{}x{} at {:.2} Hz", timing.h_active, timing.v_active,
+//!              timing.refresh_rate());
+//! }
+//!
+//! let mut display = DdcCi::new(bus);
+//! let brightness = display.get_vcp_feature(VCP_BRIGHTNESS)?;
+//! display.set_vcp_feature(VCP_BRIGHTNESS, brightness.maximum / 2)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use core::{invalid_data, I2CTransfer};
+use shared::{read_message, write_message, write_read};
+use std::io;
+use std::mem::ManuallyDrop;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Address of the EDID on the DDC bus
+pub const EDID_ADDRESS: u16 = 0x50;
+
+/// Address of the E-DDC segment pointer
+pub const SEGMENT_POINTER_ADDRESS: u16 = 0x30;
+
+/// Size of an EDID block
+pub const EDID_BLOCK_LEN: usize = 128;
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+/// Tag of CTA-861 extension blocks
+const CTA_EXTENSION_TAG: u8 = 0x02;
+
+/// IEEE OUI of the HDMI Licensing vendor-specific data block
+const HDMI_OUI: u32 = 0x00_0C03;
+
+/// Read EDID block `block`
+///
+/// The segment pointer is only written for blocks 2 and up, as some
+/// displays do not acknowledge it.  The checksum is not verified.
+pub fn read_edid_block<T, E>(bus: &mut T, block: u8) -> Result<[u8; EDID_BLOCK_LEN], E>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+{
+    let segment = [block / 2];
+    let offset = [(block % 2) * EDID_BLOCK_LEN as u8];
+    let mut data = [0; EDID_BLOCK_LEN];
+    if segment[0] == 0 {
+        write_read(bus, EDID_ADDRESS, &offset, &mut data)?;
+    } else {
+        // see shared::write_read for the ManuallyDrop
+        let mut msgs = ManuallyDrop::new([
+            write_message(&segment, SEGMENT_POINTER_ADDRESS),
+            write_message(&offset, EDID_ADDRESS),
+            read_message(&mut data, EDID_ADDRESS),
+        ]);
+        bus.transfer(&mut msgs[..])?;
+    }
+    Ok(data)
+}
+
+/// Read the base EDID block and all of its extension blocks
+///
+/// The header and the checksum of every block are verified.
+pub fn read_edid<T, E>(bus: &mut T) -> Result<Vec<u8>, E>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: From<io::Error>,
+{
+    let base = read_edid_block(bus, 0)?;
+    check_header(&base)?;
+    check_checksum(&base)?;
+    let mut edid = base.to_vec();
+    for block in 1..=base[126] {
+        let data = read_edid_block(bus, block)?;
+        check_checksum(&data)?;
+        edid.extend_from_slice(&data);
+    }
+    Ok(edid)
+}
+
+fn check_header(block: &[u8]) -> io::Result<()> {
+    if block[..8] != EDID_HEADER {
+        return Err(invalid_data("missing EDID header"));
+    }
+    Ok(())
+}
+
+/// The bytes of a block, including the checksum, must sum to zero
+fn check_checksum(block: &[u8]) -> io::Result<()> {
+    if block.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0 {
+        return Err(invalid_data("EDID block checksum mismatch"));
+    }
+    Ok(())
+}
+
+/// A video mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mode {
+    /// Active pixels per line
+    pub width: u16,
+    /// Active lines per frame
+    pub height: u16,
+    /// Nominal vertical refresh rate in Hz, the field rate for interlaced
+    /// modes, e.g. 60 for 1080i60
+    pub refresh: u16,
+    /// Whether the mode is interlaced
+    pub interlaced: bool,
+}
+
+impl Mode {
+    const fn new(width: u16, height: u16, refresh: u16) -> Mode {
+        Mode {
+            width,
+            height,
+            refresh,
+            interlaced: false,
+        }
+    }
+
+    const fn interlaced(width: u16, height: u16, refresh: u16) -> Mode {
+        Mode {
+            width,
+            height,
+            refresh,
+            interlaced: true,
+        }
+    }
+}
+
+/// Established timings, by bit of bytes 35 to 37, most significant first
+const ESTABLISHED_TIMINGS: [Mode; 17] = [
+    Mode::new(720, 400, 70),
+    Mode::new(720, 400, 88),
+    Mode::new(640, 480, 60),
+    Mode::new(640, 480, 67),
+    Mode::new(640, 480, 72),
+    Mode::new(640, 480, 75),
+    Mode::new(800, 600, 56),
+    Mode::new(800, 600, 60),
+    Mode::new(800, 600, 72),
+    Mode::new(800, 600, 75),
+    Mode::new(832, 624, 75),
+    Mode::interlaced(1024, 768, 87),
+    Mode::new(1024, 768, 60),
+    Mode::new(1024, 768, 70),
+    Mode::new(1024, 768, 75),
+    Mode::new(1280, 1024, 75),
+    Mode::new(1152, 870, 75),
+];
+
+/// Detailed timing descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetailedTiming {
+    /// Pixel clock in kHz
+    pub pixel_clock_khz: u32,
+    /// Active pixels per line
+    pub h_active: u16,
+    /// Blanking pixels per line
+    pub h_blanking: u16,
+    /// Pixels from the end of the active area to the sync pulse
+    pub h_sync_offset: u16,
+    /// Width of the horizontal sync pulse in pixels
+    pub h_sync_width: u16,
+    /// Active lines per field
+    pub v_active: u16,
+    /// Blanking lines per field
+    pub v_blanking: u16,
+    /// Lines from the end of the active area to the sync pulse
+    pub v_sync_offset: u16,
+    /// Width of the vertical sync pulse in lines
+    pub v_sync_width: u16,
+    /// Horizontal image size in mm
+    pub h_image_mm: u16,
+    /// Vertical image size in mm
+    pub v_image_mm: u16,
+    /// Whether the timing is interlaced
+    pub interlaced: bool,
+}
+
+impl DetailedTiming {
+    /// Parse an 18 byte descriptor, if it is a detailed timing
+    fn parse(d: &[u8]) -> Option<DetailedTiming> {
+        let pixel_clock = LittleEndian::read_u16(d);
+        if pixel_clock == 0 {
+            return None;
+        }
+        let high = |byte: u8, shift: u32| u16::from(byte) << shift;
+        Some(DetailedTiming {
+            pixel_clock_khz: u32::from(pixel_clock) * 10,
+            h_active: u16::from(d[2]) | high(d[4] & 0xF0, 4),
+            h_blanking: u16::from(d[3]) | high(d[4] & 0x0F, 8),
+            v_active: u16::from(d[5]) | high(d[7] & 0xF0, 4),
+            v_blanking: u16::from(d[6]) | high(d[7] & 0x0F, 8),
+            h_sync_offset: u16::from(d[8]) | high(d[11] & 0xC0, 2),
+            h_sync_width: u16::from(d[9]) | high(d[11] & 0x30, 4),
+            v_sync_offset: u16::from(d[10] >> 4) | high(d[11] & 0x0C, 2),
+            v_sync_width: u16::from(d[10] & 0x0F) | high(d[11] & 0x03, 4),
+            h_image_mm: u16::from(d[12]) | high(d[14] & 0xF0, 4),
+            v_image_mm: u16::from(d[13]) | high(d[14] & 0x0F, 8),
+            interlaced: d[17] & 0x80 != 0,
+        })
+    }
+
+    /// Field rate in Hz
+    pub fn refresh_rate(&self) -> f64 {
+        let h_total = f64::from(self.h_active + self.h_blanking);
+        let v_total = f64::from(self.v_active + self.v_blanking);
+        f64::from(self.pixel_clock_khz) * 1000.0 / (h_total * v_total)
+    }
+
+    /// The mode of this timing
+    pub fn mode(&self) -> Mode {
+        // interlaced timings describe a single field
+        let height = if self.interlaced {
+            self.v_active * 2
+        } else {
+            self.v_active
+        };
+        Mode {
+            width: self.h_active,
+            height,
+            refresh: self.refresh_rate().round() as u16,
+            interlaced: self.interlaced,
+        }
+    }
+}
+
+/// Decode the text of a display descriptor
+fn descriptor_text(d: &[u8]) -> String {
+    let text = &d[5..18];
+    let end = text.iter().position(|&c| c == b'\n').unwrap_or(text.len());
+    String::from_utf8_lossy(&text[..end]).trim_end().to_string()
+}
+
+/// Parse a standard timing, EDID 1.3 and up
+fn standard_timing(b: &[u8]) -> Option<Mode> {
+    if (b[0] == 0x01 && b[1] == 0x01) || b[0] == 0x00 {
+        return None;
+    }
+    let width = (u16::from(b[0]) + 31) * 8;
+    let height = match b[1] >> 6 {
+        0 => width * 10 / 16,
+        1 => width * 3 / 4,
+        2 => width * 4 / 5,
+        _ => width * 9 / 16,
+    };
+    Some(Mode::new(width, height, u16::from(b[1] & 0x3F) + 60))
+}
+
+/// Video mode of a CTA-861 Video Identification Code
+///
+/// Covers VICs 1 to 64 and the 3840x2160 and 4096x2160 modes.
+pub fn vic_mode(vic: u8) -> Option<Mode> {
+    let (width, height, refresh, interlaced) = match vic {
+        1 => (640, 480, 60, false),
+        2 | 3 => (720, 480, 60, false),
+        4 => (1280, 720, 60, false),
+        5 => (1920, 1080, 60, true),
+        6 | 7 => (1440, 480, 60, true),
+        8 | 9 => (1440, 240, 60, false),
+        10 | 11 => (2880, 480, 60, true),
+        12 | 13 => (2880, 240, 60, false),
+        14 | 15 => (1440, 480, 60, false),
+        16 => (1920, 1080, 60, false),
+        17 | 18 => (720, 576, 50, false),
+        19 => (1280, 720, 50, false),
+        20 => (1920, 1080, 50, true),
+        21 | 22 => (1440, 576, 50, true),
+        23 | 24 => (1440, 288, 50, false),
+        25 | 26 => (2880, 576, 50, true),
+        27 | 28 => (2880, 288, 50, false),
+        29 | 30 => (1440, 576, 50, false),
+        31 => (1920, 1080, 50, false),
+        32 => (1920, 1080, 24, false),
+        33 => (1920, 1080, 25, false),
+        34 => (1920, 1080, 30, false),
+        35 | 36 => (2880, 480, 60, false),
+        37 | 38 => (2880, 576, 50, false),
+        39 => (1920, 1080, 50, true),
+        40 => (1920, 1080, 100, true),
+        41 => (1280, 720, 100, false),
+        42 | 43 => (720, 576, 100, false),
+        44 | 45 => (1440, 576, 100, true),
+        46 => (1920, 1080, 120, true),
+        47 => (1280, 720, 120, false),
+        48 | 49 => (720, 480, 120, false),
+        50 | 51 => (1440, 480, 120, true),
+        52 | 53 => (720, 576, 200, false),
+        54 | 55 => (1440, 576, 200, true),
+        56 | 57 => (720, 480, 240, false),
+        58 | 59 => (1440, 480, 240, true),
+        60 => (1280, 720, 24, false),
+        61 => (1280, 720, 25, false),
+        62 => (1280, 720, 30, false),
+        63 => (1920, 1080, 120, false),
+        64 => (1920, 1080, 100, false),
+        93 | 103 => (3840, 2160, 24, false),
+        94 | 104 => (3840, 2160, 25, false),
+        95 | 105 => (3840, 2160, 30, false),
+        96 | 106 => (3840, 2160, 50, false),
+        97 | 107 => (3840, 2160, 60, false),
+        98 => (4096, 2160, 24, false),
+        99 => (4096, 2160, 25, false),
+        100 => (4096, 2160, 30, false),
+        101 => (4096, 2160, 50, false),
+        102 => (4096, 2160, 60, false),
+        _ => return None,
+    };
+    Some(Mode {
+        width,
+        height,
+        refresh,
+        interlaced,
+    })
+}
+
+/// Short Video Descriptor of a CTA-861 video data block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShortVideoDescriptor {
+    /// Video Identification Code
+    pub vic: u8,
+    /// Whether this is a native mode of the display
+    pub native: bool,
+}
+
+impl ShortVideoDescriptor {
+    fn parse(byte: u8) -> ShortVideoDescriptor {
+        // the native bit only exists for VICs 1 to 64
+        if (129..=192).contains(&byte) {
+            ShortVideoDescriptor {
+                vic: byte & 0x7F,
+                native: true,
+            }
+        } else {
+            ShortVideoDescriptor {
+                vic: byte,
+                native: false,
+            }
+        }
+    }
+
+    /// The video mode, for known VICs
+    pub fn mode(&self) -> Option<Mode> {
+        vic_mode(self.vic)
+    }
+}
+
+/// Short Audio Descriptor of a CTA-861 audio data block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShortAudioDescriptor {
+    /// Audio format code, 1 for LPCM
+    pub format: u8,
+    /// Maximum number of channels
+    pub max_channels: u8,
+    /// Supported sample rates, bit 0 for 32 kHz up to bit 6 for 192 kHz
+    pub sample_rates: u8,
+    /// Format dependent byte: sample sizes for LPCM, else the bit rate
+    pub detail: u8,
+}
+
+/// CTA-861 extension block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtaExtension {
+    /// Revision of the extension
+    pub revision: u8,
+    /// The display underscans IT formats by default
+    pub underscan: bool,
+    /// The display supports basic audio
+    pub basic_audio: bool,
+    /// The display supports YCbCr 4:4:4
+    pub ycbcr444: bool,
+    /// The display supports YCbCr 4:2:2
+    pub ycbcr422: bool,
+    /// Number of native detailed timings
+    pub native_timings: u8,
+    /// Short video descriptors, in order of preference
+    pub video: Vec<ShortVideoDescriptor>,
+    /// Short audio descriptors
+    pub audio: Vec<ShortAudioDescriptor>,
+    /// Whether the block has an HDMI vendor-specific data block
+    pub hdmi: bool,
+    /// Detailed timings following the data blocks
+    pub detailed_timings: Vec<DetailedTiming>,
+}
+
+impl CtaExtension {
+    fn parse(block: &[u8]) -> io::Result<CtaExtension> {
+        let dtd_offset = block[2] as usize;
+        if dtd_offset > 127 || (dtd_offset != 0 && dtd_offset < 4) {
+            return Err(invalid_data("invalid CTA-861 detailed timing offset"));
+        }
+        let mut cta = CtaExtension {
+            revision: block[1],
+            underscan: block[3] & 0x80 != 0,
+            basic_audio: block[3] & 0x40 != 0,
+            ycbcr444: block[3] & 0x20 != 0,
+            ycbcr422: block[3] & 0x10 != 0,
+            native_timings: block[3] & 0x0F,
+            video: Vec::new(),
+            audio: Vec::new(),
+            hdmi: false,
+            detailed_timings: Vec::new(),
+        };
+        if dtd_offset == 0 {
+            return Ok(cta);
+        }
+
+        // the data block collection only exists from revision 3
+        let mut pos = 4;
+        while cta.revision >= 3 && pos < dtd_offset {
+            let tag = block[pos] >> 5;
+            let len = (block[pos] & 0x1F) as usize;
+            let end = pos + 1 + len;
+            if end > dtd_offset {
+                return Err(invalid_data("CTA-861 data block overruns"));
+            }
+            let payload = &block[pos + 1..end];
+            match tag {
+                1 => cta
+                    .audio
+                    .extend(payload.chunks(3).filter(|sad| sad.len() == 3).map(|sad| {
+                        ShortAudioDescriptor {
+                            format: sad[0] >> 3 & 0x0F,
+                            max_channels: (sad[0] & 0x07) + 1,
+                            sample_rates: sad[1] & 0x7F,
+                            detail: sad[2],
+                        }
+                    })),
+                2 => cta
+                    .video
+                    .extend(payload.iter().map(|&svd| ShortVideoDescriptor::parse(svd))),
+                3 if len >= 3 && LittleEndian::read_u24(payload) == HDMI_OUI => cta.hdmi = true,
+                _ => {}
+            }
+            pos = end;
+        }
+
+        for d in block[dtd_offset..127].chunks(18).filter(|d| d.len() == 18) {
+            match DetailedTiming::parse(d) {
+                Some(timing) => cta.detailed_timings.push(timing),
+                None => break,
+            }
+        }
+        Ok(cta)
+    }
+}
+
+/// EDID extension block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Extension {
+    /// CTA-861 extension
+    Cta861(CtaExtension),
+    /// Any other extension, as the raw block
+    Other(Vec<u8>),
+}
+
+/// Parsed EDID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edid {
+    /// Three letter PNP ID of the manufacturer
+    pub manufacturer: String,
+    /// Product code assigned by the manufacturer
+    pub product_code: u16,
+    /// Serial number, zero if unused
+    pub serial_number: u32,
+    /// Week of manufacture, 0xFF if `year` is the model year
+    pub week: u8,
+    /// Year of manufacture or model year
+    pub year: u16,
+    /// EDID version
+    pub version: u8,
+    /// EDID revision
+    pub revision: u8,
+    /// Whether the input is digital
+    pub digital: bool,
+    /// Screen width and height in cm, if known
+    pub screen_size_cm: Option<(u8, u8)>,
+    /// Supported established timings
+    pub established_timings: Vec<Mode>,
+    /// Standard timings
+    pub standard_timings: Vec<Mode>,
+    /// Detailed timings of the base block, the first one preferred
+    pub detailed_timings: Vec<DetailedTiming>,
+    /// Display product name descriptor
+    pub display_name: Option<String>,
+    /// Display serial number descriptor
+    pub display_serial: Option<String>,
+    /// Extension blocks
+    pub extensions: Vec<Extension>,
+}
+
+impl Edid {
+    /// Read and parse the EDID of the display on `bus`
+    pub fn read<T, E>(bus: &mut T) -> Result<Edid, E>
+    where
+        T: for<'x> I2CTransfer<'x, Error = E>,
+        E: From<io::Error>,
+    {
+        Ok(Edid::parse(&read_edid(bus)?)?)
+    }
+
+    /// Parse an EDID, such as one read with `read_edid` or from sysfs
+    pub fn parse(data: &[u8]) -> io::Result<Edid> {
+        let blocks = data.len() / EDID_BLOCK_LEN;
+        if blocks == 0 || blocks * EDID_BLOCK_LEN != data.len() {
+            return Err(invalid_data("EDID is not a whole number of blocks"));
+        }
+        let base = &data[..EDID_BLOCK_LEN];
+        check_header(base)?;
+        for block in data.chunks(EDID_BLOCK_LEN) {
+            check_checksum(block)?;
+        }
+
+        let id = BigEndian::read_u16(&base[8..]);
+        let manufacturer = [10, 5, 0]
+            .iter()
+            .map(|&shift| (b'A' - 1 + (id >> shift & 0x1F) as u8) as char)
+            .collect();
+
+        let mut edid = Edid {
+            manufacturer,
+            product_code: LittleEndian::read_u16(&base[10..]),
+            serial_number: LittleEndian::read_u32(&base[12..]),
+            week: base[16],
+            year: 1990 + u16::from(base[17]),
+            version: base[18],
+            revision: base[19],
+            digital: base[20] & 0x80 != 0,
+            screen_size_cm: match (base[21], base[22]) {
+                (0, _) | (_, 0) => None,
+                size => Some(size),
+            },
+            established_timings: ESTABLISHED_TIMINGS
+                .iter()
+                .enumerate()
+                .filter(|&(bit, _)| base[35 + bit / 8] & (0x80 >> (bit % 8)) != 0)
+                .map(|(_, &mode)| mode)
+                .collect(),
+            standard_timings: base[38..54].chunks(2).filter_map(standard_timing).collect(),
+            detailed_timings: Vec::new(),
+            display_name: None,
+            display_serial: None,
+            extensions: Vec::new(),
+        };
+
+        for d in base[54..126].chunks(18) {
+            if let Some(timing) = DetailedTiming::parse(d) {
+                edid.detailed_timings.push(timing);
+                continue;
+            }
+            match d[3] {
+                0xFC => edid.display_name = Some(descriptor_text(d)),
+                0xFF => edid.display_serial = Some(descriptor_text(d)),
+                _ => {}
+            }
+        }
+
+        let count = base[126] as usize;
+        for block in data[EDID_BLOCK_LEN..].chunks(EDID_BLOCK_LEN).take(count) {
+            edid.extensions.push(if block[0] == CTA_EXTENSION_TAG {
+                Extension::Cta861(CtaExtension::parse(block)?)
+            } else {
+                Extension::Other(block.to_vec())
+            });
+        }
+        Ok(edid)
+    }
+
+    /// The preferred timing, the first detailed timing of the base block
+    pub fn preferred_timing(&self) -> Option<&DetailedTiming> {
+        self.detailed_timings.first()
+    }
+
+    /// The CTA-861 extensions
+    pub fn cta_extensions(&self) -> impl Iterator<Item = &CtaExtension> + '_ {
+        self.extensions
+            .iter()
+            .filter_map(|extension| match *extension {
+                Extension::Cta861(ref cta) => Some(cta),
+                Extension::Other(_) => None,
+            })
+    }
+
+    /// All supported modes, without duplicates
+    ///
+    /// Detailed timings come first, starting with the preferred timing,
+    /// followed by the CTA-861 video modes, standard and established
+    /// timings.
+    pub fn modes(&self) -> Vec<Mode> {
+        let mut modes: Vec<Mode> = self.detailed_timings.iter().map(|t| t.mode()).collect();
+        for cta in self.cta_extensions() {
+            modes.extend(cta.detailed_timings.iter().map(|t| t.mode()));
+            modes.extend(cta.video.iter().filter_map(|svd| svd.mode()));
+        }
+        modes.extend(self.standard_timings.iter().cloned());
+        modes.extend(self.established_timings.iter().cloned());
+
+        let mut unique = Vec::with_capacity(modes.len());
+        for mode in modes {
+            if !unique.contains(&mode) {
+                unique.push(mode);
+            }
+        }
+        unique
+    }
+}
+
+/// Address of the DDC/CI interface of the display
+pub const DDC_CI_ADDRESS: u16 = 0x37;
+
+/// Source address of the host in messages to the display
+const HOST_ADDRESS: u8 = 0x51;
+
+/// Initial value of the checksum of replies, the virtual host address
+const REPLY_CHECKSUM_SEED: u8 = 0x50;
+
+const GET_VCP_FEATURE: u8 = 0x01;
+const GET_VCP_FEATURE_REPLY: u8 = 0x02;
+const SET_VCP_FEATURE: u8 = 0x03;
+const SAVE_CURRENT_SETTINGS: u8 = 0x0C;
+const CAPABILITIES_REQUEST: u8 = 0xF3;
+const CAPABILITIES_REPLY: u8 = 0xE3;
+
+/// Largest capabilities fragment, plus opcode and offset
+const MAX_REPLY_PAYLOAD: usize = 35;
+
+/// Attempts at a request answered with the null message
+const MAX_ATTEMPTS: usize = 3;
+
+/// Upper bound on the length of a capabilities string
+const MAX_CAPABILITIES_LEN: usize = 8192;
+
+/// Default delay, the longest of the DDC/CI reply and command delays
+const DEFAULT_DELAY_MS: u64 = 50;
+
+/// VCP code of the luminance control
+pub const VCP_BRIGHTNESS: u8 = 0x10;
+/// VCP code of the contrast control
+pub const VCP_CONTRAST: u8 = 0x12;
+/// VCP code of the input source select
+pub const VCP_INPUT_SOURCE: u8 = 0x60;
+/// VCP code of the audio speaker volume
+pub const VCP_AUDIO_VOLUME: u8 = 0x62;
+/// VCP code of the power mode
+pub const VCP_POWER_MODE: u8 = 0xD6;
+
+/// XOR checksum of DDC/CI messages
+fn ddc_checksum(seed: u8, bytes: &[u8]) -> u8 {
+    bytes.iter().fold(seed, |sum, &byte| sum ^ byte)
+}
+
+/// Value of a VCP feature, as returned by Get VCP Feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VcpValue {
+    /// Whether the feature is momentary, such as a degauss, rather
+    /// than a value which is set
+    pub momentary: bool,
+    /// Maximum value of a continuous feature
+    pub maximum: u16,
+    /// Current value
+    pub current: u16,
+}
+
+/// VCP feature listed in a capabilities string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VcpCapability {
+    /// VCP code
+    pub code: u8,
+    /// Allowed values of a non-continuous feature, such as input sources
+    pub values: Vec<u8>,
+}
+
+/// MCCS capabilities string
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    /// Protocol class, usually "monitor"
+    pub protocol: Option<String>,
+    /// Display technology, such as "lcd"
+    pub display_type: Option<String>,
+    /// Model name
+    pub model: Option<String>,
+    /// Supported DDC/CI commands
+    pub commands: Vec<u8>,
+    /// Supported VCP features
+    pub vcp: Vec<VcpCapability>,
+    /// MCCS version, as major and minor number
+    pub mccs_version: Option<(u8, u8)>,
+    /// Entries not listed above, as key and raw value
+    pub other: Vec<(String, String)>,
+}
+
+impl Capabilities {
+    /// Parse a capabilities string
+    pub fn parse(caps: &str) -> io::Result<Capabilities> {
+        let mut caps = caps.trim().trim_end_matches('\0').trim();
+        // the outer parentheses are missing on some displays
+        if caps.starts_with('(') && caps.ends_with(')') {
+            caps = &caps[1..caps.len() - 1];
+        }
+
+        let mut parsed = Capabilities::default();
+        for (key, value) in caps_entries(caps)? {
+            match key.as_str() {
+                "prot" => parsed.protocol = Some(value.trim().to_string()),
+                "type" => parsed.display_type = Some(value.trim().to_string()),
+                "model" => parsed.model = Some(value.trim().to_string()),
+                "cmds" => {
+                    parsed.commands = parse_hex_list(&value)?
+                        .into_iter()
+                        .map(|capability| capability.code)
+                        .collect()
+                }
+                "vcp" => parsed.vcp = parse_hex_list(&value)?,
+                "mccs_ver" => {
+                    let mut version = value.trim().splitn(2, '.').map(|n| n.parse::<u8>());
+                    parsed.mccs_version = match (version.next(), version.next()) {
+                        (Some(Ok(major)), Some(Ok(minor))) => Some((major, minor)),
+                        _ => return Err(invalid_data("invalid mccs_ver in capabilities")),
+                    };
+                }
+                _ => parsed.other.push((key, value)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// The VCP feature with `code`, if supported
+    pub fn vcp_feature(&self, code: u8) -> Option<&VcpCapability> {
+        self.vcp.iter().find(|capability| capability.code == code)
+    }
+}
+
+/// Split a capabilities string into its `key(value)` entries
+fn caps_entries(caps: &str) -> io::Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    let mut rest = caps;
+    while !rest.trim().is_empty() {
+        let open = rest
+            .find('(')
+            .ok_or_else(|| invalid_data("capabilities entry without value"))?;
+        let key = rest[..open].trim().to_string();
+        let mut depth = 0;
+        let mut close = None;
+        for (i, c) in rest[open..].char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let close = close.ok_or_else(|| invalid_data("unbalanced parentheses in capabilities"))?;
+        entries.push((key, rest[open + 1..close].to_string()));
+        rest = &rest[close + 1..];
+    }
+    Ok(entries)
+}
+
+/// Parse a list of hex codes, each optionally followed by its values
+///
+/// Codes are usually separated by spaces, but some displays omit them.
+fn parse_hex_list(list: &str) -> io::Result<Vec<VcpCapability>> {
+    let mut parsed: Vec<VcpCapability> = Vec::new();
+    let mut chars = list.chars().peekable();
+    let mut in_values = false;
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\r' | '\n' => {}
+            '(' if !in_values && !parsed.is_empty() => in_values = true,
+            ')' if in_values => in_values = false,
+            _ => {
+                let low = chars.next();
+                let byte = match (c.to_digit(16), low.and_then(|low| low.to_digit(16))) {
+                    (Some(high), Some(low)) => (high << 4 | low) as u8,
+                    _ => return Err(invalid_data("invalid hex code in capabilities")),
+                };
+                if in_values {
+                    parsed.last_mut().unwrap().values.push(byte);
+                } else {
+                    parsed.push(VcpCapability {
+                        code: byte,
+                        values: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+    if in_values {
+        return Err(invalid_data("unbalanced parentheses in capabilities"));
+    }
+    Ok(parsed)
+}
+
+/// DDC/CI interface of a display
+///
+/// Displays need time to process each message: a reply is read no sooner
+/// than the delay after its request, and consecutive commands are at
+/// least the delay apart.
+pub struct DdcCi<T> {
+    bus: T,
+    delay: Duration,
+    last_transfer: Option<Instant>,
+}
+
+impl<T> DdcCi<T> {
+    /// Control the display on `bus`
+    pub fn new(bus: T) -> DdcCi<T> {
+        DdcCi {
+            bus,
+            delay: Duration::from_millis(DEFAULT_DELAY_MS),
+            last_transfer: None,
+        }
+    }
+
+    /// Set the delay between messages, 50 ms by default
+    ///
+    /// Some displays need more than DDC/CI requires.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Get a reference to the underlying bus
+    pub fn inner(&self) -> &T {
+        &self.bus
+    }
+
+    /// Get a mutable reference to the underlying bus
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.bus
+    }
+
+    /// Unwrap the underlying bus
+    pub fn into_inner(self) -> T {
+        self.bus
+    }
+
+    /// Sleep until the delay since the last transfer has passed
+    fn wait(&self) {
+        if let Some(last) = self.last_transfer {
+            let elapsed = last.elapsed();
+            if elapsed < self.delay {
+                thread::sleep(self.delay - elapsed);
+            }
+        }
+    }
+}
+
+impl<T, E> DdcCi<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: From<io::Error>,
+{
+    fn send(&mut self, payload: &[u8]) -> Result<(), E> {
+        let mut msg = Vec::with_capacity(payload.len() + 3);
+        msg.push(HOST_ADDRESS);
+        msg.push(0x80 | payload.len() as u8);
+        msg.extend_from_slice(payload);
+        msg.push(ddc_checksum((DDC_CI_ADDRESS << 1) as u8, &msg));
+        self.wait();
+        let result = write_read(&mut self.bus, DDC_CI_ADDRESS, &msg, &mut []);
+        self.last_transfer = Some(Instant::now());
+        result
+    }
+
+    /// Read a reply, returning its payload
+    ///
+    /// An empty payload is the null message, sent by a display which has
+    /// nothing to reply yet.
+    fn receive(&mut self, len: usize) -> Result<Vec<u8>, E> {
+        let mut reply = vec![0; len + 3];
+        self.wait();
+        let result = write_read(&mut self.bus, DDC_CI_ADDRESS, &[], &mut reply);
+        self.last_transfer = Some(Instant::now());
+        result?;
+
+        let payload_len = (reply[1] & 0x7F) as usize;
+        if reply[0] != (DDC_CI_ADDRESS << 1) as u8 || reply[1] & 0x80 == 0 || payload_len > len {
+            return Err(invalid_data("malformed DDC/CI reply").into());
+        }
+        let end = 2 + payload_len;
+        if ddc_checksum(REPLY_CHECKSUM_SEED, &reply[..end]) != reply[end] {
+            return Err(invalid_data("DDC/CI reply checksum mismatch").into());
+        }
+        reply.truncate(end);
+        Ok(reply.split_off(2))
+    }
+
+    /// Send a request and read its reply, retrying null messages
+    fn request(&mut self, payload: &[u8], reply_len: usize) -> Result<Vec<u8>, E> {
+        for _ in 0..MAX_ATTEMPTS {
+            self.send(payload)?;
+            let reply = self.receive(reply_len)?;
+            if !reply.is_empty() {
+                return Ok(reply);
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::TimedOut, "display sent no DDC/CI reply").into())
+    }
+
+    /// Read the VCP feature `code`
+    pub fn get_vcp_feature(&mut self, code: u8) -> Result<VcpValue, E> {
+        let reply = self.request(&[GET_VCP_FEATURE, code], 8)?;
+        if reply.len() != 8 || reply[0] != GET_VCP_FEATURE_REPLY || reply[2] != code {
+            return Err(invalid_data("unexpected reply to Get VCP Feature").into());
+        }
+        if reply[1] != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("VCP code 0x{:02x} is not supported", code),
+            )
+            .into());
+        }
+        Ok(VcpValue {
+            momentary: reply[3] == 1,
+            maximum: BigEndian::read_u16(&reply[4..]),
+            current: BigEndian::read_u16(&reply[6..]),
+        })
+    }
+
+    /// Set the VCP feature `code` to `value`
+    ///
+    /// The display does not acknowledge the change; read the feature back
+    /// to check it.
+    pub fn set_vcp_feature(&mut self, code: u8, value: u16) -> Result<(), E> {
+        let mut payload = [SET_VCP_FEATURE, code, 0, 0];
+        BigEndian::write_u16(&mut payload[2..], value);
+        self.send(&payload)
+    }
+
+    /// Make the display store its current settings
+    pub fn save_current_settings(&mut self) -> Result<(), E> {
+        self.send(&[SAVE_CURRENT_SETTINGS])
+    }
+
+    /// Read the raw capabilities string, fragment by fragment
+    pub fn capabilities_string(&mut self) -> Result<String, E> {
+        let mut caps = Vec::new();
+        loop {
+            let offset = caps.len() as u16;
+            let mut payload = [CAPABILITIES_REQUEST, 0, 0];
+            BigEndian::write_u16(&mut payload[1..], offset);
+            let reply = self.request(&payload, MAX_REPLY_PAYLOAD)?;
+            if reply.len() < 3
+                || reply[0] != CAPABILITIES_REPLY
+                || BigEndian::read_u16(&reply[1..]) != offset
+            {
+                return Err(invalid_data("unexpected reply to Capabilities Request").into());
+            }
+            if reply.len() == 3 {
+                break;
+            }
+            caps.extend_from_slice(&reply[3..]);
+            if caps.len() > MAX_CAPABILITIES_LEN {
+                return Err(invalid_data("capabilities string too long").into());
+            }
+        }
+        Ok(String::from_utf8_lossy(&caps)
+            .trim_end_matches('\0')
+            .to_string())
+    }
+
+    /// Read and parse the capabilities string
+    pub fn capabilities(&mut self) -> Result<Capabilities, E> {
+        let caps = self.capabilities_string()?;
+        Ok(Capabilities::parse(&caps)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{I2CResult, MockI2CBus, MockI2CTarget};
+    use std::sync::{Arc, Mutex};
+
+    /// EEPROM of a display, with its segment pointer
+    struct Display {
+        edid: Vec<u8>,
+        segment: Arc<Mutex<u8>>,
+        offset: u8,
+    }
+
+    impl MockI2CTarget for Display {
+        fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+            self.offset = data[0];
+            Ok(())
+        }
+
+        fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+            // the segment pointer resets at the end of each transfer
+            let mut segment = self.segment.lock().unwrap();
+            let start = *segment as usize * 256 + self.offset as usize;
+            data.copy_from_slice(&self.edid[start..start + data.len()]);
+            *segment = 0;
+            Ok(())
+        }
+    }
+
+    struct SegmentPointer(Arc<Mutex<u8>>);
+
+    impl MockI2CTarget for SegmentPointer {
+        fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+            *self.0.lock().unwrap() = data[0];
+            Ok(())
+        }
+
+        fn handle_read(&mut self, _data: &mut [u8]) -> I2CResult<()> {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "write only"))
+        }
+    }
+
+    fn display_bus(edid: Vec<u8>) -> MockI2CBus {
+        let segment = Arc::new(Mutex::new(0));
+        let mut bus = MockI2CBus::new();
+        bus.add_target(SEGMENT_POINTER_ADDRESS, SegmentPointer(segment.clone()));
+        bus.add_target(
+            EDID_ADDRESS,
+            Display {
+                edid,
+                segment,
+                offset: 0,
+            },
+        );
+        bus
+    }
+
+    fn block(bytes: &[u8]) -> Vec<u8> {
+        let mut block = bytes.to_vec();
+        block.resize(EDID_BLOCK_LEN, 0);
+        let sum = block.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        block[127] = 0u8.wrapping_sub(sum);
+        block
+    }
+
+    fn text_descriptor(tag: u8, text: &str) -> Vec<u8> {
+        let mut d = vec![0, 0, 0, tag, 0];
+        d.extend_from_slice(text.as_bytes());
+        d.push(b'\n');
+        d.resize(18, b' ');
+        d
+    }
+
+    /// A monitor with a CTA-861 extension and a DisplayID extension
+    fn sample_edid() -> Vec<u8> {
+        let mut base = EDID_HEADER.to_vec();
+        base.extend_from_slice(&[0x10, 0xAC, 0xC3, 0xA0, 0x78, 0x56, 0x34, 0x12, 12, 30]);
+        base.extend_from_slice(&[0x01, 0x04, 0xA5, 53, 30, 0x78, 0x3A]);
+        base.extend_from_slice(&[0; 10]);
+        base.extend_from_slice(&[0x21, 0x08, 0x00]);
+        base.extend_from_slice(&[0xD1, 0xC0, 0x81, 0x80]);
+        base.extend_from_slice(&[0x01; 12]);
+        base.extend_from_slice(&[
+            0x02, 0x3A, 0x80, 0x18, 0x71, 0x38, 0x2D, 0x40, 0x58, 0x2C, 0x45, 0x00, 0x13, 0x2B,
+            0x21, 0x00, 0x00, 0x1E,
+        ]);
+        base.extend(text_descriptor(0xFC, "DELL U2720Q"));
+        base.extend(text_descriptor(0xFF, "ABC123"));
+        base.extend_from_slice(&[0, 0, 0, 0x10, 0]);
+        base.resize(126, 0);
+        base.push(2);
+
+        let mut cta = vec![0x02, 0x03, 19, 0xF1];
+        cta.extend_from_slice(&[0x44, 0x90, 0x04, 0x61, 0x05]);
+        cta.extend_from_slice(&[0x23, 0x09, 0x07, 0x07]);
+        cta.extend_from_slice(&[0x65, 0x03, 0x0C, 0x00, 0x10, 0x00]);
+        cta.extend_from_slice(&[
+            0x01, 0x1D, 0x00, 0x72, 0x51, 0xD0, 0x1E, 0x20, 0x6E, 0x28, 0x55, 0x00, 0x13, 0x2B,
+            0x21, 0x00, 0x00, 0x1E,
+        ]);
+        // 1080i60, the same mode as VIC 5
+        cta.extend_from_slice(&[
+            0x01, 0x1D, 0x80, 0x18, 0x71, 0x1C, 0x16, 0x20, 0x58, 0x2C, 0x25, 0x00, 0x13, 0x2B,
+            0x21, 0x00, 0x00, 0x9E,
+        ]);
+
+        let mut edid = block(&base);
+        edid.extend(block(&cta));
+        edid.extend(block(&[0x70, 0x12, 0x79]));
+        edid
+    }
+
+    #[test]
+    fn reads_blocks_across_segments() {
+        let edid = sample_edid();
+        let mut bus = display_bus(edid.clone());
+        assert_eq!(read_edid_block(&mut bus, 2).unwrap()[..], edid[256..384]);
+        assert_eq!(read_edid(&mut bus).unwrap(), edid);
+
+        let mut corrupt = edid;
+        corrupt[200] ^= 1;
+        let mut bus = display_bus(corrupt);
+        let err = read_edid(&mut bus).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parses_base_block() {
+        let edid = Edid::read(&mut display_bus(sample_edid())).unwrap();
+        assert_eq!(edid.manufacturer, "DEL");
+        assert_eq!(edid.product_code, 0xA0C3);
+        assert_eq!(edid.serial_number, 0x1234_5678);
+        assert_eq!((edid.week, edid.year), (12, 2020));
+        assert_eq!((edid.version, edid.revision), (1, 4));
+        assert!(edid.digital);
+        assert_eq!(edid.screen_size_cm, Some((53, 30)));
+        assert_eq!(edid.display_name.as_ref().unwrap(), "DELL U2720Q");
+        assert_eq!(edid.display_serial.as_ref().unwrap(), "ABC123");
+        assert_eq!(
+            edid.established_timings,
+            vec![
+                Mode::new(640, 480, 60),
+                Mode::new(800, 600, 60),
+                Mode::new(1024, 768, 60)
+            ]
+        );
+        assert_eq!(
+            edid.standard_timings,
+            vec![Mode::new(1920, 1080, 60), Mode::new(1280, 1024, 60)]
+        );
+
+        let preferred = edid.preferred_timing().unwrap();
+        assert_eq!(preferred.pixel_clock_khz, 148_500);
+        assert_eq!((preferred.h_active, preferred.h_blanking), (1920, 280));
+        assert_eq!((preferred.v_active, preferred.v_blanking), (1080, 45));
+        assert_eq!((preferred.h_sync_offset, preferred.h_sync_width), (88, 44));
+        assert_eq!((preferred.v_sync_offset, preferred.v_sync_width), (4, 5));
+        assert_eq!((preferred.h_image_mm, preferred.v_image_mm), (531, 299));
+        assert!((preferred.refresh_rate() - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parses_cta_extension() {
+        let edid = Edid::parse(&sample_edid()).unwrap();
+        assert_eq!(edid.extensions.len(), 2);
+        match edid.extensions[1] {
+            Extension::Other(ref block) => assert_eq!(block[0], 0x70),
+            _ => panic!("expected a raw extension"),
+        }
+
+        let cta = edid.cta_extensions().next().unwrap();
+        assert!(cta.underscan && cta.basic_audio && cta.ycbcr444 && cta.ycbcr422);
+        assert_eq!(cta.native_timings, 1);
+        assert_eq!(
+            cta.video,
+            vec![
+                ShortVideoDescriptor {
+                    vic: 16,
+                    native: true
+                },
+                ShortVideoDescriptor {
+                    vic: 4,
+                    native: false
+                },
+                ShortVideoDescriptor {
+                    vic: 97,
+                    native: false
+                },
+                ShortVideoDescriptor {
+                    vic: 5,
+                    native: false
+                },
+            ]
+        );
+        assert_eq!(
+            cta.audio,
+            vec![ShortAudioDescriptor {
+                format: 1,
+                max_channels: 2,
+                sample_rates: 0x07,
+                detail: 0x07,
+            }]
+        );
+        assert!(cta.hdmi);
+        assert_eq!(cta.detailed_timings.len(), 2);
+        assert_eq!(cta.detailed_timings[0].mode(), Mode::new(1280, 720, 60));
+        let interlaced = cta.detailed_timings[1];
+        assert!(interlaced.interlaced);
+        assert!((interlaced.refresh_rate() - 60.0).abs() < 0.1);
+        assert_eq!(interlaced.mode(), Mode::interlaced(1920, 1080, 60));
+        assert_eq!(vic_mode(5), Some(interlaced.mode()));
+
+        let modes = edid.modes();
+        assert_eq!(
+            &modes[..5],
+            &[
+                Mode::new(1920, 1080, 60),
+                Mode::new(1280, 720, 60),
+                Mode::interlaced(1920, 1080, 60),
+                Mode::new(3840, 2160, 60),
+                Mode::new(1280, 1024, 60),
+            ]
+        );
+        // the detailed timing and VIC 5 are listed once
+        let i1080 = Mode::interlaced(1920, 1080, 60);
+        assert_eq!(modes.iter().filter(|&&m| m == i1080).count(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_edid() {
+        let mut edid = sample_edid();
+        assert!(Edid::parse(&edid[..100]).is_err());
+        edid[1] = 0;
+        assert!(Edid::parse(&edid).is_err());
+    }
+
+    /// DDC/CI interface of a display
+    struct Monitor {
+        caps: Vec<u8>,
+        vcp: Vec<(u8, u16, u16)>,
+        reply: Vec<u8>,
+        busy: usize,
+    }
+
+    impl Monitor {
+        fn new() -> Monitor {
+            Monitor {
+                caps: b"(prot(monitor)type(lcd)model(U2720Q)cmds(01 02 03 0C E3 F3)\
+                        vcp(02 10 12 60( 0F 11 12) D6(01 04) E2(00 01))mccs_ver(2.1))"
+                    .to_vec(),
+                vcp: vec![(VCP_BRIGHTNESS, 100, 75), (VCP_INPUT_SOURCE, 0x12, 0x0F)],
+                reply: Vec::new(),
+                busy: 0,
+            }
+        }
+
+        fn set_reply(&mut self, payload: &[u8]) {
+            let mut reply = vec![0x6E, 0x80 | payload.len() as u8];
+            reply.extend_from_slice(payload);
+            let checksum = ddc_checksum(REPLY_CHECKSUM_SEED, &reply);
+            reply.push(checksum);
+            self.reply = reply;
+        }
+    }
+
+    impl MockI2CTarget for Monitor {
+        fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+            assert_eq!(data[0], HOST_ADDRESS);
+            assert_eq!(data[1] as usize, 0x80 | (data.len() - 3));
+            assert_eq!(ddc_checksum(0x6E, data), 0);
+            let payload = &data[2..data.len() - 1];
+            if self.busy > 0 {
+                self.busy -= 1;
+                self.set_reply(&[]);
+                return Ok(());
+            }
+            match payload[0] {
+                GET_VCP_FEATURE => {
+                    let code = payload[1];
+                    match self.vcp.iter().find(|&&(c, _, _)| c == code) {
+                        Some(&(_, max, cur)) => self.set_reply(&[
+                            GET_VCP_FEATURE_REPLY,
+                            0,
+                            code,
+                            0,
+                            (max >> 8) as u8,
+                            max as u8,
+                            (cur >> 8) as u8,
+                            cur as u8,
+                        ]),
+                        None => self.set_reply(&[GET_VCP_FEATURE_REPLY, 1, code, 0, 0, 0, 0, 0]),
+                    }
+                }
+                SET_VCP_FEATURE => {
+                    let value = BigEndian::read_u16(&payload[2..]);
+                    for feature in &mut self.vcp {
+                        if feature.0 == payload[1] {
+                            feature.2 = value;
+                        }
+                    }
+                }
+                CAPABILITIES_REQUEST => {
+                    let offset = BigEndian::read_u16(&payload[1..]) as usize;
+                    let end = ::std::cmp::min(offset + 32, self.caps.len());
+                    let mut fragment = payload.to_vec();
+                    fragment[0] = CAPABILITIES_REPLY;
+                    fragment.extend_from_slice(&self.caps[offset..end]);
+                    self.set_reply(&fragment);
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+            for (i, byte) in data.iter_mut().enumerate() {
+                *byte = *self.reply.get(i).unwrap_or(&0);
+            }
+            Ok(())
+        }
+    }
+
+    fn monitor(monitor: Monitor) -> DdcCi<MockI2CBus> {
+        let mut bus = MockI2CBus::new();
+        bus.add_target(DDC_CI_ADDRESS, monitor);
+        DdcCi::new(bus).with_delay(Duration::from_millis(0))
+    }
+
+    #[test]
+    fn get_and_set_vcp() {
+        let mut display = monitor(Monitor::new());
+        assert_eq!(
+            display.get_vcp_feature(VCP_BRIGHTNESS).unwrap(),
+            VcpValue {
+                momentary: false,
+                maximum: 100,
+                current: 75
+            }
+        );
+        display.set_vcp_feature(VCP_BRIGHTNESS, 30).unwrap();
+        assert_eq!(display.get_vcp_feature(VCP_BRIGHTNESS).unwrap().current, 30);
+        let err = display.get_vcp_feature(VCP_CONTRAST).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // null messages are retried a few times
+        let mut display = monitor(Monitor {
+            busy: 2,
+            ..Monitor::new()
+        });
+        assert_eq!(
+            display.get_vcp_feature(VCP_INPUT_SOURCE).unwrap().current,
+            0x0F
+        );
+        let mut display = monitor(Monitor {
+            busy: MAX_ATTEMPTS,
+            ..Monitor::new()
+        });
+        let err = display.get_vcp_feature(VCP_INPUT_SOURCE).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn commands_are_delayed() {
+        let mut bus = MockI2CBus::new();
+        bus.add_target(DDC_CI_ADDRESS, Monitor::new());
+        let mut display = DdcCi::new(bus).with_delay(Duration::from_millis(20));
+        let start = Instant::now();
+        display.get_vcp_feature(VCP_BRIGHTNESS).unwrap();
+        display.set_vcp_feature(VCP_BRIGHTNESS, 10).unwrap();
+        // reply delay, then the delay before the next command
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn capabilities() {
+        let mut display = monitor(Monitor::new());
+        let caps = display.capabilities().unwrap();
+        assert_eq!(caps.protocol.as_ref().unwrap(), "monitor");
+        assert_eq!(caps.display_type.as_ref().unwrap(), "lcd");
+        assert_eq!(caps.model.as_ref().unwrap(), "U2720Q");
+        assert_eq!(caps.commands, vec![0x01, 0x02, 0x03, 0x0C, 0xE3, 0xF3]);
+        assert_eq!(caps.mccs_version, Some((2, 1)));
+        assert_eq!(caps.vcp.len(), 6);
+        assert_eq!(
+            caps.vcp_feature(VCP_INPUT_SOURCE).unwrap().values,
+            vec![0x0F, 0x11, 0x12]
+        );
+        assert!(caps.vcp_feature(VCP_BRIGHTNESS).unwrap().values.is_empty());
+        assert!(caps.vcp_feature(VCP_CONTRAST).is_some());
+    }
+
+    #[test]
+    fn parses_capabilities_quirks() {
+        // no outer parentheses, no spaces between codes, unknown entries
+        let caps =
+            Capabilities::parse("prot(monitor) vcp(021012D6(0104)) asset_eep(40)\0").unwrap();
+        assert_eq!(caps.vcp.len(), 4);
+        assert_eq!(caps.vcp_feature(VCP_POWER_MODE).unwrap().values, vec![1, 4]);
+        assert_eq!(
+            caps.other,
+            vec![("asset_eep".to_string(), "40".to_string())]
+        );
+
+        assert!(Capabilities::parse("(vcp(10 12)").is_err());
+        assert!(Capabilities::parse("(vcp(1G))").is_err());
+        assert!(Capabilities::parse("(mccs_ver(two))").is_err());
+    }
+}
@@ -0,0 +1,457 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! 24Cxx family EEPROMs
+//!
+//! The `Eeprom` driver presents the memory of an AT24 compatible EEPROM
+//! as a seekable stream, taking care of the details which differ between
+//! chip sizes:
+//!
+//! * Chips up to 2 KiB are addressed with a single byte, larger chips
+//!   with two bytes.
+//! * Address bits which do not fit in the address bytes select the slave
+//!   address, so e.g. a 24C16 occupies the eight addresses 0x50-0x57.
+//! * Writes wrap around within a page, so they are split at page
+//!   boundaries.
+//! * The chip does not acknowledge its address while it completes a
+//!   write, so every page write is followed by acknowledge polling.
+//!
+//! ```rust,no_run
+//! # fn run() -> std::io::Result<()> {
+//! use i2cdev::eeprom::{Eeprom, EepromChip};
+//! use i2cdev::linux::LinuxI2CBus;
+//! use std::io::{Read, Seek, SeekFrom, Write};
+//!
+//! let bus = LinuxI2CBus::new("/dev/i2c-1")?;
+//! let mut eeprom = Eeprom::new(bus, 0x50, EepromChip::AT24C256);
+//! eeprom.seek(SeekFrom::Start(0x100))?;
+//! eeprom.write_all(b"board rev B")?;
+//!
+//! let mut id = [0; 11];
+//! eeprom.seek(SeekFrom::Start(0x100))?;
+//! eeprom.read_exact(&mut id)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::I2CTransfer;
+use shared::write_read;
+use std::cmp;
+use std::error::Error;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Largest read issued in a single transfer, as in the kernel's at24 driver
+const READ_CHUNK: usize = 128;
+
+/// Geometry of a 24Cxx EEPROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EepromChip {
+    /// Capacity in bytes
+    pub size: usize,
+    /// Write page size in bytes
+    pub page_size: usize,
+    /// Number of address bytes sent before the data, 1 or 2
+    pub address_bytes: usize,
+}
+
+impl EepromChip {
+    /// 24C01, 128 bytes
+    pub const AT24C01: EepromChip = EepromChip::new(128, 8, 1);
+    /// 24C02, 256 bytes
+    pub const AT24C02: EepromChip = EepromChip::new(256, 8, 1);
+    /// 24C04, 512 bytes
+    pub const AT24C04: EepromChip = EepromChip::new(512, 16, 1);
+    /// 24C08, 1 KiB
+    pub const AT24C08: EepromChip = EepromChip::new(1024, 16, 1);
+    /// 24C16, 2 KiB
+    pub const AT24C16: EepromChip = EepromChip::new(2048, 16, 1);
+    /// 24C32, 4 KiB
+    pub const AT24C32: EepromChip = EepromChip::new(4096, 32, 2);
+    /// 24C64, 8 KiB
+    pub const AT24C64: EepromChip = EepromChip::new(8192, 32, 2);
+    /// 24C128, 16 KiB
+    pub const AT24C128: EepromChip = EepromChip::new(16384, 64, 2);
+    /// 24C256, 32 KiB
+    pub const AT24C256: EepromChip = EepromChip::new(32768, 64, 2);
+    /// 24C512, 64 KiB
+    pub const AT24C512: EepromChip = EepromChip::new(65536, 128, 2);
+    /// 24CM01, 128 KiB
+    pub const AT24CM01: EepromChip = EepromChip::new(131_072, 256, 2);
+    /// 24CM02, 256 KiB
+    pub const AT24CM02: EepromChip = EepromChip::new(262_144, 256, 2);
+    /// 24CM04 class parts, 512 KiB
+    pub const AT24CM04: EepromChip = EepromChip::new(524_288, 256, 2);
+
+    /// Describe a chip not covered by the predefined constants
+    pub const fn new(size: usize, page_size: usize, address_bytes: usize) -> EepromChip {
+        EepromChip {
+            size,
+            page_size,
+            address_bytes,
+        }
+    }
+
+    /// Number of bytes reachable through a single slave address
+    fn block_size(&self) -> usize {
+        1 << (8 * self.address_bytes)
+    }
+}
+
+/// AT24 compatible EEPROM on an I2C bus
+///
+/// The bus must be able to address arbitrary slaves, so for chips which
+/// occupy several addresses (24C04, 24C08, 24C16 and 24CM0x) it should be
+/// a `LinuxI2CBus` rather than a `LinuxI2CDevice`.
+pub struct Eeprom<T> {
+    bus: T,
+    address: u16,
+    chip: EepromChip,
+    write_timeout: Duration,
+    position: usize,
+}
+
+impl<T, E> Eeprom<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: Error + From<io::Error>,
+{
+    /// Access the EEPROM at the given base address
+    ///
+    /// Chips which occupy several slave addresses are identified by the
+    /// lowest one.
+    pub fn new(bus: T, address: u16, chip: EepromChip) -> Eeprom<T> {
+        Eeprom {
+            bus,
+            address,
+            chip,
+            write_timeout: Duration::from_millis(25),
+            position: 0,
+        }
+    }
+
+    /// Set how long to poll for the completion of a page write
+    ///
+    /// The default of 25 ms covers the 5 to 10 ms write cycle of common
+    /// parts with a generous margin.
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// The geometry of the EEPROM
+    pub fn chip(&self) -> EepromChip {
+        self.chip
+    }
+
+    /// Get a reference to the underlying bus
+    pub fn inner(&self) -> &T {
+        &self.bus
+    }
+
+    /// Unwrap the underlying bus
+    pub fn into_inner(self) -> T {
+        self.bus
+    }
+
+    /// Fill `data` with the memory starting at `offset`
+    pub fn read_at(&mut self, offset: usize, data: &mut [u8]) -> Result<(), E> {
+        self.check_range(offset, data.len())?;
+        let mut done = 0;
+        while done < data.len() {
+            let current = offset + done;
+            let len = cmp::min(
+                cmp::min(data.len() - done, READ_CHUNK),
+                self.bytes_to_boundary(current, self.chip.block_size()),
+            );
+            let (address, word) = self.locate(current);
+            write_read(&mut self.bus, address, &word, &mut data[done..done + len])?;
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Write `data` to the memory starting at `offset`
+    ///
+    /// The data is written one page at a time, waiting for each write
+    /// cycle to complete.
+    pub fn write_at(&mut self, offset: usize, data: &[u8]) -> Result<(), E> {
+        self.check_range(offset, data.len())?;
+        let mut done = 0;
+        while done < data.len() {
+            let current = offset + done;
+            let len = cmp::min(
+                data.len() - done,
+                self.bytes_to_boundary(current, self.chip.page_size),
+            );
+            let (address, mut buf) = self.locate(current);
+            buf.extend_from_slice(&data[done..done + len]);
+            write_read(&mut self.bus, address, &buf, &mut [])?;
+            self.wait_for_write(current)?;
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Poll the chip until it acknowledges again after a write cycle
+    fn wait_for_write(&mut self, offset: usize) -> Result<(), E> {
+        let (address, word) = self.locate(offset);
+        let start = Instant::now();
+        loop {
+            // writing just the word address sets the address pointer,
+            // which leaves the memory untouched
+            match write_read(&mut self.bus, address, &word, &mut []) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    if start.elapsed() > self.write_timeout {
+                        return Err(e);
+                    }
+                }
+            }
+            thread::sleep(Duration::from_micros(500));
+        }
+    }
+
+    /// Slave address and word address bytes of a memory offset
+    fn locate(&self, offset: usize) -> (u16, Vec<u8>) {
+        let block_bits = 8 * self.chip.address_bytes;
+        let address = self.address | (offset >> block_bits) as u16;
+        let word = (0..self.chip.address_bytes)
+            .rev()
+            .map(|i| (offset >> (8 * i)) as u8)
+            .collect();
+        (address, word)
+    }
+
+    fn bytes_to_boundary(&self, offset: usize, boundary: usize) -> usize {
+        boundary - offset % boundary
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> Result<(), E> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.chip.size => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "access beyond the end of the EEPROM",
+            )
+            .into()),
+        }
+    }
+}
+
+impl<T, E> Read for Eeprom<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: Error + From<io::Error> + Into<io::Error>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len(), self.chip.size - self.position);
+        let position = self.position;
+        self.read_at(position, &mut buf[..len])
+            .map_err(Into::into)?;
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl<T, E> Write for Eeprom<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: Error + From<io::Error> + Into<io::Error>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len(), self.chip.size - self.position);
+        let position = self.position;
+        self.write_at(position, &buf[..len]).map_err(Into::into)?;
+        self.position += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Seeking past the end of the EEPROM is an error
+impl<T> Seek for Eeprom<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset as i64),
+            SeekFrom::End(delta) => (self.chip.size as i64).checked_add(delta),
+            SeekFrom::Current(delta) => (self.position as i64).checked_add(delta),
+        };
+        match position {
+            Some(position) if position >= 0 && position as usize <= self.chip.size => {
+                self.position = position as usize;
+                Ok(position as u64)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek outside of the EEPROM",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc;
+    use mock::{I2CResult, MockI2CBus, MockI2CTarget};
+    use std::sync::{Arc, Mutex};
+
+    /// Simulated 24Cxx EEPROM
+    struct Chip {
+        memory: Vec<u8>,
+        chip: EepromChip,
+        pointer: usize,
+        /// Polls left to NACK before the current write cycle completes
+        busy: usize,
+        /// Polls to NACK after each accepted write
+        write_cycle: usize,
+        page_writes: usize,
+    }
+
+    /// The part of the chip answering one of its slave addresses
+    struct Block {
+        chip: Arc<Mutex<Chip>>,
+        index: usize,
+    }
+
+    impl MockI2CTarget for Block {
+        fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+            let mut chip = self.chip.lock().unwrap();
+            if chip.busy > 0 {
+                chip.busy -= 1;
+                return Err(io::Error::from_raw_os_error(libc::ENXIO));
+            }
+            let (word, payload) = data.split_at(chip.chip.address_bytes);
+            let block_size = chip.chip.block_size();
+            let offset = word.iter().fold(0, |o, &b| (o << 8) | b as usize);
+            let offset = self.index * block_size + offset;
+            chip.pointer = offset;
+            if payload.is_empty() {
+                return Ok(());
+            }
+            // writes wrap around within the page
+            let page_size = chip.chip.page_size;
+            let page = offset - offset % page_size;
+            for (i, &byte) in payload.iter().enumerate() {
+                let address = page + (offset % page_size + i) % page_size;
+                chip.memory[address] = byte;
+            }
+            chip.busy = chip.write_cycle;
+            chip.page_writes += 1;
+            Ok(())
+        }
+
+        fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+            let mut chip = self.chip.lock().unwrap();
+            if chip.busy > 0 {
+                chip.busy -= 1;
+                return Err(io::Error::from_raw_os_error(libc::ENXIO));
+            }
+            for byte in data.iter_mut() {
+                let pointer = chip.pointer;
+                *byte = chip.memory[pointer];
+                chip.pointer = (pointer + 1) % chip.chip.size;
+            }
+            Ok(())
+        }
+    }
+
+    fn eeprom(chip: EepromChip) -> (Eeprom<MockI2CBus>, Arc<Mutex<Chip>>) {
+        let sim = Arc::new(Mutex::new(Chip {
+            memory: vec![0xFF; chip.size],
+            chip,
+            pointer: 0,
+            busy: 0,
+            write_cycle: 2,
+            page_writes: 0,
+        }));
+        let mut bus = MockI2CBus::new();
+        for index in 0..cmp::max(1, chip.size / chip.block_size()) {
+            let block = Block {
+                chip: sim.clone(),
+                index,
+            };
+            bus.add_target(0x50 + index as u16, block);
+        }
+        (Eeprom::new(bus, 0x50, chip), sim)
+    }
+
+    #[test]
+    fn writes_are_split_at_page_boundaries() {
+        let (mut eeprom, sim) = eeprom(EepromChip::AT24C02);
+        let data: Vec<u8> = (0..20).collect();
+        eeprom.write_at(5, &data).unwrap();
+        let sim = sim.lock().unwrap();
+        assert_eq!(&sim.memory[5..25], &data[..]);
+        // pages 0-7, 8-15, 16-23 and 24-31
+        assert_eq!(sim.page_writes, 4);
+    }
+
+    #[test]
+    fn address_bits_spill_into_the_slave_address() {
+        let (mut eeprom, sim) = eeprom(EepromChip::AT24C16);
+        let data: Vec<u8> = (0..64).collect();
+        eeprom.write_at(0x2F0, &data).unwrap();
+        assert_eq!(&sim.lock().unwrap().memory[0x2F0..0x330], &data[..]);
+
+        let mut read = vec![0; 64];
+        eeprom.read_at(0x2F0, &mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    fn two_byte_addresses() {
+        let (mut eeprom, sim) = eeprom(EepromChip::AT24CM01);
+        let data = vec![0xA5; 300];
+        eeprom.write_at(0xFF80, &data).unwrap();
+        assert_eq!(&sim.lock().unwrap().memory[0xFF80..0x100AC], &data[..]);
+
+        let mut read = vec![0; 300];
+        eeprom.read_at(0xFF80, &mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    fn ack_polling_gives_up_after_the_timeout() {
+        let (eeprom, sim) = eeprom(EepromChip::AT24C02);
+        let timeout = Duration::from_millis(20);
+        let mut eeprom = eeprom.with_write_timeout(timeout);
+        eeprom.write_at(0, &[1]).unwrap();
+        // the chip accepts the next write, then never completes it
+        sim.lock().unwrap().write_cycle = usize::MAX;
+        let start = Instant::now();
+        let err = eeprom.write_at(1, &[2]).unwrap_err();
+        let elapsed = start.elapsed();
+        assert_eq!(err.raw_os_error(), Some(libc::ENXIO));
+        assert_eq!(sim.lock().unwrap().memory[1], 2);
+        assert!(elapsed >= timeout);
+        assert!(elapsed < timeout * 10);
+    }
+
+    #[test]
+    fn io_traits() {
+        let (mut eeprom, _) = eeprom(EepromChip::AT24C01);
+        eeprom.seek(SeekFrom::End(-4)).unwrap();
+        assert_eq!(eeprom.write(b"abcdef").unwrap(), 4);
+        assert_eq!(eeprom.write(b"ef").unwrap(), 0);
+
+        eeprom.seek(SeekFrom::Current(-4)).unwrap();
+        let mut data = Vec::new();
+        eeprom.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"abcd");
+
+        assert!(eeprom.seek(SeekFrom::Start(129)).is_err());
+        assert!(eeprom.seek(SeekFrom::Current(-200)).is_err());
+        assert!(eeprom.write_at(127, &[0, 0]).is_err());
+    }
+}
@@ -10,6 +10,7 @@
 #![allow(non_camel_case_types)]
 
 use byteorder::{NativeEndian, ReadBytesExt, WriteBytesExt};
+use core::SMBUS_BLOCK_MAX;
 use nix;
 use std::io::Cursor;
 use std::mem;
@@ -31,36 +32,69 @@ pub struct i2c_msg {
 }
 
 bitflags! {
-    struct I2CFunctions: u32 {
+    /// Adapter functionality as reported by the I2C_FUNCS ioctl
+    ///
+    /// The names match the `I2C_FUNC_*` definitions in
+    /// `include/uapi/linux/i2c.h`.  For details, see
+    /// https://www.kernel.org/doc/Documentation/i2c/functionality
+    pub struct I2CFunctions: u32 {
+        /// Plain I2C-level commands (I2C_RDWR)
         const I2C_FUNC_I2C = 0x0000_0001;
+        /// Ten bit addressing
         const I2C_FUNC_10BIT_ADDR = 0x0000_0002;
-        const I2C_FUNC_PROTOCOL_MANGLING = 0x0000_0004; /* I2C_M_IGNORE_NAK etc. */
+        /// Protocol mangling flags (I2C_M_IGNORE_NAK etc.)
+        const I2C_FUNC_PROTOCOL_MANGLING = 0x0000_0004;
+        /// SMBus packet error checking
         const I2C_FUNC_SMBUS_PEC = 0x0000_0008;
-        const I2C_FUNC_NOSTART = 0x0000_0010; /* I2C_M_NOSTART */
-        const I2C_FUNC_SMBUS_BLOCK_PROC_CALL = 0x0000_8000; /* SMBus 2.0 */
+        /// Messages without a start condition (I2C_M_NOSTART)
+        const I2C_FUNC_NOSTART = 0x0000_0010;
+        /// Adapter can act as an I2C slave
+        const I2C_FUNC_SLAVE = 0x0000_0020;
+        /// SMBus 2.0 block process call
+        const I2C_FUNC_SMBUS_BLOCK_PROC_CALL = 0x0000_8000;
+        /// SMBus quick command
         const I2C_FUNC_SMBUS_QUICK = 0x0001_0000;
+        /// SMBus receive byte
         const I2C_FUNC_SMBUS_READ_BYTE = 0x0002_0000;
+        /// SMBus send byte
         const I2C_FUNC_SMBUS_WRITE_BYTE = 0x0004_0000;
+        /// SMBus read byte data
         const I2C_FUNC_SMBUS_READ_BYTE_DATA = 0x0008_0000;
+        /// SMBus write byte data
         const I2C_FUNC_SMBUS_WRITE_BYTE_DATA = 0x0010_0000;
+        /// SMBus read word data
         const I2C_FUNC_SMBUS_READ_WORD_DATA = 0x0020_0000;
+        /// SMBus write word data
         const I2C_FUNC_SMBUS_WRITE_WORD_DATA = 0x0040_0000;
+        /// SMBus process call
         const I2C_FUNC_SMBUS_PROC_CALL = 0x0080_0000;
+        /// SMBus block read
         const I2C_FUNC_SMBUS_READ_BLOCK_DATA = 0x0100_0000;
+        /// SMBus block write
         const I2C_FUNC_SMBUS_WRITE_BLOCK_DATA  = 0x0200_0000;
-        const I2C_FUNC_SMBUS_READ_I2C_BLOCK = 0x0400_0000; /* I2C-like block xfer  */
-        const I2C_FUNC_SMBUS_WRITE_I2C_BLOCK = 0x0800_0000; /* w/ 1-byte reg. addr. */
-
+        /// I2C-like block read (w/ 1-byte reg. addr.)
+        const I2C_FUNC_SMBUS_READ_I2C_BLOCK = 0x0400_0000;
+        /// I2C-like block write (w/ 1-byte reg. addr.)
+        const I2C_FUNC_SMBUS_WRITE_I2C_BLOCK = 0x0800_0000;
+        /// SMBus host notify
+        const I2C_FUNC_SMBUS_HOST_NOTIFY = 0x1000_0000;
+
+        /// SMBus receive and send byte
         const I2C_FUNC_SMBUS_BYTE = (I2CFunctions::I2C_FUNC_SMBUS_READ_BYTE.bits |
                                      I2CFunctions::I2C_FUNC_SMBUS_WRITE_BYTE.bits);
+        /// SMBus read and write byte data
         const I2C_FUNC_SMBUS_BYTE_DATA = (I2CFunctions::I2C_FUNC_SMBUS_READ_BYTE_DATA.bits |
                                           I2CFunctions::I2C_FUNC_SMBUS_WRITE_BYTE_DATA.bits);
+        /// SMBus read and write word data
         const I2C_FUNC_SMBUS_WORD_DATA = (I2CFunctions::I2C_FUNC_SMBUS_READ_WORD_DATA.bits |
                                           I2CFunctions::I2C_FUNC_SMBUS_WRITE_WORD_DATA.bits);
+        /// SMBus block read and write
         const I2C_FUNC_SMBUS_BLOCK_DATA = (I2CFunctions::I2C_FUNC_SMBUS_READ_BLOCK_DATA.bits |
                                            I2CFunctions::I2C_FUNC_SMBUS_WRITE_BLOCK_DATA.bits);
+        /// I2C-like block read and write
         const I2C_FUNC_SMBUS_I2C_BLOCK = (I2CFunctions::I2C_FUNC_SMBUS_READ_I2C_BLOCK.bits |
                                           I2CFunctions::I2C_FUNC_SMBUS_WRITE_I2C_BLOCK.bits);
+        /// Everything the kernel can emulate on top of a plain I2C adapter
         const I2C_FUNC_SMBUS_EMUL = (I2CFunctions::I2C_FUNC_SMBUS_QUICK.bits |
                                      I2CFunctions::I2C_FUNC_SMBUS_BYTE.bits |
                                      I2CFunctions::I2C_FUNC_SMBUS_BYTE_DATA.bits |
@@ -72,9 +106,6 @@ bitflags! {
     }
 }
 
-/// As specified in SMBus standard
-const I2C_SMBUS_BLOCK_MAX: u8 = 32;
-
 // In C, this is a union, but the largest item is clearly
 // the largest.  Rust does not have unions at this time,
 // so we improvise.  See https://github.com/rust-lang/rust/issues/5492
@@ -87,7 +118,7 @@ const I2C_SMBUS_BLOCK_MAX: u8 = 32;
 // };
 #[repr(C)]
 struct i2c_smbus_data {
-    block: [u8; (I2C_SMBUS_BLOCK_MAX + 2) as usize],
+    block: [u8; SMBUS_BLOCK_MAX + 2],
 }
 
 impl i2c_smbus_data {
@@ -153,13 +184,21 @@ pub struct i2c_rdwr_ioctl_data {
 mod ioctl {
     pub use super::i2c_rdwr_ioctl_data;
     pub use super::i2c_smbus_ioctl_data;
-    use super::{I2C_PEC, I2C_RDWR, I2C_SLAVE, I2C_SLAVE_FORCE, I2C_SMBUS};
+    use super::{
+        I2C_FUNCS, I2C_PEC, I2C_RDWR, I2C_RETRIES, I2C_SLAVE, I2C_SLAVE_FORCE, I2C_SMBUS,
+        I2C_TENBIT, I2C_TIMEOUT,
+    };
+    use libc::c_ulong;
 
     ioctl_write_int_bad!(set_i2c_slave_address, I2C_SLAVE);
     ioctl_write_int_bad!(set_i2c_slave_address_force, I2C_SLAVE_FORCE);
     ioctl_write_int_bad!(set_smbus_pec, I2C_PEC);
+    ioctl_write_int_bad!(set_retries, I2C_RETRIES);
+    ioctl_write_int_bad!(set_timeout, I2C_TIMEOUT);
+    ioctl_write_int_bad!(set_tenbit, I2C_TENBIT);
     ioctl_write_ptr_bad!(i2c_smbus, I2C_SMBUS, i2c_smbus_ioctl_data);
     ioctl_write_ptr_bad!(i2c_rdwr, I2C_RDWR, i2c_rdwr_ioctl_data);
+    ioctl_read_bad!(get_funcs, I2C_FUNCS, c_ulong);
 }
 
 pub fn i2c_set_slave_address(fd: RawFd, slave_address: u16) -> Result<(), nix::Error> {
@@ -183,6 +222,38 @@ pub fn i2c_set_smbus_pec(fd: RawFd, enable: bool) -> Result<(), nix::Error> {
     Ok(())
 }
 
+/// Set the adapter retry count, saturating at the largest count the
+/// kernel accepts
+pub fn i2c_set_retries(fd: RawFd, retries: u32) -> Result<(), nix::Error> {
+    unsafe {
+        ioctl::set_retries(fd, retries.min(i32::MAX as u32) as i32)?;
+    }
+    Ok(())
+}
+
+/// Set the adapter timeout, in units of 10 ms
+pub fn i2c_set_timeout(fd: RawFd, timeout: u32) -> Result<(), nix::Error> {
+    unsafe {
+        ioctl::set_timeout(fd, timeout as i32)?;
+    }
+    Ok(())
+}
+
+pub fn i2c_set_ten_bit(fd: RawFd, enable: bool) -> Result<(), nix::Error> {
+    unsafe {
+        ioctl::set_tenbit(fd, i32::from(enable))?;
+    }
+    Ok(())
+}
+
+pub fn i2c_get_functionality(fd: RawFd) -> Result<I2CFunctions, nix::Error> {
+    let mut funcs: libc::c_ulong = 0;
+    unsafe {
+        ioctl::get_funcs(fd, &mut funcs)?;
+    }
+    Ok(I2CFunctions::from_bits_truncate(funcs as u32))
+}
+
 unsafe fn i2c_smbus_access(
     fd: RawFd,
     read_write: I2CSMBusReadWrite,
@@ -350,7 +421,7 @@ pub fn i2c_smbus_read_block_data(fd: RawFd, register: u8) -> Result<Vec<u8>, I2C
     // create a vector from the data in the block starting at byte
     // 1 and ending after count bytes after that
     let count = data.block[0];
-    Ok((&data.block[1..(count + 1) as usize]).to_vec())
+    Ok(data.block[1..(count + 1) as usize].to_vec())
 }
 
 pub fn i2c_smbus_read_i2c_block_data(
@@ -373,7 +444,7 @@ pub fn i2c_smbus_read_i2c_block_data(
     // create a vector from the data in the block starting at byte
     // 1 and ending after count bytes after that
     let count = data.block[0];
-    Ok((&data.block[1..(count + 1) as usize]).to_vec())
+    Ok(data.block[1..(count + 1) as usize].to_vec())
 }
 
 #[inline]
@@ -441,7 +512,7 @@ pub fn i2c_smbus_process_call_block(
     // create a vector from the data in the block starting at byte
     // 1 and ending after count bytes after that
     let count = data.block[0];
-    Ok((&data.block[1..(count + 1) as usize]).to_vec())
+    Ok(data.block[1..(count + 1) as usize].to_vec())
 }
 
 #[inline]
@@ -0,0 +1,977 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! IPMI FRU information storage
+//!
+//! Field replaceable units such as server boards, chassis and power
+//! supplies describe themselves in an EEPROM using the IPMI Platform
+//! Management FRU Information Storage Definition v1.0.  The storage
+//! starts with a common header pointing to the optional internal use,
+//! chassis info, board info, product info and multirecord areas.
+//!
+//! `Fru` parses and generates the whole storage, verifying the
+//! checksums of every area and record.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::linux::LinuxI2CError> {
+//! use i2cdev::eeprom::{Eeprom, EepromChip};
+//! use i2cdev::fru::{Field, Fru};
+//! use i2cdev::linux::LinuxI2CBus;
+//!
+//! let bus = LinuxI2CBus::new("/dev/i2c-1")?;
+//! let mut eeprom = Eeprom::new(bus, 0x50, EepromChip::AT24C02);
+//! let mut fru = Fru::read(&mut eeprom)?;
+//! if let Some(ref mut board) = fru.board {
+//!     println!("{:?}", board.product_name.as_str());
+//!     board.serial_number = Field::from("SN0042");
+//! }
+//! fru.write(&mut eeprom)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+use core::{invalid_data, invalid_input, I2CTransfer};
+use eeprom::Eeprom;
+use std::error::Error;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Version of the storage format and of every area
+const FORMAT_VERSION: u8 = 0x01;
+
+/// Version of the multirecord header format
+const RECORD_FORMAT_VERSION: u8 = 0x02;
+
+/// Type/length byte ending the fields of an info area
+const END_OF_FIELDS: u8 = 0xC1;
+
+/// Set in the second byte of the last multirecord header
+const END_OF_LIST: u8 = 0x80;
+
+const HEADER_LEN: usize = 8;
+const RECORD_HEADER_LEN: usize = 5;
+
+/// Longest field, limited by the 6-bit length
+const MAX_FIELD_LEN: usize = 63;
+
+/// Seconds from the Unix epoch to 1996-01-01 00:00 UTC
+const EPOCH_1996: u64 = 820_454_400;
+
+/// Language code of English, for which text fields are ASCII/Latin-1
+pub const LANGUAGE_ENGLISH: u8 = 0;
+
+/// Explicit language code of English, equivalent to `LANGUAGE_ENGLISH`
+const LANGUAGE_ENGLISH_EXPLICIT: u8 = 25;
+
+fn is_english(language: u8) -> bool {
+    language == LANGUAGE_ENGLISH || language == LANGUAGE_ENGLISH_EXPLICIT
+}
+
+/// Checksum making the sum of `data` and itself zero
+pub fn checksum(data: &[u8]) -> u8 {
+    0u8.wrapping_sub(data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)))
+}
+
+/// A type/length encoded field of an info area
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    /// Binary or unspecified data
+    Binary(Vec<u8>),
+    /// BCD plus: digits, space, dash and period
+    BcdPlus(String),
+    /// 6-bit ASCII packed: uppercase ASCII from space to underscore
+    Ascii6(String),
+    /// Text, stored as Latin-1 in English areas and as UCS-2 in areas of
+    /// other languages
+    ///
+    /// Single Latin-1 characters are stored as binary, since their
+    /// type/length byte would read as the end of the fields.
+    Text(String),
+}
+
+impl Default for Field {
+    fn default() -> Field {
+        Field::Text(String::new())
+    }
+}
+
+impl<'a> From<&'a str> for Field {
+    fn from(s: &'a str) -> Field {
+        Field::Text(s.to_string())
+    }
+}
+
+impl Field {
+    /// The content of a textual field
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Field::Binary(_) => None,
+            Field::BcdPlus(ref s) | Field::Ascii6(ref s) | Field::Text(ref s) => Some(s),
+        }
+    }
+
+    /// Whether the field holds no data
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            Field::Binary(ref data) => data.is_empty(),
+            Field::BcdPlus(ref s) | Field::Ascii6(ref s) | Field::Text(ref s) => s.is_empty(),
+        }
+    }
+
+    /// Decode a field of an area in `language` from its type/length byte
+    /// and data
+    fn decode(type_length: u8, data: &[u8], language: u8) -> io::Result<Field> {
+        Ok(match type_length >> 6 {
+            0 => Field::Binary(data.to_vec()),
+            1 => Field::BcdPlus(
+                data.iter()
+                    .flat_map(|&b| vec![b >> 4, b & 0x0F])
+                    .map(|digit| match digit {
+                        0..=9 => Ok((b'0' + digit) as char),
+                        0x0A => Ok(' '),
+                        0x0B => Ok('-'),
+                        0x0C => Ok('.'),
+                        _ => Err(invalid_data("reserved BCD plus digit")),
+                    })
+                    .collect::<io::Result<String>>()?
+                    // packing pads odd lengths with a space
+                    .trim_end_matches(' ')
+                    .to_string(),
+            ),
+            2 => {
+                let mut s = String::new();
+                for chunk in data.chunks(3) {
+                    let mut bits = chunk
+                        .iter()
+                        .rev()
+                        .fold(0u32, |bits, &b| bits << 8 | u32::from(b));
+                    for _ in 0..chunk.len() * 8 / 6 {
+                        s.push((0x20 + (bits & 0x3F) as u8) as char);
+                        bits >>= 6;
+                    }
+                }
+                // packing pads the last bytes with spaces
+                Field::Ascii6(s.trim_end_matches(' ').to_string())
+            }
+            _ if is_english(language) => Field::Text(data.iter().map(|&b| b as char).collect()),
+            _ => {
+                if data.len() & 1 != 0 {
+                    return Err(invalid_data("odd length UCS-2 FRU field"));
+                }
+                Field::Text(
+                    data.chunks(2)
+                        .map(|pair| {
+                            ::std::char::from_u32(u32::from(LittleEndian::read_u16(pair)))
+                                .ok_or_else(|| invalid_data("surrogate in UCS-2 FRU field"))
+                        })
+                        .collect::<io::Result<String>>()?,
+                )
+            }
+        })
+    }
+
+    /// Encode the field of an area in `language` with its type/length byte
+    fn encode(&self, out: &mut Vec<u8>, language: u8) -> io::Result<()> {
+        let (kind, data) = match *self {
+            Field::Binary(ref data) => (0, data.clone()),
+            Field::BcdPlus(ref s) => {
+                let digits = s
+                    .chars()
+                    .map(|c| match c {
+                        '0'..='9' => Ok(c as u8 - b'0'),
+                        ' ' => Ok(0x0A),
+                        '-' => Ok(0x0B),
+                        '.' => Ok(0x0C),
+                        _ => Err(invalid_input("character not representable in BCD plus")),
+                    })
+                    .collect::<io::Result<Vec<u8>>>()?;
+                let data = digits
+                    .chunks(2)
+                    .map(|pair| pair[0] << 4 | pair.get(1).cloned().unwrap_or(0x0A))
+                    .collect();
+                (1, data)
+            }
+            Field::Ascii6(ref s) => {
+                let codes = s
+                    .chars()
+                    .map(|c| match c {
+                        ' '..='_' => Ok(c as u8 - 0x20),
+                        _ => Err(invalid_input("character not representable in 6-bit ASCII")),
+                    })
+                    .collect::<io::Result<Vec<u8>>>()?;
+                let mut data = Vec::new();
+                for chunk in codes.chunks(4) {
+                    let bits = chunk
+                        .iter()
+                        .rev()
+                        .fold(0u32, |bits, &c| bits << 6 | u32::from(c));
+                    let len = chunk.len() - chunk.len() / 4;
+                    for i in 0..len {
+                        data.push((bits >> (8 * i)) as u8);
+                    }
+                }
+                (2, data)
+            }
+            Field::Text(ref s) if is_english(language) => {
+                let data = s
+                    .chars()
+                    .map(|c| {
+                        if (c as u32) < 0x100 {
+                            Ok(c as u8)
+                        } else {
+                            Err(invalid_input("character not representable in Latin-1"))
+                        }
+                    })
+                    .collect::<io::Result<Vec<u8>>>()?;
+                (3, data)
+            }
+            Field::Text(ref s) => {
+                let mut data = Vec::new();
+                for c in s.chars() {
+                    if c as u32 > 0xFFFF {
+                        return Err(invalid_input("character not representable in UCS-2"));
+                    }
+                    let mut pair = [0; 2];
+                    LittleEndian::write_u16(&mut pair, c as u16);
+                    data.extend_from_slice(&pair);
+                }
+                (3, data)
+            }
+        };
+        if data.len() > MAX_FIELD_LEN {
+            return Err(invalid_input("FRU field longer than 63 bytes"));
+        }
+        // a single byte text field would read as the end of the fields
+        if kind == 3 && data.len() == 1 {
+            return Field::Binary(data).encode(out, language);
+        }
+        out.push(kind << 6 | data.len() as u8);
+        out.extend_from_slice(&data);
+        Ok(())
+    }
+}
+
+/// Chassis info area
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChassisInfo {
+    /// SMBIOS chassis type, e.g. 0x17 for rack mount chassis
+    pub chassis_type: u8,
+    /// Chassis part number
+    pub part_number: Field,
+    /// Chassis serial number
+    pub serial_number: Field,
+    /// Custom fields
+    pub custom: Vec<Field>,
+}
+
+/// Board info area
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BoardInfo {
+    /// Language code of the text fields, which are UCS-2 unless it is
+    /// `LANGUAGE_ENGLISH` or 25
+    pub language: u8,
+    /// Manufacturing time in minutes since 1996-01-01 00:00 UTC, 0 if
+    /// unspecified
+    pub manufactured_minutes: u32,
+    /// Board manufacturer
+    pub manufacturer: Field,
+    /// Board product name
+    pub product_name: Field,
+    /// Board serial number
+    pub serial_number: Field,
+    /// Board part number
+    pub part_number: Field,
+    /// FRU file ID
+    pub fru_file_id: Field,
+    /// Custom fields
+    pub custom: Vec<Field>,
+}
+
+impl BoardInfo {
+    /// Manufacturing time, if specified
+    pub fn manufactured(&self) -> Option<SystemTime> {
+        match self.manufactured_minutes {
+            0 => None,
+            minutes => Some(UNIX_EPOCH + Duration::from_secs(EPOCH_1996 + u64::from(minutes) * 60)),
+        }
+    }
+
+    /// Set the manufacturing time, which is rounded down to the minute
+    pub fn set_manufactured(&mut self, time: SystemTime) -> io::Result<()> {
+        let minutes = time
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|d| d.as_secs().checked_sub(EPOCH_1996))
+            .map(|secs| secs / 60)
+            .filter(|&minutes| minutes < 1 << 24)
+            .ok_or_else(|| invalid_input("manufacturing time outside the FRU range"))?;
+        self.manufactured_minutes = minutes as u32;
+        Ok(())
+    }
+}
+
+/// Product info area
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProductInfo {
+    /// Language code of the text fields, which are UCS-2 unless it is
+    /// `LANGUAGE_ENGLISH` or 25
+    pub language: u8,
+    /// Product manufacturer
+    pub manufacturer: Field,
+    /// Product name
+    pub product_name: Field,
+    /// Product part or model number
+    pub part_number: Field,
+    /// Product version
+    pub version: Field,
+    /// Product serial number
+    pub serial_number: Field,
+    /// Asset tag
+    pub asset_tag: Field,
+    /// FRU file ID
+    pub fru_file_id: Field,
+    /// Custom fields
+    pub custom: Vec<Field>,
+}
+
+/// A record of the multirecord area
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiRecord {
+    /// Record type, e.g. 0x00 for power supply information or 0xC0 to
+    /// 0xFF for OEM records
+    pub record_type: u8,
+    /// Record data, at most 255 bytes
+    pub data: Vec<u8>,
+}
+
+/// The FRU information storage of a device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fru {
+    /// Internal use area, without its version byte
+    pub internal_use: Option<Vec<u8>>,
+    /// Chassis info area
+    pub chassis: Option<ChassisInfo>,
+    /// Board info area
+    pub board: Option<BoardInfo>,
+    /// Product info area
+    pub product: Option<ProductInfo>,
+    /// Records of the multirecord area
+    pub multirecords: Vec<MultiRecord>,
+}
+
+/// Sequential reader of the fields of an info area
+struct Fields<'a> {
+    area: &'a [u8],
+    position: usize,
+    language: u8,
+}
+
+impl<'a> Fields<'a> {
+    fn next_field(&mut self) -> io::Result<Option<Field>> {
+        let type_length = *self
+            .area
+            .get(self.position)
+            .ok_or_else(|| invalid_data("FRU area without end of fields"))?;
+        if type_length == END_OF_FIELDS {
+            return Ok(None);
+        }
+        let start = self.position + 1;
+        let end = start + (type_length & 0x3F) as usize;
+        let data = self
+            .area
+            .get(start..end)
+            .ok_or_else(|| invalid_data("FRU field beyond the end of its area"))?;
+        self.position = end;
+        Field::decode(type_length, data, self.language).map(Some)
+    }
+
+    fn required(&mut self) -> io::Result<Field> {
+        self.next_field()?
+            .ok_or_else(|| invalid_data("FRU area missing a required field"))
+    }
+
+    fn custom(&mut self) -> io::Result<Vec<Field>> {
+        let mut custom = Vec::new();
+        while let Some(field) = self.next_field()? {
+            custom.push(field);
+        }
+        Ok(custom)
+    }
+}
+
+/// Validate the info area at `offset` and read its fields after `fixed`
+/// bytes of fixed data, the first of which is the language code if
+/// `has_language`
+fn info_area<'a>(
+    data: &'a [u8],
+    offset: usize,
+    fixed: usize,
+    has_language: bool,
+) -> io::Result<(&'a [u8], Fields<'a>)> {
+    let len = data
+        .get(offset + 1)
+        .map(|&len| len as usize * 8)
+        .ok_or_else(|| invalid_data("FRU area beyond the end of the storage"))?;
+    let area = data
+        .get(offset..offset + len)
+        .filter(|area| area.len() >= 2 + fixed)
+        .ok_or_else(|| invalid_data("FRU area beyond the end of the storage"))?;
+    if area[0] & 0x0F != FORMAT_VERSION {
+        return Err(invalid_data("unsupported FRU area version"));
+    }
+    if checksum(area) != 0 {
+        return Err(invalid_data("FRU area checksum mismatch"));
+    }
+    let fields = Fields {
+        area: &area[..len - 1],
+        position: 2 + fixed,
+        language: if has_language {
+            area[2]
+        } else {
+            LANGUAGE_ENGLISH
+        },
+    };
+    Ok((&area[2..2 + fixed], fields))
+}
+
+/// Pad with zeros to a multiple of 8 bytes
+fn pad(data: &mut Vec<u8>) {
+    let len = (data.len() + 7) & !7;
+    data.resize(len, 0);
+}
+
+/// Encode an info area from its fixed data and fields
+fn encode_info_area(
+    language: u8,
+    fixed: &[u8],
+    fields: &[&Field],
+    custom: &[Field],
+) -> io::Result<Vec<u8>> {
+    let mut area = vec![FORMAT_VERSION, 0];
+    area.extend_from_slice(fixed);
+    for field in fields.iter().cloned().chain(custom) {
+        field.encode(&mut area, language)?;
+    }
+    area.push(END_OF_FIELDS);
+    while area.len() % 8 != 7 {
+        area.push(0);
+    }
+    if area.len() + 1 > 255 * 8 {
+        return Err(invalid_input("FRU area too long"));
+    }
+    area[1] = ((area.len() + 1) / 8) as u8;
+    let sum = checksum(&area);
+    area.push(sum);
+    Ok(area)
+}
+
+/// Offsets in bytes of the internal use, chassis, board, product and
+/// multirecord areas, zero for absent ones
+fn parse_header(header: &[u8]) -> io::Result<[usize; 5]> {
+    if header.len() < HEADER_LEN {
+        return Err(invalid_data("FRU storage shorter than its header"));
+    }
+    if header[0] & 0x0F != FORMAT_VERSION {
+        return Err(invalid_data("unsupported FRU format version"));
+    }
+    if checksum(&header[..HEADER_LEN]) != 0 {
+        return Err(invalid_data("FRU header checksum mismatch"));
+    }
+    let mut offsets = [0; 5];
+    for (offset, &byte) in offsets.iter_mut().zip(&header[1..6]) {
+        *offset = byte as usize * 8;
+    }
+    Ok(offsets)
+}
+
+/// End of the internal use area, which has no length of its own
+fn internal_use_end(offsets: &[usize; 5], len: usize) -> usize {
+    offsets[1..]
+        .iter()
+        .cloned()
+        .filter(|&offset| offset > offsets[0])
+        .min()
+        .unwrap_or(len)
+}
+
+fn parse_record_header(header: &[u8]) -> io::Result<(u8, bool, usize, u8)> {
+    if checksum(header) != 0 {
+        return Err(invalid_data("FRU multirecord header checksum mismatch"));
+    }
+    if header[1] & 0x0F != RECORD_FORMAT_VERSION {
+        return Err(invalid_data("unsupported FRU multirecord format version"));
+    }
+    Ok((
+        header[0],
+        header[1] & END_OF_LIST != 0,
+        header[2] as usize,
+        header[3],
+    ))
+}
+
+impl Fru {
+    /// Read the FRU information storage from an EEPROM
+    ///
+    /// Only the bytes covered by the areas are read.
+    pub fn read<T, E>(eeprom: &mut Eeprom<T>) -> Result<Fru, E>
+    where
+        T: for<'x> I2CTransfer<'x, Error = E>,
+        E: Error + From<io::Error>,
+    {
+        let mut header = [0; HEADER_LEN];
+        eeprom.read_at(0, &mut header)?;
+        let offsets = parse_header(&header)?;
+        let size = eeprom.chip().size;
+
+        let mut end = HEADER_LEN;
+        if offsets[0] != 0 {
+            end = end.max(internal_use_end(&offsets, size));
+        }
+        for &offset in offsets[1..4].iter().filter(|&&offset| offset != 0) {
+            let mut area_header = [0; 2];
+            eeprom.read_at(offset, &mut area_header)?;
+            end = end.max(offset + area_header[1] as usize * 8);
+        }
+        if offsets[4] != 0 {
+            let mut offset = offsets[4];
+            loop {
+                let mut record_header = [0; RECORD_HEADER_LEN];
+                eeprom.read_at(offset, &mut record_header)?;
+                let (_, last, len, _) = parse_record_header(&record_header)?;
+                offset += RECORD_HEADER_LEN + len;
+                if last {
+                    break;
+                }
+            }
+            end = end.max(offset);
+        }
+
+        if end > size {
+            return Err(invalid_data("FRU area beyond the end of the EEPROM").into());
+        }
+        let mut data = vec![0; end];
+        eeprom.read_at(0, &mut data)?;
+        Ok(Fru::parse(&data)?)
+    }
+
+    /// Write the FRU information storage to the start of an EEPROM
+    pub fn write<T, E>(&self, eeprom: &mut Eeprom<T>) -> Result<(), E>
+    where
+        T: for<'x> I2CTransfer<'x, Error = E>,
+        E: Error + From<io::Error>,
+    {
+        let data = self.to_bytes()?;
+        eeprom.write_at(0, &data)
+    }
+
+    /// Parse the FRU information storage, verifying its checksums
+    pub fn parse(data: &[u8]) -> io::Result<Fru> {
+        let offsets = parse_header(data)?;
+        let mut fru = Fru::default();
+
+        if offsets[0] != 0 {
+            let end = internal_use_end(&offsets, data.len());
+            let area = data
+                .get(offsets[0]..end)
+                .filter(|area| !area.is_empty())
+                .ok_or_else(|| invalid_data("FRU area beyond the end of the storage"))?;
+            if area[0] & 0x0F != FORMAT_VERSION {
+                return Err(invalid_data("unsupported FRU area version"));
+            }
+            fru.internal_use = Some(area[1..].to_vec());
+        }
+
+        if offsets[1] != 0 {
+            let (fixed, mut fields) = info_area(data, offsets[1], 1, false)?;
+            fru.chassis = Some(ChassisInfo {
+                chassis_type: fixed[0],
+                part_number: fields.required()?,
+                serial_number: fields.required()?,
+                custom: fields.custom()?,
+            });
+        }
+
+        if offsets[2] != 0 {
+            let (fixed, mut fields) = info_area(data, offsets[2], 4, true)?;
+            fru.board = Some(BoardInfo {
+                language: fixed[0],
+                manufactured_minutes: LittleEndian::read_u24(&fixed[1..]),
+                manufacturer: fields.required()?,
+                product_name: fields.required()?,
+                serial_number: fields.required()?,
+                part_number: fields.required()?,
+                fru_file_id: fields.required()?,
+                custom: fields.custom()?,
+            });
+        }
+
+        if offsets[3] != 0 {
+            let (fixed, mut fields) = info_area(data, offsets[3], 1, true)?;
+            fru.product = Some(ProductInfo {
+                language: fixed[0],
+                manufacturer: fields.required()?,
+                product_name: fields.required()?,
+                part_number: fields.required()?,
+                version: fields.required()?,
+                serial_number: fields.required()?,
+                asset_tag: fields.required()?,
+                fru_file_id: fields.required()?,
+                custom: fields.custom()?,
+            });
+        }
+
+        if offsets[4] != 0 {
+            let mut offset = offsets[4];
+            loop {
+                let header = data
+                    .get(offset..offset + RECORD_HEADER_LEN)
+                    .ok_or_else(|| invalid_data("FRU multirecord beyond the end of the storage"))?;
+                let (record_type, last, len, record_checksum) = parse_record_header(header)?;
+                offset += RECORD_HEADER_LEN;
+                let record = data
+                    .get(offset..offset + len)
+                    .ok_or_else(|| invalid_data("FRU multirecord beyond the end of the storage"))?;
+                if checksum(record) != record_checksum {
+                    return Err(invalid_data("FRU multirecord checksum mismatch"));
+                }
+                fru.multirecords.push(MultiRecord {
+                    record_type,
+                    data: record.to_vec(),
+                });
+                offset += len;
+                if last {
+                    break;
+                }
+            }
+        }
+
+        Ok(fru)
+    }
+
+    /// Generate the FRU information storage
+    ///
+    /// The areas follow the header in the order of the header, each
+    /// padded to a multiple of 8 bytes.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut data = vec![0; HEADER_LEN];
+        data[0] = FORMAT_VERSION;
+        let mut areas = Vec::new();
+
+        if let Some(ref internal_use) = self.internal_use {
+            let mut area = vec![FORMAT_VERSION];
+            area.extend_from_slice(internal_use);
+            pad(&mut area);
+            areas.push((1, area));
+        }
+
+        if let Some(ref chassis) = self.chassis {
+            let area = encode_info_area(
+                LANGUAGE_ENGLISH,
+                &[chassis.chassis_type],
+                &[&chassis.part_number, &chassis.serial_number],
+                &chassis.custom,
+            )?;
+            areas.push((2, area));
+        }
+
+        if let Some(ref board) = self.board {
+            if board.manufactured_minutes >= 1 << 24 {
+                return Err(invalid_input("manufacturing time outside the FRU range"));
+            }
+            let mut fixed = [board.language, 0, 0, 0];
+            LittleEndian::write_u24(&mut fixed[1..], board.manufactured_minutes);
+            let area = encode_info_area(
+                board.language,
+                &fixed,
+                &[
+                    &board.manufacturer,
+                    &board.product_name,
+                    &board.serial_number,
+                    &board.part_number,
+                    &board.fru_file_id,
+                ],
+                &board.custom,
+            )?;
+            areas.push((3, area));
+        }
+
+        if let Some(ref product) = self.product {
+            let area = encode_info_area(
+                product.language,
+                &[product.language],
+                &[
+                    &product.manufacturer,
+                    &product.product_name,
+                    &product.part_number,
+                    &product.version,
+                    &product.serial_number,
+                    &product.asset_tag,
+                    &product.fru_file_id,
+                ],
+                &product.custom,
+            )?;
+            areas.push((4, area));
+        }
+
+        if !self.multirecords.is_empty() {
+            let mut area = Vec::new();
+            for (i, record) in self.multirecords.iter().enumerate() {
+                if record.data.len() > 255 {
+                    return Err(invalid_input("FRU multirecord longer than 255 bytes"));
+                }
+                let mut flags = RECORD_FORMAT_VERSION;
+                if i == self.multirecords.len() - 1 {
+                    flags |= END_OF_LIST;
+                }
+                let mut header = [
+                    record.record_type,
+                    flags,
+                    record.data.len() as u8,
+                    checksum(&record.data),
+                    0,
+                ];
+                header[4] = checksum(&header[..4]);
+                area.extend_from_slice(&header);
+                area.extend_from_slice(&record.data);
+            }
+            areas.push((5, area));
+        }
+
+        for (index, area) in areas {
+            let offset = data.len() / 8;
+            if offset > 0xFF {
+                return Err(invalid_input("FRU storage too long for its header"));
+            }
+            data[index] = offset as u8;
+            data.extend_from_slice(&area);
+            pad(&mut data);
+        }
+        data[HEADER_LEN - 1] = checksum(&data[..HEADER_LEN - 1]);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eeprom::EepromChip;
+    use mock::{I2CResult, MockI2CBus, MockI2CTarget};
+
+    fn decode(type_length: u8, data: &[u8]) -> Field {
+        Field::decode(type_length, data, LANGUAGE_ENGLISH).unwrap()
+    }
+
+    fn encode(field: &Field) -> Vec<u8> {
+        let mut out = Vec::new();
+        field.encode(&mut out, LANGUAGE_ENGLISH).unwrap();
+        out
+    }
+
+    fn sample() -> Fru {
+        Fru {
+            internal_use: Some(vec![0xAA, 0x55]),
+            chassis: Some(ChassisInfo {
+                chassis_type: 0x17,
+                part_number: Field::from("CH-1000"),
+                serial_number: Field::Ascii6("C0FFEE".to_string()),
+                custom: vec![],
+            }),
+            board: Some(BoardInfo {
+                language: LANGUAGE_ENGLISH,
+                manufactured_minutes: 13_000_000,
+                manufacturer: Field::from("ACME"),
+                product_name: Field::from("Mainboard X1"),
+                serial_number: Field::BcdPlus("2024-0001".to_string()),
+                part_number: Field::from("MB-X1-02"),
+                fru_file_id: Field::default(),
+                custom: vec![Field::Binary(vec![1, 2, 3])],
+            }),
+            product: Some(ProductInfo {
+                language: LANGUAGE_ENGLISH,
+                manufacturer: Field::from("ACME"),
+                product_name: Field::from("Server 1U"),
+                part_number: Field::from("SRV-1U"),
+                version: Field::from("B"),
+                serial_number: Field::from("S123456"),
+                asset_tag: Field::default(),
+                fru_file_id: Field::default(),
+                custom: vec![Field::from("extra")],
+            }),
+            multirecords: vec![
+                MultiRecord {
+                    record_type: 0x00,
+                    data: vec![0x5E, 0x01, 0x00, 0x00],
+                },
+                MultiRecord {
+                    record_type: 0xC0,
+                    data: vec![0x57, 0x01, 0x00, 0x42],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn six_bit_ascii() {
+        assert_eq!(
+            decode(0x83, &[0x29, 0xDC, 0xA6]),
+            Field::Ascii6("IPMI".to_string())
+        );
+        assert_eq!(
+            encode(&Field::Ascii6("IPMI".to_string())),
+            [0x83, 0x29, 0xDC, 0xA6]
+        );
+        let field = Field::Ascii6("HELLO_WORLD 42".to_string());
+        let encoded = encode(&field);
+        assert_eq!(decode(encoded[0], &encoded[1..]), field);
+        assert!(Field::Ascii6("lower".to_string())
+            .encode(&mut Vec::new(), LANGUAGE_ENGLISH)
+            .is_err());
+    }
+
+    #[test]
+    fn bcd_plus() {
+        assert_eq!(
+            decode(0x42, &[0x1C, 0x2B]),
+            Field::BcdPlus("1.2-".to_string())
+        );
+        assert_eq!(
+            encode(&Field::BcdPlus("12-3".to_string())),
+            [0x42, 0x12, 0xB3]
+        );
+        assert_eq!(
+            encode(&Field::BcdPlus("123".to_string())),
+            [0x42, 0x12, 0x3A]
+        );
+        assert!(Field::decode(0x41, &[0xEF], LANGUAGE_ENGLISH).is_err());
+    }
+
+    #[test]
+    fn text_and_binary() {
+        assert_eq!(encode(&Field::default()), [0xC0]);
+        assert_eq!(encode(&Field::from("ab")), [0xC2, b'a', b'b']);
+        // a one byte text field would be mistaken for the end of fields
+        assert_eq!(encode(&Field::from("B")), [0x01, b'B']);
+        assert!(Field::from("x".repeat(64).as_str())
+            .encode(&mut Vec::new(), LANGUAGE_ENGLISH)
+            .is_err());
+    }
+
+    #[test]
+    fn unicode_text_outside_english() {
+        let german = 8;
+        let text = Field::decode(0xC4, &[0xE9, 0x00, 0x41, 0x00], german).unwrap();
+        assert_eq!(text, Field::from("\u{e9}A"));
+        assert_eq!(
+            Field::decode(0xC4, &[0xE9, 0x00, 0x41, 0x00], 25).unwrap(),
+            Field::Text("\u{e9}\0A\0".to_string())
+        );
+        assert!(Field::decode(0xC3, &[0xE9, 0x00, 0x41], german).is_err());
+
+        let mut out = Vec::new();
+        Field::from("\u{3a9}").encode(&mut out, german).unwrap();
+        assert_eq!(out, [0xC2, 0xA9, 0x03]);
+        assert!(Field::from("\u{3a9}")
+            .encode(&mut Vec::new(), LANGUAGE_ENGLISH)
+            .is_err());
+
+        let fru = Fru {
+            board: Some(BoardInfo {
+                language: german,
+                manufacturer: Field::from("M\u{fc}ller"),
+                product_name: Field::from("B"),
+                ..BoardInfo::default()
+            }),
+            ..Fru::default()
+        };
+        assert_eq!(Fru::parse(&fru.to_bytes().unwrap()).unwrap(), fru);
+    }
+
+    #[test]
+    fn round_trip() {
+        let fru = sample();
+        let data = fru.to_bytes().unwrap();
+        assert_eq!(data.len() % 8, 0);
+        assert_eq!(checksum(&data[..HEADER_LEN]), 0);
+
+        let mut parsed = Fru::parse(&data).unwrap();
+        // the internal use area is padded to the next area
+        assert_eq!(parsed.internal_use, Some(vec![0xAA, 0x55, 0, 0, 0, 0, 0]));
+        parsed.internal_use = fru.internal_use.clone();
+        // one byte text is stored as binary
+        let product = parsed.product.as_mut().unwrap();
+        assert_eq!(product.version, Field::Binary(vec![b'B']));
+        product.version = Field::from("B");
+        assert_eq!(parsed, fru);
+    }
+
+    #[test]
+    fn checksums() {
+        let data = sample().to_bytes().unwrap();
+        for &offset in &[3, data[3] as usize * 8 + 10, data[5] as usize * 8 + 6] {
+            let mut corrupt = data.clone();
+            corrupt[offset] ^= 0x01;
+            let err = Fru::parse(&corrupt).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn manufacture_time() {
+        let mut board = BoardInfo::default();
+        assert_eq!(board.manufactured(), None);
+        let time = UNIX_EPOCH + Duration::from_secs(EPOCH_1996 + 3600);
+        board.set_manufactured(time).unwrap();
+        assert_eq!(board.manufactured_minutes, 60);
+        assert_eq!(board.manufactured(), Some(time));
+        assert!(board.set_manufactured(UNIX_EPOCH).is_err());
+    }
+
+    /// 24C02 without write cycle delays
+    struct Chip {
+        memory: Vec<u8>,
+        pointer: usize,
+    }
+
+    impl MockI2CTarget for Chip {
+        fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+            self.pointer = data[0] as usize;
+            for &byte in &data[1..] {
+                self.memory[self.pointer] = byte;
+                self.pointer = (self.pointer + 1) % 256;
+            }
+            Ok(())
+        }
+
+        fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+            for byte in data.iter_mut() {
+                *byte = self.memory[self.pointer];
+                self.pointer = (self.pointer + 1) % 256;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn eeprom() {
+        let mut bus = MockI2CBus::new();
+        bus.add_target(
+            0x50,
+            Chip {
+                memory: vec![0xFF; 256],
+                pointer: 0,
+            },
+        );
+        let mut eeprom = Eeprom::new(bus, 0x50, EepromChip::AT24C02);
+        let mut fru = sample();
+        fru.internal_use = None;
+        fru.product.as_mut().unwrap().version = Field::from("B1");
+        fru.write(&mut eeprom).unwrap();
+        assert_eq!(Fru::read(&mut eeprom).unwrap(), fru);
+    }
+}
@@ -101,6 +101,10 @@
 #[macro_use]
 extern crate bitflags;
 extern crate byteorder;
+#[cfg(feature = "embedded-hal")]
+extern crate embedded_hal;
+#[cfg(feature = "embedded-hal-async")]
+extern crate embedded_hal_async;
 extern crate libc;
 #[macro_use]
 extern crate nix;
@@ -115,5 +119,33 @@ pub mod core;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub mod linux;
 
+#[cfg(all(
+    feature = "embedded-hal",
+    any(target_os = "linux", target_os = "android")
+))]
+mod linux_hal;
+
+pub mod asynch;
+
+pub mod shared;
+
+pub mod regmap;
+
+pub mod eeprom;
+
+pub mod smbus;
+
+pub mod arp;
+
+pub mod pmbus;
+
+pub mod sbs;
+
+pub mod ddc;
+
+pub mod spd;
+
+pub mod fru;
+
 /// Mock I2C device
 pub mod mock;
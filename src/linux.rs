@@ -6,9 +6,10 @@
 // option.  This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use core::{I2CDevice, I2CTransfer};
+use core::{I2CDevice, I2CRegister16, I2CTransfer};
 use ffi;
 use nix;
+use smbus::{self, SmbusAlert};
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
@@ -16,10 +17,12 @@ use std::fs::OpenOptions;
 use std::io;
 use std::io::prelude::*;
 use std::os::unix::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 // Expose these core structs from this module
 pub use core::I2CMessage;
+pub use ffi::I2CFunctions;
 
 /// Concrete linux I2C device
 pub struct LinuxI2CDevice {
@@ -93,6 +96,19 @@ impl AsRawFd for LinuxI2CBus {
     }
 }
 
+/// Convert a timeout into the 10 ms units used by the I2C_TIMEOUT ioctl,
+/// rounding up so that a non-zero timeout never becomes zero
+fn timeout_to_ioctl_units(timeout: Duration) -> u32 {
+    let units = timeout
+        .checked_add(Duration::from_nanos(9_999_999))
+        .map_or(u128::MAX, |t| t.as_millis() / 10);
+    if units > i32::MAX as u128 {
+        i32::MAX as u32
+    } else {
+        units as u32
+    }
+}
+
 impl LinuxI2CDevice {
     /// Create a new I2CDevice for the specified path
     pub fn new<P: AsRef<Path>>(
@@ -168,6 +184,41 @@ impl LinuxI2CDevice {
         self.pec = enable;
         Ok(())
     }
+
+    /// Enable/Disable ten bit addressing for this device
+    ///
+    /// This must be enabled before calling `set_slave_address` with an
+    /// address above 0x7F, and only works if the adapter has
+    /// I2C_FUNC_10BIT_ADDR.
+    pub fn set_ten_bit_addressing(&mut self, enable: bool) -> Result<(), LinuxI2CError> {
+        ffi::i2c_set_ten_bit(self.as_raw_fd(), enable).map_err(From::from)
+    }
+
+    /// Set the number of times the adapter retries a transfer on
+    /// arbitration loss
+    ///
+    /// This setting is shared by every user of the adapter, not just
+    /// this device.
+    pub fn set_retries(&mut self, retries: u32) -> Result<(), LinuxI2CError> {
+        ffi::i2c_set_retries(self.as_raw_fd(), retries).map_err(From::from)
+    }
+
+    /// Set the adapter timeout
+    ///
+    /// The kernel counts in units of 10 ms, so the timeout is rounded up
+    /// to the next multiple of 10 ms.  This setting is shared by every
+    /// user of the adapter, not just this device.
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<(), LinuxI2CError> {
+        ffi::i2c_set_timeout(self.as_raw_fd(), timeout_to_ioctl_units(timeout)).map_err(From::from)
+    }
+
+    /// Query the functionality supported by the underlying adapter
+    ///
+    /// This issues the I2C_FUNCS ioctl; see `I2CFunctions` for the
+    /// meaning of the individual flags.
+    pub fn functionality(&self) -> Result<I2CFunctions, LinuxI2CError> {
+        ffi::i2c_get_functionality(self.as_raw_fd()).map_err(From::from)
+    }
 }
 
 impl I2CDevice for LinuxI2CDevice {
@@ -281,6 +332,18 @@ impl I2CDevice for LinuxI2CDevice {
     }
 }
 
+impl I2CRegister16 for LinuxI2CDevice {
+    fn read_block16(&mut self, register: u16, data: &mut [u8]) -> Result<(), LinuxI2CError> {
+        let address = [(register >> 8) as u8, register as u8];
+        let mut messages = [
+            LinuxI2CMessage::write(&address),
+            LinuxI2CMessage::read(data),
+        ];
+        self.transfer(&mut messages)?;
+        Ok(())
+    }
+}
+
 impl<'a> I2CTransfer<'a> for LinuxI2CDevice {
     type Error = LinuxI2CError;
     type Message = LinuxI2CMessage<'a>;
@@ -288,7 +351,7 @@ impl<'a> I2CTransfer<'a> for LinuxI2CDevice {
     /// Issue the provided sequence of I2C transactions
     fn transfer(&mut self, messages: &'a mut [Self::Message]) -> Result<u32, LinuxI2CError> {
         for msg in messages.iter_mut() {
-            (*msg).addr = self.slave_address;
+            msg.addr = self.slave_address;
         }
         ffi::i2c_rdwr(self.as_raw_fd(), messages).map_err(From::from)
     }
@@ -301,6 +364,176 @@ impl LinuxI2CBus {
         let bus = LinuxI2CBus { devfile: file };
         Ok(bus)
     }
+
+    /// Read the SMBus Alert Response Address until no device responds
+    ///
+    /// Call this when SMBALERT# is asserted; see `smbus::read_alerts`.
+    pub fn smbus_alerts(&mut self) -> Result<Vec<SmbusAlert>, LinuxI2CError> {
+        smbus::read_alerts(self)
+    }
+
+    /// Open the first adapter whose sysfs `name` matches `name`
+    ///
+    /// Adapter numbers are assigned at probe time and may change between
+    /// kernel versions, whereas the name reported by the adapter driver is
+    /// stable.  See `adapters()` for the list of candidates.
+    pub fn open_by_name(name: &str) -> Result<LinuxI2CBus, LinuxI2CError> {
+        match adapters()?.into_iter().find(|adapter| adapter.name == name) {
+            Some(adapter) => LinuxI2CBus::new(adapter.path),
+            None => Err(LinuxI2CError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no I2C adapter named {:?}", name),
+            ))),
+        }
+    }
+
+    /// Set the number of times the adapter retries a transfer on
+    /// arbitration loss
+    ///
+    /// This setting is shared by every user of the adapter, not just
+    /// this bus handle.
+    pub fn set_retries(&mut self, retries: u32) -> Result<(), LinuxI2CError> {
+        ffi::i2c_set_retries(self.as_raw_fd(), retries).map_err(From::from)
+    }
+
+    /// Set the adapter timeout
+    ///
+    /// The kernel counts in units of 10 ms, so the timeout is rounded up
+    /// to the next multiple of 10 ms.  This setting is shared by every
+    /// user of the adapter, not just this bus handle.
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<(), LinuxI2CError> {
+        ffi::i2c_set_timeout(self.as_raw_fd(), timeout_to_ioctl_units(timeout)).map_err(From::from)
+    }
+
+    /// Query the functionality supported by the underlying adapter
+    ///
+    /// This issues the I2C_FUNCS ioctl; see `I2CFunctions` for the
+    /// meaning of the individual flags.
+    pub fn functionality(&self) -> Result<I2CFunctions, LinuxI2CError> {
+        ffi::i2c_get_functionality(self.as_raw_fd()).map_err(From::from)
+    }
+
+    /// Probe every non-reserved 7-bit address (0x08 to 0x77) on the bus
+    ///
+    /// This is the library equivalent of `i2cdetect`.  See `scan_range`.
+    pub fn scan(
+        &mut self,
+        mode: I2CScanMode,
+    ) -> Result<Vec<(u16, I2CAddressStatus)>, LinuxI2CError> {
+        self.scan_range(mode, 0x08, 0x77)
+    }
+
+    /// Probe the 7-bit addresses from `first` to `last` (inclusive)
+    ///
+    /// Addresses claimed by a kernel driver are reported as busy without
+    /// being probed.  Probing is not without risk: some devices interpret
+    /// a quick write as a command, and a read byte can upset write-only
+    /// devices.  `I2CScanMode::Auto` uses the same heuristic as
+    /// `i2cdetect`, never writing in the EEPROM address ranges: on an
+    /// adapter without read byte support those addresses are reported as
+    /// skipped.
+    ///
+    /// Returns `EOPNOTSUPP` if the adapter supports neither probe needed by
+    /// `mode`.
+    pub fn scan_range(
+        &mut self,
+        mode: I2CScanMode,
+        first: u16,
+        last: u16,
+    ) -> Result<Vec<(u16, I2CAddressStatus)>, LinuxI2CError> {
+        let funcs = self.functionality()?;
+        let can_quick = funcs.supports_smbus_quick();
+        let can_read = funcs.supports_smbus_read_byte();
+        match mode {
+            I2CScanMode::QuickWrite if !can_quick => return Err(nix::Error::EOPNOTSUPP.into()),
+            I2CScanMode::ReadByte if !can_read => return Err(nix::Error::EOPNOTSUPP.into()),
+            I2CScanMode::Auto if !can_quick && !can_read => {
+                return Err(nix::Error::EOPNOTSUPP.into())
+            }
+            _ => (),
+        }
+
+        let fd = self.as_raw_fd();
+        let mut results = Vec::new();
+        for address in first..=last.min(0x7F) {
+            match ffi::i2c_set_slave_address(fd, address) {
+                Ok(()) => (),
+                Err(nix::Error::EBUSY) => {
+                    results.push((address, I2CAddressStatus::Busy));
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            }
+
+            let probe = match probe_for(mode, address, can_quick, can_read) {
+                Some(I2CScanMode::ReadByte) => ffi::i2c_smbus_read_byte(fd).map(drop),
+                Some(_) => ffi::i2c_smbus_write_quick(fd, false),
+                None => {
+                    results.push((address, I2CAddressStatus::Skipped));
+                    continue;
+                }
+            };
+            let status = match probe {
+                Ok(()) => I2CAddressStatus::Present,
+                Err(_) => I2CAddressStatus::Absent,
+            };
+            results.push((address, status));
+        }
+        Ok(results)
+    }
+}
+
+/// The probe `scan_range` uses for `address`, `None` if it must be skipped
+///
+/// Only `QuickWrite` and `ReadByte` are returned.
+fn probe_for(
+    mode: I2CScanMode,
+    address: u16,
+    can_quick: bool,
+    can_read: bool,
+) -> Option<I2CScanMode> {
+    match mode {
+        I2CScanMode::Auto => {
+            let eeprom_range = (0x30..=0x37).contains(&address) || (0x50..=0x5F).contains(&address);
+            if eeprom_range {
+                if can_read {
+                    Some(I2CScanMode::ReadByte)
+                } else {
+                    None
+                }
+            } else if can_quick {
+                Some(I2CScanMode::QuickWrite)
+            } else {
+                Some(I2CScanMode::ReadByte)
+            }
+        }
+        mode => Some(mode),
+    }
+}
+
+/// Probe strategy used by `LinuxI2CBus::scan`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2CScanMode {
+    /// Quick write for most addresses, read byte for 0x30-0x37 and
+    /// 0x50-0x5F where a write could corrupt an EEPROM
+    Auto,
+    /// SMBus quick write (`i2cdetect -q`)
+    QuickWrite,
+    /// SMBus receive byte (`i2cdetect -r`)
+    ReadByte,
+}
+
+/// State of an address as found by `LinuxI2CBus::scan`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2CAddressStatus {
+    /// A device acknowledged the probe
+    Present,
+    /// Nothing acknowledged the probe
+    Absent,
+    /// The address is in use by a kernel driver and was not probed
+    Busy,
+    /// The address was not probed, as no safe probe is supported
+    Skipped,
 }
 
 /// Linux I2C message
@@ -342,7 +575,7 @@ bitflags! {
 }
 
 impl<'a> I2CMessage<'a> for LinuxI2CMessage<'a> {
-    fn read(data: &'a mut [u8]) -> LinuxI2CMessage {
+    fn read(data: &'a mut [u8]) -> LinuxI2CMessage<'a> {
         Self {
             addr: 0, // will be filled later
             flags: I2CMessageFlags::READ.bits(),
@@ -351,7 +584,7 @@ impl<'a> I2CMessage<'a> for LinuxI2CMessage<'a> {
         }
     }
 
-    fn write(data: &'a [u8]) -> LinuxI2CMessage {
+    fn write(data: &'a [u8]) -> LinuxI2CMessage<'a> {
         Self {
             addr: 0, // will be filled later
             flags: I2CMessageFlags::empty().bits(),
@@ -359,6 +592,102 @@ impl<'a> I2CMessage<'a> for LinuxI2CMessage<'a> {
             buf: data.as_ptr(),
         }
     }
+
+    fn with_address(self, slave_address: u16) -> Self {
+        LinuxI2CMessage::with_address(self, slave_address)
+    }
+}
+
+impl I2CFunctions {
+    /// Adapter supports plain I2C transfers (`I2CTransfer`)
+    pub fn supports_i2c(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_I2C)
+    }
+
+    /// Adapter supports ten bit slave addresses
+    pub fn supports_ten_bit_addresses(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_10BIT_ADDR)
+    }
+
+    /// Adapter supports messages without a start condition
+    pub fn supports_no_start(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_NOSTART)
+    }
+
+    /// Adapter supports the protocol mangling message flags
+    pub fn supports_protocol_mangling(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_PROTOCOL_MANGLING)
+    }
+
+    /// Adapter supports SMBus packet error checking
+    pub fn supports_smbus_pec(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_PEC)
+    }
+
+    /// Adapter supports `smbus_write_quick`
+    pub fn supports_smbus_quick(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_QUICK)
+    }
+
+    /// Adapter supports `smbus_read_byte`
+    pub fn supports_smbus_read_byte(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_READ_BYTE)
+    }
+
+    /// Adapter supports `smbus_write_byte`
+    pub fn supports_smbus_write_byte(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_WRITE_BYTE)
+    }
+
+    /// Adapter supports `smbus_read_byte_data`
+    pub fn supports_smbus_read_byte_data(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_READ_BYTE_DATA)
+    }
+
+    /// Adapter supports `smbus_write_byte_data`
+    pub fn supports_smbus_write_byte_data(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_WRITE_BYTE_DATA)
+    }
+
+    /// Adapter supports `smbus_read_word_data`
+    pub fn supports_smbus_read_word_data(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_READ_WORD_DATA)
+    }
+
+    /// Adapter supports `smbus_write_word_data`
+    pub fn supports_smbus_write_word_data(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_WRITE_WORD_DATA)
+    }
+
+    /// Adapter supports `smbus_process_word`
+    pub fn supports_smbus_process_call(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_PROC_CALL)
+    }
+
+    /// Adapter supports `smbus_read_block_data`
+    pub fn supports_smbus_block_read(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_READ_BLOCK_DATA)
+    }
+
+    /// Adapter supports `smbus_write_block_data`
+    pub fn supports_smbus_block_write(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_WRITE_BLOCK_DATA)
+    }
+
+    /// Adapter supports `smbus_process_block`
+    pub fn supports_smbus_block_process_call(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_BLOCK_PROC_CALL)
+    }
+
+    /// Adapter supports `smbus_read_i2c_block_data`
+    pub fn supports_smbus_i2c_block_read(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_READ_I2C_BLOCK)
+    }
+
+    /// Adapter supports `smbus_write_i2c_block_data`
+    pub fn supports_smbus_i2c_block_write(&self) -> bool {
+        self.contains(I2CFunctions::I2C_FUNC_SMBUS_WRITE_I2C_BLOCK)
+    }
 }
 
 impl<'a> LinuxI2CMessage<'a> {
@@ -382,3 +711,174 @@ impl<'a> LinuxI2CMessage<'a> {
         }
     }
 }
+
+/// An I2C adapter with an i2c-dev character device, as found in sysfs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinuxI2CAdapter {
+    /// Adapter (bus) number, the `N` in `/dev/i2c-N`
+    pub number: u32,
+    /// Path of the character device, suitable for `LinuxI2CBus::new`
+    pub path: PathBuf,
+    /// Name reported by the adapter driver
+    pub name: String,
+    /// Resolved sysfs path of the device the adapter hangs off, if known
+    pub parent: Option<PathBuf>,
+    /// Whether the adapter is a channel of an I2C multiplexer
+    pub is_mux_channel: bool,
+}
+
+/// List the I2C adapters known to the running kernel
+///
+/// Only adapters exposed through i2c-dev (and therefore openable as
+/// `/dev/i2c-N`) are returned, sorted by adapter number.
+pub fn adapters() -> Result<Vec<LinuxI2CAdapter>, LinuxI2CError> {
+    adapters_in("/sys")
+}
+
+/// List the I2C adapters found below an alternative sysfs mount point
+///
+/// This walks `<sysfs_root>/class/i2c-dev` and `<sysfs_root>/bus/i2c/devices`
+/// in the same way as `adapters()`, which uses `/sys`.  The returned device
+/// paths always point into `/dev`.
+pub fn adapters_in<P: AsRef<Path>>(sysfs_root: P) -> Result<Vec<LinuxI2CAdapter>, LinuxI2CError> {
+    let sysfs_root = sysfs_root.as_ref();
+    let class_dir = sysfs_root.join("class/i2c-dev");
+    let bus_dir = sysfs_root.join("bus/i2c/devices");
+
+    let mut adapters = Vec::new();
+    for entry in class_dir.read_dir()? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = match file_name.to_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        let number = match file_name
+            .strip_prefix("i2c-")
+            .and_then(|n| n.parse::<u32>().ok())
+        {
+            Some(number) => number,
+            None => continue,
+        };
+
+        // prefer the bus view of the adapter, falling back to the device
+        // link of the class entry on kernels without it
+        let mut adapter_dir = bus_dir.join(file_name);
+        if !adapter_dir.exists() {
+            adapter_dir = entry.path().join("device");
+        }
+        let adapter_dir = adapter_dir.canonicalize().unwrap_or(adapter_dir);
+
+        let name = read_sysfs_string(&entry.path().join("name"))
+            .or_else(|_| read_sysfs_string(&adapter_dir.join("name")))?;
+        let parent = adapter_dir.parent().map(Path::to_path_buf);
+        let is_mux_channel = adapter_dir.join("mux_device").exists();
+
+        adapters.push(LinuxI2CAdapter {
+            number,
+            path: Path::new("/dev").join(file_name),
+            name,
+            parent,
+            is_mux_channel,
+        });
+    }
+    adapters.sort_by_key(|adapter| adapter.number);
+    Ok(adapters)
+}
+
+fn read_sysfs_string(path: &Path) -> io::Result<String> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(contents.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adapters_in, probe_for, timeout_to_ioctl_units, I2CScanMode};
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    #[test]
+    fn auto_scan_never_writes_to_eeprom_ranges() {
+        let auto = |address, can_quick, can_read| {
+            probe_for(I2CScanMode::Auto, address, can_quick, can_read)
+        };
+        assert_eq!(auto(0x20, true, true), Some(I2CScanMode::QuickWrite));
+        assert_eq!(auto(0x50, true, true), Some(I2CScanMode::ReadByte));
+        assert_eq!(auto(0x34, true, true), Some(I2CScanMode::ReadByte));
+        assert_eq!(auto(0x20, false, true), Some(I2CScanMode::ReadByte));
+        // without read byte, EEPROM addresses are skipped rather than written
+        assert_eq!(auto(0x20, true, false), Some(I2CScanMode::QuickWrite));
+        assert_eq!(auto(0x30, true, false), None);
+        assert_eq!(auto(0x5F, true, false), None);
+        assert_eq!(
+            probe_for(I2CScanMode::QuickWrite, 0x50, true, true),
+            Some(I2CScanMode::QuickWrite)
+        );
+    }
+
+    #[test]
+    fn timeout_rounds_up_to_ten_milliseconds() {
+        assert_eq!(timeout_to_ioctl_units(Duration::from_millis(0)), 0);
+        assert_eq!(timeout_to_ioctl_units(Duration::from_micros(1)), 1);
+        assert_eq!(timeout_to_ioctl_units(Duration::from_millis(10)), 1);
+        assert_eq!(timeout_to_ioctl_units(Duration::from_millis(11)), 2);
+        assert_eq!(timeout_to_ioctl_units(Duration::from_secs(1)), 100);
+        assert_eq!(
+            timeout_to_ioctl_units(Duration::from_secs(u64::MAX)),
+            i32::MAX as u32
+        );
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn adapters_walks_sysfs_fixture() {
+        let root: PathBuf = env::temp_dir().join(format!("i2cdev-sysfs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        // i2c-0 is a PCI SMBus controller, i2c-3 is channel 0 of a mux
+        // sitting on i2c-0 at address 0x70
+        let host = root.join("devices/pci0000:00/0000:00:1f.4");
+        let mux = host.join("i2c-0/0-0070");
+        write_file(&host.join("i2c-0/name"), "SMBus I801 adapter\n");
+        write_file(&mux.join("name"), "pca9548\n");
+        write_file(&mux.join("i2c-3/name"), "i2c-0-mux (chan_id 0)\n");
+        symlink(&mux, mux.join("i2c-3/mux_device")).unwrap();
+
+        for (bus, dir) in &[("i2c-0", host.join("i2c-0")), ("i2c-3", mux.join("i2c-3"))] {
+            let class = root.join("class/i2c-dev").join(bus);
+            write_file(
+                &class.join("name"),
+                &fs::read_to_string(dir.join("name")).unwrap(),
+            );
+            symlink(dir, class.join("device")).unwrap();
+            fs::create_dir_all(root.join("bus/i2c/devices")).unwrap();
+            symlink(dir, root.join("bus/i2c/devices").join(bus)).unwrap();
+        }
+        symlink(&mux, root.join("bus/i2c/devices/0-0070")).unwrap();
+
+        let adapters = adapters_in(&root).unwrap();
+        assert_eq!(adapters.len(), 2);
+
+        assert_eq!(adapters[0].number, 0);
+        assert_eq!(adapters[0].path, Path::new("/dev/i2c-0"));
+        assert_eq!(adapters[0].name, "SMBus I801 adapter");
+        assert_eq!(adapters[0].parent, Some(host.canonicalize().unwrap()));
+        assert!(!adapters[0].is_mux_channel);
+
+        assert_eq!(adapters[1].number, 3);
+        assert_eq!(adapters[1].path, Path::new("/dev/i2c-3"));
+        assert_eq!(adapters[1].name, "i2c-0-mux (chan_id 0)");
+        assert_eq!(adapters[1].parent, Some(mux.canonicalize().unwrap()));
+        assert!(adapters[1].is_mux_channel);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! embedded-hal 1.0 `I2c` implementations for the Linux types
+
+use core::I2CMessage;
+use embedded_hal::i2c::{
+    self, ErrorKind, NoAcknowledgeSource, Operation, SevenBitAddress, TenBitAddress,
+};
+use ffi;
+use libc;
+use linux::{I2CMessageFlags, LinuxI2CBus, LinuxI2CDevice, LinuxI2CError, LinuxI2CMessage};
+use std::os::unix::prelude::*;
+
+impl LinuxI2CError {
+    /// The errno behind this error, if there is one
+    fn errno(&self) -> Option<i32> {
+        match *self {
+            LinuxI2CError::Nix(e) => Some(e as i32),
+            LinuxI2CError::Io(ref e) => e.raw_os_error(),
+        }
+    }
+}
+
+/// Classify an error following the kernel's
+/// Documentation/i2c/fault-codes
+fn error_kind(errno: Option<i32>) -> ErrorKind {
+    match errno {
+        Some(libc::ENXIO) => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+        Some(libc::EREMOTEIO) => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+        Some(libc::EAGAIN) => ErrorKind::ArbitrationLoss,
+        Some(libc::EIO) | Some(libc::EBUSY) => ErrorKind::Bus,
+        Some(libc::EOVERFLOW) => ErrorKind::Overrun,
+        _ => ErrorKind::Other,
+    }
+}
+
+impl i2c::Error for LinuxI2CError {
+    fn kind(&self) -> ErrorKind {
+        error_kind(self.errno())
+    }
+}
+
+impl i2c::ErrorType for LinuxI2CBus {
+    type Error = LinuxI2CError;
+}
+
+impl i2c::ErrorType for LinuxI2CDevice {
+    type Error = LinuxI2CError;
+}
+
+/// Run the operations as a single I2C_RDWR call
+///
+/// embedded-hal requires adjacent operations of the same direction to
+/// be sent without a repeated start between them.  Rather than relying on
+/// I2C_M_NOSTART, which few adapters support, such runs are merged into a
+/// single message.  An empty transaction succeeds without touching the
+/// bus, since the kernel rejects I2C_RDWR calls without messages.
+fn transaction(
+    fd: RawFd,
+    address: u16,
+    flags: I2CMessageFlags,
+    operations: &mut [Operation],
+) -> Result<(), LinuxI2CError> {
+    if operations.is_empty() {
+        return Ok(());
+    }
+
+    // (is_read, buffer) for each run of same-direction operations
+    let mut runs: Vec<(bool, Vec<u8>)> = Vec::new();
+    for op in operations.iter() {
+        let (is_read, len, data) = match *op {
+            Operation::Read(ref buf) => (true, buf.len(), None),
+            Operation::Write(buf) => (false, buf.len(), Some(buf)),
+        };
+        let start_run = match runs.last() {
+            Some(&(last_is_read, _)) => last_is_read != is_read,
+            None => true,
+        };
+        if start_run {
+            runs.push((is_read, Vec::new()));
+        }
+        let run = &mut runs.last_mut().unwrap().1;
+        match data {
+            Some(data) => run.extend_from_slice(data),
+            None => run.resize(run.len() + len, 0),
+        }
+    }
+
+    {
+        let mut messages: Vec<LinuxI2CMessage> = runs
+            .iter_mut()
+            .map(|&mut (is_read, ref mut buf)| {
+                if is_read {
+                    LinuxI2CMessage::read(buf).with_flags(flags | I2CMessageFlags::READ)
+                } else {
+                    LinuxI2CMessage::write(buf).with_flags(flags)
+                }
+                .with_address(address)
+            })
+            .collect();
+        ffi::i2c_rdwr(fd, &mut messages)?;
+    }
+
+    // scatter the merged reads back into the caller's buffers
+    let mut runs = runs.into_iter().filter(|&(is_read, _)| is_read);
+    let mut current: Option<(Vec<u8>, usize)> = None;
+    let mut last_was_read = false;
+    for op in operations.iter_mut() {
+        match *op {
+            Operation::Read(ref mut buf) => {
+                if !last_was_read {
+                    current = runs.next().map(|(_, data)| (data, 0));
+                }
+                if let Some((ref data, ref mut offset)) = current {
+                    buf.copy_from_slice(&data[*offset..*offset + buf.len()]);
+                    *offset += buf.len();
+                }
+                last_was_read = true;
+            }
+            Operation::Write(_) => last_was_read = false,
+        }
+    }
+    Ok(())
+}
+
+impl i2c::I2c<SevenBitAddress> for LinuxI2CBus {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation],
+    ) -> Result<(), Self::Error> {
+        transaction(
+            self.as_raw_fd(),
+            u16::from(address),
+            I2CMessageFlags::empty(),
+            operations,
+        )
+    }
+}
+
+impl i2c::I2c<TenBitAddress> for LinuxI2CBus {
+    fn transaction(
+        &mut self,
+        address: TenBitAddress,
+        operations: &mut [Operation],
+    ) -> Result<(), Self::Error> {
+        transaction(
+            self.as_raw_fd(),
+            address,
+            I2CMessageFlags::TEN_BIT_ADDRESS,
+            operations,
+        )
+    }
+}
+
+/// The transaction is addressed to `address`, regardless of the slave
+/// address the device was opened with
+impl i2c::I2c<SevenBitAddress> for LinuxI2CDevice {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation],
+    ) -> Result<(), Self::Error> {
+        transaction(
+            self.as_raw_fd(),
+            u16::from(address),
+            I2CMessageFlags::empty(),
+            operations,
+        )
+    }
+}
+
+/// The transaction is addressed to `address`, regardless of the slave
+/// address the device was opened with
+impl i2c::I2c<TenBitAddress> for LinuxI2CDevice {
+    fn transaction(
+        &mut self,
+        address: TenBitAddress,
+        operations: &mut [Operation],
+    ) -> Result<(), Self::Error> {
+        transaction(
+            self.as_raw_fd(),
+            address,
+            I2CMessageFlags::TEN_BIT_ADDRESS,
+            operations,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error_kind, transaction};
+    use embedded_hal::i2c::{Error, ErrorKind, NoAcknowledgeSource, Operation};
+    use libc;
+    use linux::{I2CMessageFlags, LinuxI2CError};
+    use nix;
+    use std::io;
+
+    #[test]
+    fn errno_maps_to_error_kind() {
+        assert_eq!(
+            error_kind(Some(libc::ENXIO)),
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)
+        );
+        assert_eq!(
+            error_kind(Some(libc::EREMOTEIO)),
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+        );
+        assert_eq!(error_kind(Some(libc::EAGAIN)), ErrorKind::ArbitrationLoss);
+        assert_eq!(error_kind(Some(libc::EIO)), ErrorKind::Bus);
+        assert_eq!(error_kind(Some(libc::EINVAL)), ErrorKind::Other);
+        assert_eq!(error_kind(None), ErrorKind::Other);
+
+        let nix_error = LinuxI2CError::Nix(nix::Error::EAGAIN);
+        assert_eq!(nix_error.kind(), ErrorKind::ArbitrationLoss);
+        let io_error = LinuxI2CError::Io(io::Error::from_raw_os_error(libc::ENXIO));
+        assert_eq!(
+            io_error.kind(),
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)
+        );
+    }
+
+    #[test]
+    fn empty_transaction_skips_the_bus() {
+        // an invalid descriptor makes any ioctl fail
+        assert!(transaction(-1, 0x50, I2CMessageFlags::empty(), &mut []).is_ok());
+        assert!(transaction(
+            -1,
+            0x50,
+            I2CMessageFlags::empty(),
+            &mut [Operation::Write(&[0])]
+        )
+        .is_err());
+    }
+}
@@ -5,15 +5,25 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option.  This file may not be copied, modified, or distributed
 // except according to those terms.
-use core::{I2CDevice, I2CMessage, I2CTransfer};
+use asynch::{self, AsyncI2CMessage, AsyncMessageType, I2CFuture, Ready};
+use core::{check_block_count, check_block_len, I2CDevice, I2CMessage, I2CRegister16, I2CTransfer};
+use libc;
+use std::collections::{HashMap, VecDeque};
 use std::io;
+use std::sync::{Arc, Mutex};
 
 /// I2C mock result type
 pub type I2CResult<T> = io::Result<T>;
 
 /// Mock I2C device register map
+///
+/// The map holds 256 byte-wide registers and an internal address pointer
+/// which, as on typical devices, auto-increments (and wraps) on every
+/// byte read or written.  A map created with `with_16bit_addresses` holds
+/// 65536 registers selected by two address bytes, most significant first.
 pub struct I2CRegisterMap {
-    registers: [u8; 0xFF],
+    registers: Vec<u8>,
+    address_bytes: usize,
     offset: usize,
 }
 
@@ -27,7 +37,20 @@ impl I2CRegisterMap {
     /// Create new mock I2C register map
     pub fn new() -> I2CRegisterMap {
         I2CRegisterMap {
-            registers: [0x00; 0xFF],
+            registers: vec![0x00; 0x100],
+            address_bytes: 1,
+            offset: 0,
+        }
+    }
+
+    /// Create a mock I2C register map with 16-bit register addresses
+    ///
+    /// The SMBus commands of `MockI2CDevice` assume 8-bit register
+    /// addresses and should not be used with such a map.
+    pub fn with_16bit_addresses() -> I2CRegisterMap {
+        I2CRegisterMap {
+            registers: vec![0x00; 0x10000],
+            address_bytes: 2,
             offset: 0,
         }
     }
@@ -35,32 +58,74 @@ impl I2CRegisterMap {
     /// Set several registers starting at the given offset
     pub fn write_regs(&mut self, offset: usize, data: &[u8]) {
         println!("WRITE | 0x{:X} : {:?}", offset, data);
-        self.registers[offset..(data.len() + offset)].clone_from_slice(data);
+        let len = self.registers.len();
+        for (i, byte) in data.iter().enumerate() {
+            self.registers[(offset + i) % len] = *byte;
+        }
+    }
+
+    /// Get several registers starting at the given offset
+    pub fn read_regs(&self, offset: usize, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| self.registers[(offset + i) % self.registers.len()])
+            .collect()
     }
 }
 
 impl I2CRegisterMap {
     /// Read data from the device to fill the provided slice
     fn read(&mut self, data: &mut [u8]) -> I2CResult<()> {
-        let len = data.len();
-        data.clone_from_slice(&self.registers[self.offset..(self.offset + len)]);
-        println!("READ  | 0x{:X} : {:?}", self.offset - data.len(), data);
+        let offset = self.offset;
+        data.clone_from_slice(&self.read_regs(offset, data.len()));
+        self.offset = (offset + data.len()) % self.registers.len();
+        println!("READ  | 0x{:X} : {:?}", offset, data);
         Ok(())
     }
 
     /// Write the provided buffer to the device
     fn write(&mut self, data: &[u8]) -> I2CResult<()> {
-        // ASSUMPTION: first byte sets the offset
-        // ASSUMPTION: write has length of at least one (will panic)
-        let offset = data[0] as usize;
-        let remdata = &data[1..];
+        // ASSUMPTION: the first byte(s) set the offset
+        if data.len() < self.address_bytes {
+            return Ok(());
+        }
+        let (address, remdata) = data.split_at(self.address_bytes);
+        let offset = address
+            .iter()
+            .fold(0, |offset, &byte| (offset << 8) | byte as usize);
         self.write_regs(offset, remdata);
-        self.offset = offset + remdata.len();
+        self.offset = (offset + remdata.len()) % self.registers.len();
         Ok(())
     }
+
+    /// Read an SMBus block, stored as a count byte followed by the data
+    fn read_block(&mut self, register: u8) -> I2CResult<Vec<u8>> {
+        let count = self.registers[register as usize] as usize;
+        check_block_count(count)?;
+        let mut block = vec![0; count];
+        self.offset = (register as usize + 1) % self.registers.len();
+        self.read(&mut block)?;
+        Ok(block)
+    }
+
+    /// Write an SMBus block, stored as a count byte followed by the data
+    fn write_block(&mut self, register: u8, values: &[u8]) -> I2CResult<()> {
+        check_block_len(values.len())?;
+        let mut buf = Vec::with_capacity(values.len() + 2);
+        buf.push(register);
+        buf.push(values.len() as u8);
+        buf.extend_from_slice(values);
+        self.write(&buf)
+    }
 }
 
 /// Mock I2C device exposing a register map
+///
+/// Plain reads and writes follow the common convention that the first
+/// byte written sets the register address.  SMBus block commands store
+/// their data in the map as a count byte followed by the block, so a block
+/// written with `smbus_write_block_data` reads back unchanged through
+/// `smbus_read_block_data`.  The process calls write their argument to
+/// the register and read the result back from the same register.
 #[derive(Default)]
 pub struct MockI2CDevice {
     /// I2C register map
@@ -88,27 +153,42 @@ impl I2CDevice for MockI2CDevice {
     }
 
     fn smbus_write_quick(&mut self, _bit: bool) -> I2CResult<()> {
-        unimplemented!()
+        // only the address is acknowledged, no data is transferred
+        Ok(())
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> I2CResult<u16> {
+        self.smbus_write_word_data(register, value)?;
+        self.smbus_read_word_data(register)
     }
 
-    fn smbus_read_block_data(&mut self, _register: u8) -> I2CResult<Vec<u8>> {
-        unimplemented!()
+    fn smbus_read_block_data(&mut self, register: u8) -> I2CResult<Vec<u8>> {
+        self.regmap.read_block(register)
     }
 
-    fn smbus_write_block_data(&mut self, _register: u8, _values: &[u8]) -> I2CResult<()> {
-        unimplemented!()
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> I2CResult<()> {
+        self.regmap.write_block(register, values)
     }
 
-    fn smbus_process_block(&mut self, _register: u8, _values: &[u8]) -> I2CResult<Vec<u8>> {
-        unimplemented!()
+    fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> I2CResult<Vec<u8>> {
+        self.regmap.write_block(register, values)?;
+        self.regmap.read_block(register)
     }
 
-    fn smbus_read_i2c_block_data(&mut self, _register: u8, _len: u8) -> I2CResult<Vec<u8>> {
-        unimplemented!()
+    fn smbus_read_i2c_block_data(&mut self, register: u8, len: u8) -> I2CResult<Vec<u8>> {
+        check_block_len(len as usize)?;
+        let mut block = vec![0; len as usize];
+        self.regmap.write(&[register])?;
+        self.regmap.read(&mut block)?;
+        Ok(block)
     }
 
-    fn smbus_write_i2c_block_data(&mut self, _register: u8, _values: &[u8]) -> I2CResult<()> {
-        unimplemented!()
+    fn smbus_write_i2c_block_data(&mut self, register: u8, values: &[u8]) -> I2CResult<()> {
+        check_block_len(values.len())?;
+        let mut buf = Vec::with_capacity(values.len() + 1);
+        buf.push(register);
+        buf.extend_from_slice(values);
+        self.regmap.write(&buf)
     }
 }
 
@@ -119,13 +199,16 @@ enum MessageType<'a> {
 }
 
 /// Mock I2C message
+#[derive(Debug)]
 pub struct MockI2CMessage<'a> {
+    addr: u16,
     msg_type: MessageType<'a>,
 }
 
 impl<'a> I2CMessage<'a> for MockI2CMessage<'a> {
     fn read(data: &'a mut [u8]) -> Self {
         Self {
+            addr: 0, // will be filled later
             msg_type: MessageType::Read(data),
         }
     }
@@ -133,9 +216,39 @@ impl<'a> I2CMessage<'a> for MockI2CMessage<'a> {
     /// Write data to device
     fn write(data: &'a [u8]) -> Self {
         Self {
+            addr: 0, // will be filled later
             msg_type: MessageType::Write(data),
         }
     }
+
+    fn with_address(self, slave_address: u16) -> Self {
+        MockI2CMessage::with_address(self, slave_address)
+    }
+}
+
+impl<'a> MockI2CMessage<'a> {
+    /// Set the target device address for the message
+    pub fn with_address(self, slave_address: u16) -> Self {
+        Self {
+            addr: slave_address,
+            msg_type: self.msg_type,
+        }
+    }
+
+    /// Target device address of the message
+    pub fn address(&self) -> u16 {
+        self.addr
+    }
+}
+
+/// Requires a register map created with `I2CRegisterMap::with_16bit_addresses`
+impl I2CRegister16 for MockI2CDevice {
+    fn read_block16(&mut self, register: u16, data: &mut [u8]) -> I2CResult<()> {
+        let address = [(register >> 8) as u8, register as u8];
+        let mut messages = [MockI2CMessage::write(&address), MockI2CMessage::read(data)];
+        self.transfer(&mut messages)?;
+        Ok(())
+    }
 }
 
 impl<'a> I2CTransfer<'a> for MockI2CDevice
@@ -156,3 +269,1038 @@ where
         Ok(messages.len() as u32)
     }
 }
+
+/// Simulated device attached to a `MockI2CBus`
+///
+/// Each message addressed to the target is handed to it in order; a
+/// repeated start between messages is not signalled separately.
+pub trait MockI2CTarget {
+    /// Handle a write message addressed to the target
+    fn handle_write(&mut self, data: &[u8]) -> I2CResult<()>;
+
+    /// Handle a read message addressed to the target, filling `data`
+    fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()>;
+}
+
+impl MockI2CTarget for MockI2CDevice {
+    fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+        self.regmap.write(data)
+    }
+
+    fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+        self.regmap.read(data)
+    }
+}
+
+/// Shared targets allow a test to keep a handle for inspecting the target
+/// after it has been attached to the bus
+impl<T: MockI2CTarget> MockI2CTarget for Arc<Mutex<T>> {
+    fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+        self.lock().unwrap().handle_write(data)
+    }
+
+    fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+        self.lock().unwrap().handle_read(data)
+    }
+}
+
+/// Mock I2C bus routing messages to simulated targets by address
+///
+/// Messages to an address without a target fail with `ENXIO`, the error
+/// the Linux I2C_RDWR ioctl reports when a device does not acknowledge
+/// its address.  As with the ioctl, the transfer stops at the first
+/// failing message.
+///
+/// ```rust
+/// use i2cdev::core::{I2CMessage, I2CTransfer};
+/// use i2cdev::mock::{MockI2CBus, MockI2CDevice, MockI2CMessage};
+///
+/// let mut device = MockI2CDevice::new();
+/// device.regmap.write_regs(0x00, &[0x80, 0x05]);
+///
+/// let mut bus = MockI2CBus::new();
+/// bus.add_target(0x20, device);
+///
+/// let mut data = [0; 2];
+/// bus.transfer(&mut [
+///     MockI2CMessage::write(&[0x00]).with_address(0x20),
+///     MockI2CMessage::read(&mut data).with_address(0x20),
+/// ])
+/// .unwrap();
+/// assert_eq!(data, [0x80, 0x05]);
+///
+/// assert!(bus
+///     .transfer(&mut [MockI2CMessage::write(&[0x00]).with_address(0x21)])
+///     .is_err());
+/// ```
+#[derive(Default)]
+pub struct MockI2CBus {
+    targets: HashMap<u16, Box<dyn MockI2CTarget + Send>>,
+}
+
+impl MockI2CBus {
+    /// Create a new mock bus without any targets
+    pub fn new() -> MockI2CBus {
+        MockI2CBus {
+            targets: HashMap::new(),
+        }
+    }
+
+    /// Attach a target at the given address, replacing any previous one
+    pub fn add_target<T: MockI2CTarget + Send + 'static>(&mut self, address: u16, target: T) {
+        self.targets.insert(address, Box::new(target));
+    }
+
+    /// Detach the target at the given address
+    pub fn remove_target(&mut self, address: u16) -> Option<Box<dyn MockI2CTarget + Send>> {
+        self.targets.remove(&address)
+    }
+
+    /// Check whether a target acknowledges the given address
+    pub fn has_target(&self, address: u16) -> bool {
+        self.targets.contains_key(&address)
+    }
+}
+
+impl<'a> I2CTransfer<'a> for MockI2CBus {
+    type Error = io::Error;
+    type Message = MockI2CMessage<'a>;
+
+    /// Route the provided sequence of I2C transactions to the targets
+    fn transfer(&mut self, messages: &'a mut [Self::Message]) -> Result<u32, Self::Error> {
+        for msg in messages.iter_mut() {
+            let target = match self.targets.get_mut(&msg.addr) {
+                Some(target) => target,
+                None => return Err(io::Error::from_raw_os_error(libc::ENXIO)),
+            };
+            match &mut msg.msg_type {
+                MessageType::Read(data) => target.handle_read(data)?,
+                MessageType::Write(data) => target.handle_write(data)?,
+            }
+        }
+        Ok(messages.len() as u32)
+    }
+}
+
+/// Operation expected by a `MockI2CScript`
+///
+/// Read-type variants carry the data handed back to the driver under
+/// test; write-type variants carry the data the driver is expected to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockI2CTransaction {
+    /// `read` of exactly this many bytes, returning them
+    Read(Vec<u8>),
+    /// `write` of these bytes
+    Write(Vec<u8>),
+    /// `smbus_write_quick` with this bit
+    SmbusWriteQuick(bool),
+    /// `smbus_read_byte` returning this value
+    SmbusReadByte(u8),
+    /// `smbus_write_byte` of this value
+    SmbusWriteByte(u8),
+    /// `smbus_read_byte_data` of a register, returning a value
+    SmbusReadByteData(u8, u8),
+    /// `smbus_write_byte_data` of a value to a register
+    SmbusWriteByteData(u8, u8),
+    /// `smbus_read_word_data` of a register, returning a value
+    SmbusReadWordData(u8, u16),
+    /// `smbus_write_word_data` of a value to a register
+    SmbusWriteWordData(u8, u16),
+    /// `smbus_process_word` of a register and value, returning a value
+    SmbusProcessWord(u8, u16, u16),
+    /// `smbus_read_block_data` of a register, returning a block
+    SmbusReadBlockData(u8, Vec<u8>),
+    /// `smbus_write_block_data` of a block to a register
+    SmbusWriteBlockData(u8, Vec<u8>),
+    /// `smbus_process_block` of a register and block, returning a block
+    SmbusProcessBlock(u8, Vec<u8>, Vec<u8>),
+    /// `smbus_read_i2c_block_data` of a register, returning a block whose
+    /// length must match the requested length
+    SmbusReadI2cBlockData(u8, Vec<u8>),
+    /// `smbus_write_i2c_block_data` of a block to a register
+    SmbusWriteI2cBlockData(u8, Vec<u8>),
+    /// `transfer` of exactly these messages
+    Transfer(Vec<MockI2CMessageOp>),
+}
+
+/// Message expected within a `MockI2CTransaction::Transfer`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockI2CMessageOp {
+    /// Read message of exactly this many bytes, returning them
+    Read(Vec<u8>),
+    /// Write message of these bytes
+    Write(Vec<u8>),
+}
+
+/// Mock I2C device replaying a script of expected operations
+///
+/// Each operation performed by the driver under test is checked against
+/// the next expected `MockI2CTransaction`; any mismatch panics, as does
+/// calling `done()` while expectations remain.
+///
+/// ```rust
+/// use i2cdev::core::I2CDevice;
+/// use i2cdev::mock::{MockI2CScript, MockI2CTransaction};
+///
+/// let mut dev = MockI2CScript::new(vec![
+///     MockI2CTransaction::SmbusWriteByteData(0x2D, 0x08),
+///     MockI2CTransaction::Write(vec![0x32]),
+///     MockI2CTransaction::Read(vec![0x01, 0x02]),
+/// ]);
+///
+/// dev.smbus_write_byte_data(0x2D, 0x08).unwrap();
+/// dev.write(&[0x32]).unwrap();
+/// let mut buf = [0; 2];
+/// dev.read(&mut buf).unwrap();
+/// assert_eq!(buf, [0x01, 0x02]);
+/// dev.done();
+/// ```
+#[derive(Debug, Default)]
+pub struct MockI2CScript {
+    expected: VecDeque<MockI2CTransaction>,
+}
+
+impl MockI2CScript {
+    /// Create a new scripted mock expecting the given operations in order
+    pub fn new<I: IntoIterator<Item = MockI2CTransaction>>(expected: I) -> MockI2CScript {
+        MockI2CScript {
+            expected: expected.into_iter().collect(),
+        }
+    }
+
+    /// Append further expected operations to the script
+    pub fn expect<I: IntoIterator<Item = MockI2CTransaction>>(&mut self, expected: I) {
+        self.expected.extend(expected);
+    }
+
+    /// Assert that every expected operation has been performed
+    pub fn done(&mut self) {
+        assert!(
+            self.expected.is_empty(),
+            "mock I2C script has unperformed operations: {:?}",
+            self.expected
+        );
+    }
+
+    fn next(&mut self, actual: &str) -> MockI2CTransaction {
+        match self.expected.pop_front() {
+            Some(expected) => expected,
+            None => panic!("unexpected {}: mock I2C script is exhausted", actual),
+        }
+    }
+}
+
+fn mismatch(expected: &MockI2CTransaction, actual: &str) -> ! {
+    panic!("expected {:?}, got {}", expected, actual)
+}
+
+impl I2CDevice for MockI2CScript {
+    type Error = io::Error;
+
+    fn read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+        let actual = format!("Read of {} bytes", data.len());
+        match self.next(&actual) {
+            MockI2CTransaction::Read(ref response) if response.len() == data.len() => {
+                data.copy_from_slice(response);
+                Ok(())
+            }
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> I2CResult<()> {
+        let actual = format!("Write({:?})", data);
+        match self.next(&actual) {
+            MockI2CTransaction::Write(ref expected) if expected[..] == *data => Ok(()),
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_write_quick(&mut self, bit: bool) -> I2CResult<()> {
+        let actual = format!("SmbusWriteQuick({:?})", bit);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusWriteQuick(b) if b == bit => Ok(()),
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_read_byte(&mut self) -> I2CResult<u8> {
+        let actual = "SmbusReadByte";
+        match self.next(actual) {
+            MockI2CTransaction::SmbusReadByte(value) => Ok(value),
+            expected => mismatch(&expected, actual),
+        }
+    }
+
+    fn smbus_write_byte(&mut self, value: u8) -> I2CResult<()> {
+        let actual = format!("SmbusWriteByte({})", value);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusWriteByte(v) if v == value => Ok(()),
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> I2CResult<u8> {
+        let actual = format!("SmbusReadByteData({}, _)", register);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusReadByteData(r, value) if r == register => Ok(value),
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> I2CResult<()> {
+        let actual = format!("SmbusWriteByteData({}, {})", register, value);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusWriteByteData(r, v) if r == register && v == value => Ok(()),
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> I2CResult<u16> {
+        let actual = format!("SmbusReadWordData({}, _)", register);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusReadWordData(r, value) if r == register => Ok(value),
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_write_word_data(&mut self, register: u8, value: u16) -> I2CResult<()> {
+        let actual = format!("SmbusWriteWordData({}, {})", register, value);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusWriteWordData(r, v) if r == register && v == value => Ok(()),
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> I2CResult<u16> {
+        let actual = format!("SmbusProcessWord({}, {}, _)", register, value);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusProcessWord(r, v, response) if r == register && v == value => {
+                Ok(response)
+            }
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_read_block_data(&mut self, register: u8) -> I2CResult<Vec<u8>> {
+        let actual = format!("SmbusReadBlockData({}, _)", register);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusReadBlockData(r, block) if r == register => Ok(block),
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_read_i2c_block_data(&mut self, register: u8, len: u8) -> I2CResult<Vec<u8>> {
+        let actual = format!("SmbusReadI2cBlockData({}, <{} bytes>)", register, len);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusReadI2cBlockData(r, block)
+                if r == register && block.len() == len as usize =>
+            {
+                Ok(block)
+            }
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> I2CResult<()> {
+        let actual = format!("SmbusWriteBlockData({}, {:?})", register, values);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusWriteBlockData(r, ref block)
+                if r == register && block[..] == *values =>
+            {
+                Ok(())
+            }
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_write_i2c_block_data(&mut self, register: u8, values: &[u8]) -> I2CResult<()> {
+        let actual = format!("SmbusWriteI2cBlockData({}, {:?})", register, values);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusWriteI2cBlockData(r, ref block)
+                if r == register && block[..] == *values =>
+            {
+                Ok(())
+            }
+            expected => mismatch(&expected, &actual),
+        }
+    }
+
+    fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> I2CResult<Vec<u8>> {
+        let actual = format!("SmbusProcessBlock({}, {:?}, _)", register, values);
+        match self.next(&actual) {
+            MockI2CTransaction::SmbusProcessBlock(r, ref block, ref response)
+                if r == register && block[..] == *values =>
+            {
+                Ok(response.clone())
+            }
+            expected => mismatch(&expected, &actual),
+        }
+    }
+}
+
+impl<'a> I2CTransfer<'a> for MockI2CScript {
+    type Error = io::Error;
+    type Message = MockI2CMessage<'a>;
+
+    /// Check the provided sequence of I2C transactions against the script
+    fn transfer(&mut self, messages: &'a mut [Self::Message]) -> Result<u32, Self::Error> {
+        let actual = format!("Transfer({:?})", messages);
+        let expected = match self.next(&actual) {
+            MockI2CTransaction::Transfer(ops) => ops,
+            expected => mismatch(&expected, &actual),
+        };
+        if expected.len() != messages.len() {
+            mismatch(&MockI2CTransaction::Transfer(expected), &actual);
+        }
+        for (op, msg) in expected.iter().zip(messages.iter_mut()) {
+            match (op, &mut msg.msg_type) {
+                (MockI2CMessageOp::Write(data), MessageType::Write(sent)) if data[..] == **sent => {
+                }
+                (MockI2CMessageOp::Read(data), MessageType::Read(buf))
+                    if data.len() == buf.len() =>
+                {
+                    buf.copy_from_slice(data)
+                }
+                _ => mismatch(&MockI2CTransaction::Transfer(expected.clone()), &actual),
+            }
+        }
+        Ok(messages.len() as u32)
+    }
+}
+
+/// Fault raised by an `I2CFaultInjector`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2CFault {
+    /// The device does not acknowledge (`ENXIO`)
+    Nack,
+    /// The adapter times out (`ETIMEDOUT`)
+    Timeout,
+    /// The adapter loses arbitration (`EAGAIN`)
+    ArbitrationLost,
+    /// A read completes with fewer bytes than requested
+    /// (`io::ErrorKind::UnexpectedEof`); the data read is still delivered
+    ShortRead,
+    /// A read succeeds, but the given mask is XORed into the last byte
+    /// returned
+    CorruptRead(u8),
+}
+
+impl I2CFault {
+    fn to_io_error(self) -> io::Error {
+        match self {
+            I2CFault::Nack => io::Error::from_raw_os_error(libc::ENXIO),
+            I2CFault::Timeout => io::Error::from_raw_os_error(libc::ETIMEDOUT),
+            I2CFault::ArbitrationLost => io::Error::from_raw_os_error(libc::EAGAIN),
+            _ => io::Error::new(io::ErrorKind::UnexpectedEof, "short read"),
+        }
+    }
+}
+
+/// Condition under which an `I2CFaultInjector` raises a fault
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum I2CFaultTrigger {
+    /// The operation with this index, counting every operation on the
+    /// injector from zero
+    Operation(usize),
+    /// Any operation, with the given probability between 0 and 1
+    Probability(f64),
+    /// Any operation addressing this register; for plain writes the first
+    /// byte is taken as the register
+    Register(u8),
+}
+
+/// Decisions taken for a single operation
+#[derive(Default)]
+struct FaultPlan {
+    error: Option<I2CFault>,
+    short_read: bool,
+    corrupt_mask: u8,
+}
+
+impl FaultPlan {
+    fn fail<E: From<io::Error>>(&self) -> Result<(), E> {
+        match self.error {
+            Some(fault) => Err(fault.to_io_error().into()),
+            None => Ok(()),
+        }
+    }
+
+    fn finish_read<E: From<io::Error>>(&self, data: &mut [u8]) -> Result<(), E> {
+        if let Some(last) = data.last_mut() {
+            *last ^= self.corrupt_mask;
+        }
+        if self.short_read {
+            return Err(I2CFault::ShortRead.to_io_error().into());
+        }
+        Ok(())
+    }
+}
+
+/// Wrapper injecting faults into the operations of an I2C device
+///
+/// Faults are registered together with the trigger that raises them.
+/// Probabilistic triggers draw from a pseudo-random generator seeded
+/// through `with_seed`, so failing test runs can be reproduced.  NACK,
+/// timeout and arbitration loss faults fail the operation before it
+/// reaches the wrapped device; short and corrupted reads let the
+/// operation go through first.
+///
+/// ```rust
+/// use i2cdev::core::I2CDevice;
+/// use i2cdev::mock::{I2CFault, I2CFaultInjector, I2CFaultTrigger, MockI2CDevice};
+///
+/// let mut dev = I2CFaultInjector::new(MockI2CDevice::new())
+///     .with_fault(I2CFaultTrigger::Operation(1), I2CFault::Timeout)
+///     .with_fault(I2CFaultTrigger::Register(0x42), I2CFault::CorruptRead(0x01));
+///
+/// assert!(dev.smbus_write_byte_data(0x10, 0x00).is_ok());
+/// assert!(dev.smbus_write_byte_data(0x10, 0x00).is_err());
+/// assert_eq!(dev.smbus_read_byte_data(0x42).unwrap(), 0x01);
+/// ```
+pub struct I2CFaultInjector<T> {
+    inner: T,
+    faults: Vec<(I2CFaultTrigger, I2CFault)>,
+    operation: usize,
+    rng_state: u64,
+}
+
+impl<T> I2CFaultInjector<T>
+where
+    T: I2CDevice,
+    T::Error: From<io::Error>,
+{
+    /// Wrap a device without any faults registered
+    pub fn new(inner: T) -> I2CFaultInjector<T> {
+        I2CFaultInjector {
+            inner,
+            faults: Vec::new(),
+            operation: 0,
+            rng_state: 0x853C_49E6_748F_EA9B,
+        }
+    }
+
+    /// Seed the generator used by probabilistic triggers
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        // xorshift gets stuck at zero
+        self.rng_state = if seed == 0 {
+            0x853C_49E6_748F_EA9B
+        } else {
+            seed
+        };
+        self
+    }
+
+    /// Register a fault raised whenever the trigger fires
+    pub fn with_fault(mut self, trigger: I2CFaultTrigger, fault: I2CFault) -> Self {
+        self.add_fault(trigger, fault);
+        self
+    }
+
+    /// Register a fault raised whenever the trigger fires
+    pub fn add_fault(&mut self, trigger: I2CFaultTrigger, fault: I2CFault) {
+        self.faults.push((trigger, fault));
+    }
+
+    /// Remove every registered fault
+    pub fn clear_faults(&mut self) {
+        self.faults.clear();
+    }
+
+    /// Number of operations performed (or attempted) so far
+    pub fn operations(&self) -> usize {
+        self.operation
+    }
+
+    /// Get a reference to the wrapped device
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped device
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped device
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn next_random(&mut self) -> f64 {
+        // xorshift64*
+        self.rng_state ^= self.rng_state >> 12;
+        self.rng_state ^= self.rng_state << 25;
+        self.rng_state ^= self.rng_state >> 27;
+        let value = self.rng_state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn plan(&mut self, register: Option<u8>, is_read: bool) -> FaultPlan {
+        let operation = self.operation;
+        self.operation += 1;
+
+        let mut plan = FaultPlan::default();
+        for i in 0..self.faults.len() {
+            let (trigger, fault) = self.faults[i];
+            let fired = match trigger {
+                I2CFaultTrigger::Operation(n) => n == operation,
+                I2CFaultTrigger::Probability(p) => self.next_random() < p,
+                I2CFaultTrigger::Register(r) => register == Some(r),
+            };
+            if !fired {
+                continue;
+            }
+            match fault {
+                I2CFault::ShortRead if is_read => plan.short_read = true,
+                I2CFault::CorruptRead(mask) if is_read => plan.corrupt_mask ^= mask,
+                I2CFault::ShortRead | I2CFault::CorruptRead(_) => (),
+                _ => {
+                    if plan.error.is_none() {
+                        plan.error = Some(fault);
+                    }
+                }
+            }
+        }
+        plan
+    }
+
+    fn read_byte_with<F>(&mut self, register: Option<u8>, op: F) -> Result<u8, T::Error>
+    where
+        F: FnOnce(&mut T) -> Result<u8, T::Error>,
+    {
+        let plan = self.plan(register, true);
+        plan.fail()?;
+        let mut buf = [op(&mut self.inner)?];
+        plan.finish_read(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_word_with<F>(&mut self, register: u8, op: F) -> Result<u16, T::Error>
+    where
+        F: FnOnce(&mut T) -> Result<u16, T::Error>,
+    {
+        let plan = self.plan(Some(register), true);
+        plan.fail()?;
+        // words are transferred lsb first, so the msb is the last byte
+        let mut buf = op(&mut self.inner)?.to_le_bytes();
+        plan.finish_read(&mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn read_block_with<F>(&mut self, register: u8, op: F) -> Result<Vec<u8>, T::Error>
+    where
+        F: FnOnce(&mut T) -> Result<Vec<u8>, T::Error>,
+    {
+        let plan = self.plan(Some(register), true);
+        plan.fail()?;
+        let mut block = op(&mut self.inner)?;
+        plan.finish_read(&mut block)?;
+        Ok(block)
+    }
+
+    fn write_with<F>(&mut self, register: Option<u8>, op: F) -> Result<(), T::Error>
+    where
+        F: FnOnce(&mut T) -> Result<(), T::Error>,
+    {
+        self.plan(register, false).fail()?;
+        op(&mut self.inner)
+    }
+}
+
+impl<T> I2CDevice for I2CFaultInjector<T>
+where
+    T: I2CDevice,
+    T::Error: From<io::Error>,
+{
+    type Error = T::Error;
+
+    fn read(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+        let plan = self.plan(None, true);
+        plan.fail()?;
+        self.inner.read(data)?;
+        plan.finish_read(data)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.write_with(data.first().cloned(), |dev| dev.write(data))
+    }
+
+    fn smbus_write_quick(&mut self, bit: bool) -> Result<(), Self::Error> {
+        self.write_with(None, |dev| dev.smbus_write_quick(bit))
+    }
+
+    fn smbus_read_byte(&mut self) -> Result<u8, Self::Error> {
+        self.read_byte_with(None, |dev| dev.smbus_read_byte())
+    }
+
+    fn smbus_write_byte(&mut self, value: u8) -> Result<(), Self::Error> {
+        self.write_with(None, |dev| dev.smbus_write_byte(value))
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> Result<u8, Self::Error> {
+        self.read_byte_with(Some(register), |dev| dev.smbus_read_byte_data(register))
+    }
+
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+        self.write_with(Some(register), |dev| {
+            dev.smbus_write_byte_data(register, value)
+        })
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> Result<u16, Self::Error> {
+        self.read_word_with(register, |dev| dev.smbus_read_word_data(register))
+    }
+
+    fn smbus_write_word_data(&mut self, register: u8, value: u16) -> Result<(), Self::Error> {
+        self.write_with(Some(register), |dev| {
+            dev.smbus_write_word_data(register, value)
+        })
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> Result<u16, Self::Error> {
+        self.read_word_with(register, |dev| dev.smbus_process_word(register, value))
+    }
+
+    fn smbus_read_block_data(&mut self, register: u8) -> Result<Vec<u8>, Self::Error> {
+        self.read_block_with(register, |dev| dev.smbus_read_block_data(register))
+    }
+
+    fn smbus_read_i2c_block_data(&mut self, register: u8, len: u8) -> Result<Vec<u8>, Self::Error> {
+        self.read_block_with(register, |dev| dev.smbus_read_i2c_block_data(register, len))
+    }
+
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), Self::Error> {
+        self.write_with(Some(register), |dev| {
+            dev.smbus_write_block_data(register, values)
+        })
+    }
+
+    fn smbus_write_i2c_block_data(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), Self::Error> {
+        self.write_with(Some(register), |dev| {
+            dev.smbus_write_i2c_block_data(register, values)
+        })
+    }
+
+    fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        self.read_block_with(register, |dev| dev.smbus_process_block(register, values))
+    }
+}
+
+fn ready<'a, T: Send + Unpin + 'a>(result: I2CResult<T>) -> I2CFuture<'a, T, io::Error> {
+    Box::pin(Ready(Some(result)))
+}
+
+/// Operations complete immediately, so the futures are ready on first poll
+impl asynch::AsyncI2CDevice for MockI2CDevice {
+    type Error = io::Error;
+
+    fn read<'a>(&'a mut self, data: &'a mut [u8]) -> I2CFuture<'a, (), Self::Error> {
+        ready(I2CDevice::read(self, data))
+    }
+
+    fn write<'a>(&'a mut self, data: &'a [u8]) -> I2CFuture<'a, (), Self::Error> {
+        ready(I2CDevice::write(self, data))
+    }
+
+    fn smbus_write_quick(&mut self, bit: bool) -> I2CFuture<'_, (), Self::Error> {
+        ready(I2CDevice::smbus_write_quick(self, bit))
+    }
+
+    fn smbus_read_byte(&mut self) -> I2CFuture<'_, u8, Self::Error> {
+        ready(I2CDevice::smbus_read_byte(self))
+    }
+
+    fn smbus_write_byte(&mut self, value: u8) -> I2CFuture<'_, (), Self::Error> {
+        ready(I2CDevice::smbus_write_byte(self, value))
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> I2CFuture<'_, u8, Self::Error> {
+        ready(I2CDevice::smbus_read_byte_data(self, register))
+    }
+
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> I2CFuture<'_, (), Self::Error> {
+        ready(I2CDevice::smbus_write_byte_data(self, register, value))
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> I2CFuture<'_, u16, Self::Error> {
+        ready(I2CDevice::smbus_read_word_data(self, register))
+    }
+
+    fn smbus_write_word_data(
+        &mut self,
+        register: u8,
+        value: u16,
+    ) -> I2CFuture<'_, (), Self::Error> {
+        ready(I2CDevice::smbus_write_word_data(self, register, value))
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> I2CFuture<'_, u16, Self::Error> {
+        ready(I2CDevice::smbus_process_word(self, register, value))
+    }
+
+    fn smbus_read_block_data(&mut self, register: u8) -> I2CFuture<'_, Vec<u8>, Self::Error> {
+        ready(I2CDevice::smbus_read_block_data(self, register))
+    }
+
+    fn smbus_read_i2c_block_data(
+        &mut self,
+        register: u8,
+        len: u8,
+    ) -> I2CFuture<'_, Vec<u8>, Self::Error> {
+        ready(I2CDevice::smbus_read_i2c_block_data(self, register, len))
+    }
+
+    fn smbus_write_block_data<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, (), Self::Error> {
+        ready(I2CDevice::smbus_write_block_data(self, register, values))
+    }
+
+    fn smbus_write_i2c_block_data<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, (), Self::Error> {
+        ready(I2CDevice::smbus_write_i2c_block_data(
+            self, register, values,
+        ))
+    }
+
+    fn smbus_process_block<'a>(
+        &'a mut self,
+        register: u8,
+        values: &'a [u8],
+    ) -> I2CFuture<'a, Vec<u8>, Self::Error> {
+        ready(I2CDevice::smbus_process_block(self, register, values))
+    }
+}
+
+/// Messages are routed immediately, so the future is ready on first poll
+impl asynch::AsyncI2CTransfer for MockI2CBus {
+    type Error = io::Error;
+
+    fn transfer<'a>(
+        &'a mut self,
+        msgs: &'a mut [AsyncI2CMessage<'a>],
+    ) -> I2CFuture<'a, u32, Self::Error> {
+        let mut messages: Vec<MockI2CMessage> = msgs
+            .iter_mut()
+            .map(|msg| {
+                match msg.msg_type {
+                    AsyncMessageType::Read(ref mut data) => MockI2CMessage::read(data),
+                    AsyncMessageType::Write(data) => MockI2CMessage::write(data),
+                }
+                .with_address(msg.addr)
+            })
+            .collect();
+        ready(I2CTransfer::transfer(self, &mut messages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn async_mock_completes_immediately() {
+        use asynch::tests::block_on;
+        use asynch::{AsyncI2CDevice, AsyncI2CTransfer};
+
+        let mut dev = MockI2CDevice::new();
+        block_on(AsyncI2CDevice::smbus_write_byte_data(&mut dev, 0x05, 0x42)).unwrap();
+        assert_eq!(
+            block_on(AsyncI2CDevice::smbus_read_byte_data(&mut dev, 0x05)).unwrap(),
+            0x42
+        );
+
+        let mut bus = MockI2CBus::new();
+        bus.add_target(0x30, dev);
+        let mut data = [0; 1];
+        {
+            let mut msgs = [
+                AsyncI2CMessage::write(&[0x05]).with_address(0x30),
+                AsyncI2CMessage::read(&mut data).with_address(0x30),
+            ];
+            assert_eq!(
+                block_on(AsyncI2CTransfer::transfer(&mut bus, &mut msgs)).unwrap(),
+                2
+            );
+        }
+        assert_eq!(data, [0x42]);
+    }
+
+    #[test]
+    fn register_map_covers_full_byte_range() {
+        let mut dev = MockI2CDevice::new();
+        dev.smbus_write_byte_data(0xFF, 0x42).unwrap();
+        assert_eq!(dev.smbus_read_byte_data(0xFF).unwrap(), 0x42);
+
+        // the address pointer wraps around after the last register
+        dev.write(&[0xFF, 0x01, 0x02]).unwrap();
+        assert_eq!(dev.smbus_read_byte_data(0x00).unwrap(), 0x02);
+    }
+
+    #[test]
+    fn sixteen_bit_register_addresses() {
+        let mut dev = MockI2CDevice {
+            regmap: I2CRegisterMap::with_16bit_addresses(),
+        };
+        dev.write_block16(0x1234, &[0xAA, 0xBB]).unwrap();
+        dev.write_reg16(0xFFFF, 0xCC).unwrap();
+        assert_eq!(dev.regmap.read_regs(0x1234, 2), vec![0xAA, 0xBB]);
+        assert_eq!(dev.read_reg16(0x1235).unwrap(), 0xBB);
+
+        // reads wrap around the end of the address space
+        let mut data = [0; 2];
+        dev.read_block16(0xFFFF, &mut data).unwrap();
+        assert_eq!(data, [0xCC, 0x00]);
+    }
+
+    #[test]
+    fn smbus_block_commands() {
+        let mut dev = MockI2CDevice::new();
+        dev.smbus_write_block_data(0x10, &[1, 2, 3]).unwrap();
+        assert_eq!(dev.regmap.read_regs(0x10, 4), vec![3, 1, 2, 3]);
+        assert_eq!(dev.smbus_read_block_data(0x10).unwrap(), vec![1, 2, 3]);
+
+        dev.smbus_write_i2c_block_data(0x20, &[4, 5]).unwrap();
+        assert_eq!(dev.smbus_read_i2c_block_data(0x20, 2).unwrap(), vec![4, 5]);
+        assert_eq!(dev.smbus_process_block(0x30, &[6, 7]).unwrap(), vec![6, 7]);
+        assert_eq!(dev.smbus_process_word(0x40, 0x1234).unwrap(), 0x1234);
+        dev.smbus_write_quick(false).unwrap();
+    }
+
+    #[test]
+    fn smbus_block_limits() {
+        let mut dev = MockI2CDevice::new();
+        assert!(dev.smbus_write_block_data(0x00, &[0; 33]).is_err());
+        assert!(dev.smbus_write_i2c_block_data(0x00, &[0; 33]).is_err());
+        assert!(dev.smbus_read_i2c_block_data(0x00, 33).is_err());
+
+        // a count byte above 32 is a protocol error
+        dev.smbus_write_byte_data(0x50, 33).unwrap();
+        assert!(dev.smbus_read_block_data(0x50).is_err());
+    }
+
+    #[test]
+    fn fault_injector_triggers() {
+        let mut dev = I2CFaultInjector::new(MockI2CDevice::new())
+            .with_fault(I2CFaultTrigger::Operation(2), I2CFault::Nack)
+            .with_fault(I2CFaultTrigger::Register(0x20), I2CFault::ArbitrationLost);
+
+        dev.smbus_write_byte_data(0x10, 0xAA).unwrap();
+        dev.smbus_write_byte_data(0x11, 0xBB).unwrap();
+        let err = dev.smbus_read_byte_data(0x10).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENXIO));
+        assert_eq!(dev.smbus_read_byte_data(0x10).unwrap(), 0xAA);
+
+        let err = dev.smbus_write_block_data(0x20, &[1]).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EAGAIN));
+        // the failed write never reached the device
+        assert_eq!(dev.inner().regmap.read_regs(0x20, 1), vec![0]);
+        assert_eq!(dev.operations(), 5);
+    }
+
+    #[test]
+    fn fault_injector_short_and_corrupt_reads() {
+        let mut inner = MockI2CDevice::new();
+        inner.regmap.write_regs(0x00, &[0x12, 0x34]);
+        let mut dev = I2CFaultInjector::new(inner)
+            .with_fault(I2CFaultTrigger::Operation(0), I2CFault::CorruptRead(0x80))
+            .with_fault(I2CFaultTrigger::Operation(1), I2CFault::ShortRead);
+
+        assert_eq!(dev.smbus_read_word_data(0x00).unwrap(), 0xB412);
+        let err = dev.smbus_read_word_data(0x00).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(dev.smbus_read_word_data(0x00).unwrap(), 0x3412);
+    }
+
+    #[test]
+    fn fault_injector_probability_is_reproducible() {
+        let run = |seed| {
+            let mut dev = I2CFaultInjector::new(MockI2CDevice::new())
+                .with_seed(seed)
+                .with_fault(I2CFaultTrigger::Probability(0.5), I2CFault::Timeout);
+            (0..64)
+                .map(|_| dev.smbus_read_byte().is_err())
+                .collect::<Vec<_>>()
+        };
+        let failures = run(7);
+        assert_eq!(failures, run(7));
+        let count = failures.iter().filter(|&&failed| failed).count();
+        assert!(count > 16 && count < 48);
+    }
+
+    #[test]
+    fn bus_stops_at_first_nack() {
+        let device = Arc::new(Mutex::new(MockI2CDevice::new()));
+        let mut bus = MockI2CBus::new();
+        bus.add_target(0x50, device.clone());
+
+        let err = bus
+            .transfer(&mut [
+                MockI2CMessage::write(&[0x10, 0xAA]).with_address(0x50),
+                MockI2CMessage::write(&[0x00]).with_address(0x51),
+                MockI2CMessage::write(&[0x11, 0xBB]).with_address(0x50),
+            ])
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENXIO));
+
+        let device = device.lock().unwrap();
+        assert_eq!(device.regmap.read_regs(0x10, 2), vec![0xAA, 0x00]);
+    }
+
+    #[test]
+    fn script_replays_smbus_and_transfers() {
+        let mut dev = MockI2CScript::new(vec![
+            MockI2CTransaction::SmbusReadWordData(0x10, 0xBEEF),
+            MockI2CTransaction::SmbusProcessBlock(0x20, vec![1, 2], vec![3]),
+            MockI2CTransaction::Transfer(vec![
+                MockI2CMessageOp::Write(vec![0x01]),
+                MockI2CMessageOp::Read(vec![0xAA, 0xBB]),
+            ]),
+        ]);
+
+        assert_eq!(dev.smbus_read_word_data(0x10).unwrap(), 0xBEEF);
+        assert_eq!(dev.smbus_process_block(0x20, &[1, 2]).unwrap(), vec![3]);
+
+        let mut buf = [0; 2];
+        {
+            let mut msgs = [
+                MockI2CMessage::write(&[0x01]),
+                MockI2CMessage::read(&mut buf),
+            ];
+            assert_eq!(dev.transfer(&mut msgs).unwrap(), 2);
+        }
+        assert_eq!(buf, [0xAA, 0xBB]);
+        dev.done();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected SmbusWriteByteData(1, 2)")]
+    fn script_panics_on_mismatch() {
+        let mut dev = MockI2CScript::new(vec![MockI2CTransaction::SmbusWriteByteData(1, 2)]);
+        let _ = dev.smbus_write_byte_data(1, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "mock I2C script is exhausted")]
+    fn script_panics_when_exhausted() {
+        let mut dev = MockI2CScript::new(vec![]);
+        let _ = dev.smbus_read_byte();
+    }
+
+    #[test]
+    #[should_panic(expected = "unperformed operations")]
+    fn script_panics_on_leftovers() {
+        let mut dev = MockI2CScript::new(vec![MockI2CTransaction::SmbusReadByte(0)]);
+        dev.done();
+    }
+}
@@ -0,0 +1,971 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! PMBus protocol layer
+//!
+//! PMBus devices, such as voltage regulators and power supplies, are SMBus
+//! devices with a standard command set.  Telemetry and limits use one of
+//! three numeric formats: LINEAR11 for most values, and either LINEAR16
+//! or DIRECT for output voltages as selected by `VOUT_MODE`.  `Pmbus`
+//! converts these to and from `f64` and tracks the selected `PAGE` and
+//! `PHASE`.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::linux::LinuxI2CError> {
+//! use i2cdev::linux::LinuxI2CDevice;
+//! use i2cdev::pmbus::{Command, Pmbus, StatusWord};
+//!
+//! let dev = LinuxI2CDevice::new("/dev/i2c-1", 0x40)?;
+//! let mut vrm = Pmbus::new(dev);
+//! vrm.set_page(1)?;
+//! let vout = vrm.read_vout(Command::ReadVout)?;
+//! let iout = vrm.read_linear11(Command::ReadIout)?;
+//! if vrm.status_word()?.contains(StatusWord::IOUT_OC_FAULT) {
+//!     println!("overcurrent at {} V, {} A", vout, iout);
+//! }
+//! println!("{}", vrm.read_string(Command::MfrModel)?);
+//! # Ok(())
+//! # }
+//! ```
+
+use core::{invalid_data, invalid_input, I2CDevice};
+use std::io;
+
+/// PMBus command codes
+///
+/// Variants are named after the commands of PMBus Part II.  Manufacturer
+/// specific commands may be passed as a plain `u8` to the methods of
+/// `Pmbus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Command {
+    /// PAGE (0x00)
+    Page = 0x00,
+    /// OPERATION (0x01)
+    Operation = 0x01,
+    /// ON_OFF_CONFIG (0x02)
+    OnOffConfig = 0x02,
+    /// CLEAR_FAULTS (0x03)
+    ClearFaults = 0x03,
+    /// PHASE (0x04)
+    Phase = 0x04,
+    /// PAGE_PLUS_WRITE (0x05)
+    PagePlusWrite = 0x05,
+    /// PAGE_PLUS_READ (0x06)
+    PagePlusRead = 0x06,
+    /// WRITE_PROTECT (0x10)
+    WriteProtect = 0x10,
+    /// STORE_DEFAULT_ALL (0x11)
+    StoreDefaultAll = 0x11,
+    /// RESTORE_DEFAULT_ALL (0x12)
+    RestoreDefaultAll = 0x12,
+    /// CAPABILITY (0x19)
+    Capability = 0x19,
+    /// QUERY (0x1A)
+    Query = 0x1A,
+    /// SMBALERT_MASK (0x1B)
+    SmbalertMask = 0x1B,
+    /// VOUT_MODE (0x20)
+    VoutMode = 0x20,
+    /// VOUT_COMMAND (0x21)
+    VoutCommand = 0x21,
+    /// VOUT_TRIM (0x22)
+    VoutTrim = 0x22,
+    /// VOUT_CAL_OFFSET (0x23)
+    VoutCalOffset = 0x23,
+    /// VOUT_MAX (0x24)
+    VoutMax = 0x24,
+    /// VOUT_MARGIN_HIGH (0x25)
+    VoutMarginHigh = 0x25,
+    /// VOUT_MARGIN_LOW (0x26)
+    VoutMarginLow = 0x26,
+    /// VOUT_TRANSITION_RATE (0x27)
+    VoutTransitionRate = 0x27,
+    /// VOUT_DROOP (0x28)
+    VoutDroop = 0x28,
+    /// VOUT_SCALE_LOOP (0x29)
+    VoutScaleLoop = 0x29,
+    /// VOUT_SCALE_MONITOR (0x2A)
+    VoutScaleMonitor = 0x2A,
+    /// VOUT_MIN (0x2B)
+    VoutMin = 0x2B,
+    /// COEFFICIENTS (0x30)
+    Coefficients = 0x30,
+    /// POUT_MAX (0x31)
+    PoutMax = 0x31,
+    /// FREQUENCY_SWITCH (0x33)
+    FrequencySwitch = 0x33,
+    /// VIN_ON (0x35)
+    VinOn = 0x35,
+    /// VIN_OFF (0x36)
+    VinOff = 0x36,
+    /// INTERLEAVE (0x37)
+    Interleave = 0x37,
+    /// IOUT_CAL_GAIN (0x38)
+    IoutCalGain = 0x38,
+    /// IOUT_CAL_OFFSET (0x39)
+    IoutCalOffset = 0x39,
+    /// FAN_CONFIG_1_2 (0x3A)
+    FanConfig12 = 0x3A,
+    /// FAN_COMMAND_1 (0x3B)
+    FanCommand1 = 0x3B,
+    /// FAN_COMMAND_2 (0x3C)
+    FanCommand2 = 0x3C,
+    /// FAN_CONFIG_3_4 (0x3D)
+    FanConfig34 = 0x3D,
+    /// FAN_COMMAND_3 (0x3E)
+    FanCommand3 = 0x3E,
+    /// FAN_COMMAND_4 (0x3F)
+    FanCommand4 = 0x3F,
+    /// VOUT_OV_FAULT_LIMIT (0x40)
+    VoutOvFaultLimit = 0x40,
+    /// VOUT_OV_FAULT_RESPONSE (0x41)
+    VoutOvFaultResponse = 0x41,
+    /// VOUT_OV_WARN_LIMIT (0x42)
+    VoutOvWarnLimit = 0x42,
+    /// VOUT_UV_WARN_LIMIT (0x43)
+    VoutUvWarnLimit = 0x43,
+    /// VOUT_UV_FAULT_LIMIT (0x44)
+    VoutUvFaultLimit = 0x44,
+    /// VOUT_UV_FAULT_RESPONSE (0x45)
+    VoutUvFaultResponse = 0x45,
+    /// IOUT_OC_FAULT_LIMIT (0x46)
+    IoutOcFaultLimit = 0x46,
+    /// IOUT_OC_FAULT_RESPONSE (0x47)
+    IoutOcFaultResponse = 0x47,
+    /// IOUT_OC_LV_FAULT_LIMIT (0x48)
+    IoutOcLvFaultLimit = 0x48,
+    /// IOUT_OC_LV_FAULT_RESPONSE (0x49)
+    IoutOcLvFaultResponse = 0x49,
+    /// IOUT_OC_WARN_LIMIT (0x4A)
+    IoutOcWarnLimit = 0x4A,
+    /// IOUT_UC_FAULT_LIMIT (0x4B)
+    IoutUcFaultLimit = 0x4B,
+    /// IOUT_UC_FAULT_RESPONSE (0x4C)
+    IoutUcFaultResponse = 0x4C,
+    /// OT_FAULT_LIMIT (0x4F)
+    OtFaultLimit = 0x4F,
+    /// OT_FAULT_RESPONSE (0x50)
+    OtFaultResponse = 0x50,
+    /// OT_WARN_LIMIT (0x51)
+    OtWarnLimit = 0x51,
+    /// UT_WARN_LIMIT (0x52)
+    UtWarnLimit = 0x52,
+    /// UT_FAULT_LIMIT (0x53)
+    UtFaultLimit = 0x53,
+    /// UT_FAULT_RESPONSE (0x54)
+    UtFaultResponse = 0x54,
+    /// VIN_OV_FAULT_LIMIT (0x55)
+    VinOvFaultLimit = 0x55,
+    /// VIN_OV_FAULT_RESPONSE (0x56)
+    VinOvFaultResponse = 0x56,
+    /// VIN_OV_WARN_LIMIT (0x57)
+    VinOvWarnLimit = 0x57,
+    /// VIN_UV_WARN_LIMIT (0x58)
+    VinUvWarnLimit = 0x58,
+    /// VIN_UV_FAULT_LIMIT (0x59)
+    VinUvFaultLimit = 0x59,
+    /// VIN_UV_FAULT_RESPONSE (0x5A)
+    VinUvFaultResponse = 0x5A,
+    /// IIN_OC_FAULT_LIMIT (0x5B)
+    IinOcFaultLimit = 0x5B,
+    /// IIN_OC_FAULT_RESPONSE (0x5C)
+    IinOcFaultResponse = 0x5C,
+    /// IIN_OC_WARN_LIMIT (0x5D)
+    IinOcWarnLimit = 0x5D,
+    /// POWER_GOOD_ON (0x5E)
+    PowerGoodOn = 0x5E,
+    /// POWER_GOOD_OFF (0x5F)
+    PowerGoodOff = 0x5F,
+    /// TON_DELAY (0x60)
+    TonDelay = 0x60,
+    /// TON_RISE (0x61)
+    TonRise = 0x61,
+    /// TON_MAX_FAULT_LIMIT (0x62)
+    TonMaxFaultLimit = 0x62,
+    /// TON_MAX_FAULT_RESPONSE (0x63)
+    TonMaxFaultResponse = 0x63,
+    /// TOFF_DELAY (0x64)
+    ToffDelay = 0x64,
+    /// TOFF_FALL (0x65)
+    ToffFall = 0x65,
+    /// TOFF_MAX_WARN_LIMIT (0x66)
+    ToffMaxWarnLimit = 0x66,
+    /// POUT_OP_FAULT_LIMIT (0x68)
+    PoutOpFaultLimit = 0x68,
+    /// POUT_OP_FAULT_RESPONSE (0x69)
+    PoutOpFaultResponse = 0x69,
+    /// POUT_OP_WARN_LIMIT (0x6A)
+    PoutOpWarnLimit = 0x6A,
+    /// PIN_OP_WARN_LIMIT (0x6B)
+    PinOpWarnLimit = 0x6B,
+    /// STATUS_BYTE (0x78)
+    StatusByte = 0x78,
+    /// STATUS_WORD (0x79)
+    StatusWord = 0x79,
+    /// STATUS_VOUT (0x7A)
+    StatusVout = 0x7A,
+    /// STATUS_IOUT (0x7B)
+    StatusIout = 0x7B,
+    /// STATUS_INPUT (0x7C)
+    StatusInput = 0x7C,
+    /// STATUS_TEMPERATURE (0x7D)
+    StatusTemperature = 0x7D,
+    /// STATUS_CML (0x7E)
+    StatusCml = 0x7E,
+    /// STATUS_OTHER (0x7F)
+    StatusOther = 0x7F,
+    /// STATUS_MFR_SPECIFIC (0x80)
+    StatusMfrSpecific = 0x80,
+    /// STATUS_FANS_1_2 (0x81)
+    StatusFans12 = 0x81,
+    /// STATUS_FANS_3_4 (0x82)
+    StatusFans34 = 0x82,
+    /// READ_EIN (0x86)
+    ReadEin = 0x86,
+    /// READ_EOUT (0x87)
+    ReadEout = 0x87,
+    /// READ_VIN (0x88)
+    ReadVin = 0x88,
+    /// READ_IIN (0x89)
+    ReadIin = 0x89,
+    /// READ_VCAP (0x8A)
+    ReadVcap = 0x8A,
+    /// READ_VOUT (0x8B)
+    ReadVout = 0x8B,
+    /// READ_IOUT (0x8C)
+    ReadIout = 0x8C,
+    /// READ_TEMPERATURE_1 (0x8D)
+    ReadTemperature1 = 0x8D,
+    /// READ_TEMPERATURE_2 (0x8E)
+    ReadTemperature2 = 0x8E,
+    /// READ_TEMPERATURE_3 (0x8F)
+    ReadTemperature3 = 0x8F,
+    /// READ_FAN_SPEED_1 (0x90)
+    ReadFanSpeed1 = 0x90,
+    /// READ_FAN_SPEED_2 (0x91)
+    ReadFanSpeed2 = 0x91,
+    /// READ_FAN_SPEED_3 (0x92)
+    ReadFanSpeed3 = 0x92,
+    /// READ_FAN_SPEED_4 (0x93)
+    ReadFanSpeed4 = 0x93,
+    /// READ_DUTY_CYCLE (0x94)
+    ReadDutyCycle = 0x94,
+    /// READ_FREQUENCY (0x95)
+    ReadFrequency = 0x95,
+    /// READ_POUT (0x96)
+    ReadPout = 0x96,
+    /// READ_PIN (0x97)
+    ReadPin = 0x97,
+    /// PMBUS_REVISION (0x98)
+    PmbusRevision = 0x98,
+    /// MFR_ID (0x99)
+    MfrId = 0x99,
+    /// MFR_MODEL (0x9A)
+    MfrModel = 0x9A,
+    /// MFR_REVISION (0x9B)
+    MfrRevision = 0x9B,
+    /// MFR_LOCATION (0x9C)
+    MfrLocation = 0x9C,
+    /// MFR_DATE (0x9D)
+    MfrDate = 0x9D,
+    /// MFR_SERIAL (0x9E)
+    MfrSerial = 0x9E,
+    /// APP_PROFILE_SUPPORT (0x9F)
+    AppProfileSupport = 0x9F,
+    /// MFR_VIN_MIN (0xA0)
+    MfrVinMin = 0xA0,
+    /// MFR_VIN_MAX (0xA1)
+    MfrVinMax = 0xA1,
+    /// MFR_IIN_MAX (0xA2)
+    MfrIinMax = 0xA2,
+    /// MFR_PIN_MAX (0xA3)
+    MfrPinMax = 0xA3,
+    /// MFR_VOUT_MIN (0xA4)
+    MfrVoutMin = 0xA4,
+    /// MFR_VOUT_MAX (0xA5)
+    MfrVoutMax = 0xA5,
+    /// MFR_IOUT_MAX (0xA6)
+    MfrIoutMax = 0xA6,
+    /// MFR_POUT_MAX (0xA7)
+    MfrPoutMax = 0xA7,
+    /// MFR_TAMBIENT_MAX (0xA8)
+    MfrTambientMax = 0xA8,
+    /// MFR_TAMBIENT_MIN (0xA9)
+    MfrTambientMin = 0xA9,
+    /// MFR_EFFICIENCY_LL (0xAA)
+    MfrEfficiencyLl = 0xAA,
+    /// MFR_EFFICIENCY_HL (0xAB)
+    MfrEfficiencyHl = 0xAB,
+    /// MFR_PIN_ACCURACY (0xAC)
+    MfrPinAccuracy = 0xAC,
+    /// IC_DEVICE_ID (0xAD)
+    IcDeviceId = 0xAD,
+    /// IC_DEVICE_REV (0xAE)
+    IcDeviceRev = 0xAE,
+}
+
+impl From<Command> for u8 {
+    fn from(command: Command) -> u8 {
+        command as u8
+    }
+}
+
+bitflags! {
+    /// Summary of the device status, as read with `STATUS_WORD`
+    ///
+    /// The low byte is `STATUS_BYTE`.  Most bits point at one of the
+    /// detailed `STATUS_*` registers.
+    pub struct StatusWord: u16 {
+        /// A fault other than those listed here
+        const NONE_OF_THE_ABOVE = 1 << 0;
+        /// Communication, memory or logic fault, see `STATUS_CML`
+        const CML = 1 << 1;
+        /// Temperature fault or warning, see `STATUS_TEMPERATURE`
+        const TEMPERATURE = 1 << 2;
+        /// Input undervoltage fault
+        const VIN_UV_FAULT = 1 << 3;
+        /// Output overcurrent fault
+        const IOUT_OC_FAULT = 1 << 4;
+        /// Output overvoltage fault
+        const VOUT_OV_FAULT = 1 << 5;
+        /// The unit is not providing power
+        const OFF = 1 << 6;
+        /// The device was busy and could not respond
+        const BUSY = 1 << 7;
+        /// Not listed in `STATUS_BYTE` or elsewhere in `STATUS_WORD`
+        const UNKNOWN = 1 << 8;
+        /// See `STATUS_OTHER`
+        const OTHER = 1 << 9;
+        /// Fan fault or warning, see `STATUS_FANS_1_2`
+        const FANS = 1 << 10;
+        /// The POWER_GOOD signal is negated
+        const POWER_GOOD_N = 1 << 11;
+        /// See `STATUS_MFR_SPECIFIC`
+        const MFR_SPECIFIC = 1 << 12;
+        /// Input fault or warning, see `STATUS_INPUT`
+        const INPUT = 1 << 13;
+        /// Output current or power fault or warning, see `STATUS_IOUT`
+        const IOUT_POUT = 1 << 14;
+        /// Output voltage fault or warning, see `STATUS_VOUT`
+        const VOUT = 1 << 15;
+    }
+}
+
+bitflags! {
+    /// Output voltage status, as read with `STATUS_VOUT`
+    pub struct StatusVout: u8 {
+        /// The output voltage is tracking its reference too slowly
+        const TRACKING_ERROR = 1 << 0;
+        /// TOFF_MAX warning
+        const TOFF_MAX_WARNING = 1 << 1;
+        /// TON_MAX fault
+        const TON_MAX_FAULT = 1 << 2;
+        /// An attempt was made to set the output beyond VOUT_MAX or VOUT_MIN
+        const MAX_MIN_WARNING = 1 << 3;
+        /// Output undervoltage fault
+        const UV_FAULT = 1 << 4;
+        /// Output undervoltage warning
+        const UV_WARNING = 1 << 5;
+        /// Output overvoltage warning
+        const OV_WARNING = 1 << 6;
+        /// Output overvoltage fault
+        const OV_FAULT = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// Output current and power status, as read with `STATUS_IOUT`
+    pub struct StatusIout: u8 {
+        /// Output overpower warning
+        const POUT_OP_WARNING = 1 << 0;
+        /// Output overpower fault
+        const POUT_OP_FAULT = 1 << 1;
+        /// The unit is limiting its output power
+        const POWER_LIMITING = 1 << 2;
+        /// Current share fault
+        const CURRENT_SHARE_FAULT = 1 << 3;
+        /// Output undercurrent fault
+        const UC_FAULT = 1 << 4;
+        /// Output overcurrent warning
+        const OC_WARNING = 1 << 5;
+        /// Output overcurrent and low voltage fault
+        const OC_LV_FAULT = 1 << 6;
+        /// Output overcurrent fault
+        const OC_FAULT = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// Input status, as read with `STATUS_INPUT`
+    pub struct StatusInput: u8 {
+        /// Input overpower warning
+        const PIN_OP_WARNING = 1 << 0;
+        /// Input overcurrent warning
+        const IIN_OC_WARNING = 1 << 1;
+        /// Input overcurrent fault
+        const IIN_OC_FAULT = 1 << 2;
+        /// The unit is off for insufficient input voltage
+        const UNIT_OFF_LOW_INPUT = 1 << 3;
+        /// Input undervoltage fault
+        const VIN_UV_FAULT = 1 << 4;
+        /// Input undervoltage warning
+        const VIN_UV_WARNING = 1 << 5;
+        /// Input overvoltage warning
+        const VIN_OV_WARNING = 1 << 6;
+        /// Input overvoltage fault
+        const VIN_OV_FAULT = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// Temperature status, as read with `STATUS_TEMPERATURE`
+    pub struct StatusTemperature: u8 {
+        /// Undertemperature fault
+        const UT_FAULT = 1 << 4;
+        /// Undertemperature warning
+        const UT_WARNING = 1 << 5;
+        /// Overtemperature warning
+        const OT_WARNING = 1 << 6;
+        /// Overtemperature fault
+        const OT_FAULT = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// Communication, memory and logic status, as read with `STATUS_CML`
+    pub struct StatusCml: u8 {
+        /// Other memory or logic fault
+        const OTHER_MEMORY_LOGIC_FAULT = 1 << 0;
+        /// Other communication fault
+        const OTHER_COMMUNICATION_FAULT = 1 << 1;
+        /// Processor fault
+        const PROCESSOR_FAULT = 1 << 3;
+        /// Memory fault
+        const MEMORY_FAULT = 1 << 4;
+        /// Packet error check failed
+        const PEC_FAILED = 1 << 5;
+        /// Invalid or unsupported data received
+        const INVALID_DATA = 1 << 6;
+        /// Invalid or unsupported command received
+        const INVALID_COMMAND = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// Fan status, as read with `STATUS_FANS_1_2`
+    pub struct StatusFans12: u8 {
+        /// Airflow warning
+        const AIRFLOW_WARNING = 1 << 0;
+        /// Airflow fault
+        const AIRFLOW_FAULT = 1 << 1;
+        /// Fan 2 speed overridden
+        const FAN2_SPEED_OVERRIDDEN = 1 << 2;
+        /// Fan 1 speed overridden
+        const FAN1_SPEED_OVERRIDDEN = 1 << 3;
+        /// Fan 2 warning
+        const FAN2_WARNING = 1 << 4;
+        /// Fan 1 warning
+        const FAN1_WARNING = 1 << 5;
+        /// Fan 2 fault
+        const FAN2_FAULT = 1 << 6;
+        /// Fan 1 fault
+        const FAN1_FAULT = 1 << 7;
+    }
+}
+
+/// Output voltage data format, as read with `VOUT_MODE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoutMode {
+    /// ULINEAR16 with the given exponent
+    Linear(i8),
+    /// VID codes of the given VID code type
+    Vid(u8),
+    /// DIRECT, with coefficients from `COEFFICIENTS` or the datasheet
+    Direct,
+    /// IEEE 754 half precision
+    Ieee754Half,
+}
+
+impl VoutMode {
+    /// Decode a `VOUT_MODE` byte
+    pub fn from_byte(byte: u8) -> VoutMode {
+        let parameter = byte & 0x1F;
+        match byte >> 5 & 0x03 {
+            0 => VoutMode::Linear(sign_extend5(parameter)),
+            1 => VoutMode::Vid(parameter),
+            2 => VoutMode::Direct,
+            _ => VoutMode::Ieee754Half,
+        }
+    }
+}
+
+/// Coefficients of the DIRECT data format
+///
+/// A value X is sent as Y = (m * X + b) * 10^R, a signed 16 bit integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coefficients {
+    /// Slope
+    pub m: i16,
+    /// Offset
+    pub b: i16,
+    /// Exponent
+    pub r: i8,
+}
+
+impl Coefficients {
+    /// Convert a DIRECT word to its value
+    pub fn decode(&self, raw: u16) -> f64 {
+        let y = f64::from(raw as i16);
+        (y / 10f64.powi(i32::from(self.r)) - f64::from(self.b)) / f64::from(self.m)
+    }
+
+    /// Convert a value to a DIRECT word, if it is representable
+    pub fn encode(&self, value: f64) -> Option<u16> {
+        let y = ((f64::from(self.m) * value + f64::from(self.b)) * 10f64.powi(i32::from(self.r)))
+            .round();
+        if y >= f64::from(i16::MIN) && y <= f64::from(i16::MAX) {
+            Some(y as i16 as u16)
+        } else {
+            None
+        }
+    }
+}
+
+fn sign_extend5(bits: u8) -> i8 {
+    ((bits << 3) as i8) >> 3
+}
+
+/// Convert a LINEAR11 word to its value
+///
+/// The top 5 bits are a signed exponent N and the low 11 bits a signed
+/// mantissa Y, for a value of Y * 2^N.
+pub fn linear11_to_f64(raw: u16) -> f64 {
+    let exponent = sign_extend5((raw >> 11) as u8);
+    let mantissa = ((raw << 5) as i16) >> 5;
+    f64::from(mantissa) * 2f64.powi(i32::from(exponent))
+}
+
+/// Convert a value to a LINEAR11 word, if it is representable
+///
+/// The smallest exponent leaving the mantissa in range is used, keeping
+/// as much precision as possible.
+pub fn f64_to_linear11(value: f64) -> Option<u16> {
+    if !value.is_finite() {
+        return None;
+    }
+    (-16..16)
+        .map(|exponent: i32| (exponent, (value / 2f64.powi(exponent)).round()))
+        .find(|&(_, mantissa)| (-1024.0..1024.0).contains(&mantissa))
+        .map(|(exponent, mantissa)| {
+            ((exponent as u16 & 0x1F) << 11) | (mantissa as i16 as u16 & 0x7FF)
+        })
+}
+
+/// Convert a ULINEAR16 word to its value, given the `VOUT_MODE` exponent
+pub fn ulinear16_to_f64(raw: u16, exponent: i8) -> f64 {
+    f64::from(raw) * 2f64.powi(i32::from(exponent))
+}
+
+/// Convert a value to a ULINEAR16 word, if it is representable
+pub fn f64_to_ulinear16(value: f64, exponent: i8) -> Option<u16> {
+    let raw = (value / 2f64.powi(i32::from(exponent))).round();
+    if raw >= 0.0 && raw <= f64::from(u16::MAX) {
+        Some(raw as u16)
+    } else {
+        None
+    }
+}
+
+/// PMBus device
+///
+/// The selected `PAGE` and `PHASE` are remembered, so selecting the one
+/// already selected costs no transfer.  If something else may change
+/// them, such as another process, call `forget_selection` first.
+pub struct Pmbus<T> {
+    dev: T,
+    page: Option<u8>,
+    phase: Option<u8>,
+}
+
+impl<T> Pmbus<T>
+where
+    T: I2CDevice,
+    T::Error: From<io::Error>,
+{
+    /// Wrap a PMBus device
+    pub fn new(dev: T) -> Pmbus<T> {
+        Pmbus {
+            dev,
+            page: None,
+            phase: None,
+        }
+    }
+
+    /// Get a reference to the wrapped device
+    pub fn inner(&self) -> &T {
+        &self.dev
+    }
+
+    /// Get a mutable reference to the wrapped device
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.dev
+    }
+
+    /// Unwrap the device
+    pub fn into_inner(self) -> T {
+        self.dev
+    }
+
+    /// Select the page subsequent commands apply to
+    ///
+    /// Page 0xFF selects all pages for writes.
+    pub fn set_page(&mut self, page: u8) -> Result<(), T::Error> {
+        if self.page != Some(page) {
+            self.page = None;
+            self.dev.smbus_write_byte_data(Command::Page as u8, page)?;
+            self.page = Some(page);
+        }
+        Ok(())
+    }
+
+    /// The selected page, if known
+    pub fn page(&self) -> Option<u8> {
+        self.page
+    }
+
+    /// Select the phase subsequent commands apply to
+    ///
+    /// Phase 0xFF selects all phases.
+    pub fn set_phase(&mut self, phase: u8) -> Result<(), T::Error> {
+        if self.phase != Some(phase) {
+            self.phase = None;
+            self.dev
+                .smbus_write_byte_data(Command::Phase as u8, phase)?;
+            self.phase = Some(phase);
+        }
+        Ok(())
+    }
+
+    /// The selected phase, if known
+    pub fn phase(&self) -> Option<u8> {
+        self.phase
+    }
+
+    /// Forget the selected page and phase, so they are written again
+    pub fn forget_selection(&mut self) {
+        self.page = None;
+        self.phase = None;
+    }
+
+    /// Send a command without data, such as `CLEAR_FAULTS`
+    pub fn send_byte<C: Into<u8>>(&mut self, command: C) -> Result<(), T::Error> {
+        self.dev.smbus_write_byte(command.into())
+    }
+
+    /// Read a byte command
+    pub fn read_byte<C: Into<u8>>(&mut self, command: C) -> Result<u8, T::Error> {
+        self.dev.smbus_read_byte_data(command.into())
+    }
+
+    /// Write a byte command
+    pub fn write_byte<C: Into<u8>>(&mut self, command: C, value: u8) -> Result<(), T::Error> {
+        self.dev.smbus_write_byte_data(command.into(), value)
+    }
+
+    /// Read a word command
+    pub fn read_word<C: Into<u8>>(&mut self, command: C) -> Result<u16, T::Error> {
+        self.dev.smbus_read_word_data(command.into())
+    }
+
+    /// Write a word command
+    pub fn write_word<C: Into<u8>>(&mut self, command: C, value: u16) -> Result<(), T::Error> {
+        self.dev.smbus_write_word_data(command.into(), value)
+    }
+
+    /// Read a block command, such as `MFR_ID`
+    pub fn read_block<C: Into<u8>>(&mut self, command: C) -> Result<Vec<u8>, T::Error> {
+        self.dev.smbus_read_block_data(command.into())
+    }
+
+    /// Write a block command
+    pub fn write_block<C: Into<u8>>(&mut self, command: C, data: &[u8]) -> Result<(), T::Error> {
+        self.dev.smbus_write_block_data(command.into(), data)
+    }
+
+    /// Read a block command holding text, such as `MFR_MODEL`
+    ///
+    /// Invalid UTF-8 is replaced, and trailing NUL bytes and spaces are
+    /// removed.
+    pub fn read_string<C: Into<u8>>(&mut self, command: C) -> Result<String, T::Error> {
+        let block = self.read_block(command)?;
+        let text = String::from_utf8_lossy(&block);
+        Ok(text.trim_end_matches(&['\0', ' '][..]).to_string())
+    }
+
+    /// Clear the fault and warning bits of the selected page
+    pub fn clear_faults(&mut self) -> Result<(), T::Error> {
+        self.send_byte(Command::ClearFaults)
+    }
+
+    /// Read `STATUS_WORD`
+    pub fn status_word(&mut self) -> Result<StatusWord, T::Error> {
+        Ok(StatusWord::from_bits_truncate(
+            self.read_word(Command::StatusWord)?,
+        ))
+    }
+
+    /// Read `STATUS_VOUT`
+    pub fn status_vout(&mut self) -> Result<StatusVout, T::Error> {
+        Ok(StatusVout::from_bits_truncate(
+            self.read_byte(Command::StatusVout)?,
+        ))
+    }
+
+    /// Read `STATUS_IOUT`
+    pub fn status_iout(&mut self) -> Result<StatusIout, T::Error> {
+        Ok(StatusIout::from_bits_truncate(
+            self.read_byte(Command::StatusIout)?,
+        ))
+    }
+
+    /// Read `STATUS_INPUT`
+    pub fn status_input(&mut self) -> Result<StatusInput, T::Error> {
+        Ok(StatusInput::from_bits_truncate(
+            self.read_byte(Command::StatusInput)?,
+        ))
+    }
+
+    /// Read `STATUS_TEMPERATURE`
+    pub fn status_temperature(&mut self) -> Result<StatusTemperature, T::Error> {
+        Ok(StatusTemperature::from_bits_truncate(
+            self.read_byte(Command::StatusTemperature)?,
+        ))
+    }
+
+    /// Read `STATUS_CML`
+    pub fn status_cml(&mut self) -> Result<StatusCml, T::Error> {
+        Ok(StatusCml::from_bits_truncate(
+            self.read_byte(Command::StatusCml)?,
+        ))
+    }
+
+    /// Read `STATUS_FANS_1_2`
+    pub fn status_fans_1_2(&mut self) -> Result<StatusFans12, T::Error> {
+        Ok(StatusFans12::from_bits_truncate(
+            self.read_byte(Command::StatusFans12)?,
+        ))
+    }
+
+    /// Read `VOUT_MODE`
+    pub fn vout_mode(&mut self) -> Result<VoutMode, T::Error> {
+        Ok(VoutMode::from_byte(self.read_byte(Command::VoutMode)?))
+    }
+
+    /// Read the DIRECT coefficients of `command` with `COEFFICIENTS`
+    ///
+    /// `read` selects the coefficients for data read from the device
+    /// rather than written to it.  Many devices do not implement this
+    /// command, the coefficients are then found in their datasheet.
+    pub fn coefficients<C: Into<u8>>(
+        &mut self,
+        command: C,
+        read: bool,
+    ) -> Result<Coefficients, T::Error> {
+        let reply = self
+            .dev
+            .smbus_process_block(Command::Coefficients as u8, &[command.into(), read as u8])?;
+        if reply.len() != 5 {
+            return Err(invalid_data("COEFFICIENTS reply is not 5 bytes").into());
+        }
+        Ok(Coefficients {
+            m: i16::from_le_bytes([reply[0], reply[1]]),
+            b: i16::from_le_bytes([reply[2], reply[3]]),
+            r: reply[4] as i8,
+        })
+    }
+
+    /// Read a LINEAR11 command, such as `READ_IOUT` or `READ_TEMPERATURE_1`
+    pub fn read_linear11<C: Into<u8>>(&mut self, command: C) -> Result<f64, T::Error> {
+        Ok(linear11_to_f64(self.read_word(command)?))
+    }
+
+    /// Write a LINEAR11 command, such as `IOUT_OC_FAULT_LIMIT`
+    pub fn write_linear11<C: Into<u8>>(&mut self, command: C, value: f64) -> Result<(), T::Error> {
+        let raw =
+            f64_to_linear11(value).ok_or_else(|| invalid_input("value out of LINEAR11 range"))?;
+        self.write_word(command, raw)
+    }
+
+    /// Read a DIRECT command with known coefficients
+    pub fn read_direct<C: Into<u8>>(
+        &mut self,
+        command: C,
+        coefficients: &Coefficients,
+    ) -> Result<f64, T::Error> {
+        Ok(coefficients.decode(self.read_word(command)?))
+    }
+
+    /// Write a DIRECT command with known coefficients
+    pub fn write_direct<C: Into<u8>>(
+        &mut self,
+        command: C,
+        coefficients: &Coefficients,
+        value: f64,
+    ) -> Result<(), T::Error> {
+        let raw = coefficients
+            .encode(value)
+            .ok_or_else(|| invalid_input("value out of DIRECT range"))?;
+        self.write_word(command, raw)
+    }
+
+    /// Read an output voltage command, such as `READ_VOUT` or `VOUT_MAX`
+    ///
+    /// The format is taken from `VOUT_MODE` of the selected page, reading
+    /// the coefficients with `COEFFICIENTS` in DIRECT mode.  VID and IEEE
+    /// 754 formats are not supported.
+    pub fn read_vout<C: Into<u8>>(&mut self, command: C) -> Result<f64, T::Error> {
+        let command = command.into();
+        match self.vout_mode()? {
+            VoutMode::Linear(exponent) => Ok(ulinear16_to_f64(self.read_word(command)?, exponent)),
+            VoutMode::Direct => {
+                let coefficients = self.coefficients(command, true)?;
+                self.read_direct(command, &coefficients)
+            }
+            _ => Err(invalid_data("unsupported VOUT_MODE").into()),
+        }
+    }
+
+    /// Write an output voltage command, such as `VOUT_COMMAND`
+    ///
+    /// See `read_vout` for the supported formats.
+    pub fn write_vout<C: Into<u8>>(&mut self, command: C, value: f64) -> Result<(), T::Error> {
+        let command = command.into();
+        match self.vout_mode()? {
+            VoutMode::Linear(exponent) => {
+                let raw = f64_to_ulinear16(value, exponent)
+                    .ok_or_else(|| invalid_input("value out of ULINEAR16 range"))?;
+                self.write_word(command, raw)
+            }
+            VoutMode::Direct => {
+                let coefficients = self.coefficients(command, false)?;
+                self.write_direct(command, &coefficients, value)
+            }
+            _ => Err(invalid_data("unsupported VOUT_MODE").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{MockI2CScript, MockI2CTransaction as T};
+
+    #[test]
+    fn linear11_conversion() {
+        // exponent -2, mantissa 0x0D5 = 213
+        assert_eq!(linear11_to_f64(0xF0D5), 53.25);
+        // exponent -3, negative mantissa
+        assert_eq!(linear11_to_f64(0xEFF0), -2.0);
+        // positive exponent
+        assert_eq!(linear11_to_f64(0x0803), 6.0);
+        assert_eq!(linear11_to_f64(0), 0.0);
+
+        for &value in &[53.25, -2.0, 6.0, 0.0, 1023.0, -1024.0, 0.001, 12.345] {
+            let raw = f64_to_linear11(value).unwrap();
+            let error = (linear11_to_f64(raw) - value).abs();
+            assert!(error <= value.abs() / 1000.0 + 1e-5, "{} -> {}", value, raw);
+        }
+        assert_eq!(f64_to_linear11(53.25), Some(0xE354));
+        assert_eq!(f64_to_linear11(2e9), None);
+        assert_eq!(f64_to_linear11(f64::NAN), None);
+    }
+
+    #[test]
+    fn linear16_and_direct_conversion() {
+        assert_eq!(VoutMode::from_byte(0x17), VoutMode::Linear(-9));
+        assert_eq!(VoutMode::from_byte(0x40), VoutMode::Direct);
+        assert_eq!(VoutMode::from_byte(0x21), VoutMode::Vid(1));
+        assert_eq!(ulinear16_to_f64(0x0600, -9), 3.0);
+        assert_eq!(f64_to_ulinear16(3.0, -9), Some(0x0600));
+        assert_eq!(f64_to_ulinear16(-1.0, -9), None);
+
+        let coefficients = Coefficients { m: 2, b: 10, r: 1 };
+        assert_eq!(coefficients.encode(4.5), Some(190));
+        assert_eq!(coefficients.decode(190), 4.5);
+        assert_eq!(coefficients.decode(0xFF9C), -10.0);
+        assert_eq!(coefficients.encode(1e6), None);
+    }
+
+    #[test]
+    fn vout_follows_vout_mode() {
+        let mut pmbus = Pmbus::new(MockI2CScript::new(vec![
+            T::SmbusReadByteData(0x20, 0x17),
+            T::SmbusReadWordData(0x8B, 0x0600),
+            T::SmbusReadByteData(0x20, 0x40),
+            T::SmbusProcessBlock(0x30, vec![0x8B, 1], vec![0x02, 0x00, 0x0A, 0x00, 0x01]),
+            T::SmbusReadWordData(0x8B, 190),
+            T::SmbusReadByteData(0x20, 0x17),
+            T::SmbusWriteWordData(0x21, 0x0680),
+            T::SmbusReadByteData(0x20, 0x21),
+        ]));
+        assert_eq!(pmbus.read_vout(Command::ReadVout).unwrap(), 3.0);
+        assert_eq!(pmbus.read_vout(Command::ReadVout).unwrap(), 4.5);
+        pmbus.write_vout(Command::VoutCommand, 3.25).unwrap();
+        assert!(pmbus.read_vout(Command::ReadVout).is_err());
+        pmbus.into_inner().done();
+    }
+
+    #[test]
+    fn page_and_phase_are_cached() {
+        let mut pmbus = Pmbus::new(MockI2CScript::new(vec![
+            T::SmbusWriteByteData(0x00, 1),
+            T::SmbusWriteByteData(0x04, 0xFF),
+            T::SmbusWriteByteData(0x00, 0),
+            T::SmbusWriteByteData(0x00, 0),
+        ]));
+        pmbus.set_page(1).unwrap();
+        pmbus.set_page(1).unwrap();
+        pmbus.set_phase(0xFF).unwrap();
+        assert_eq!(pmbus.page(), Some(1));
+        assert_eq!(pmbus.phase(), Some(0xFF));
+        pmbus.set_page(0).unwrap();
+        pmbus.forget_selection();
+        pmbus.set_page(0).unwrap();
+        pmbus.into_inner().done();
+    }
+
+    #[test]
+    fn status_and_strings() {
+        let mut pmbus = Pmbus::new(MockI2CScript::new(vec![
+            T::SmbusReadWordData(0x79, 0x8850),
+            T::SmbusReadByteData(0x7A, 0x90),
+            T::SmbusWriteByte(0x03),
+            T::SmbusReadBlockData(0x99, b"ACME  ".to_vec()),
+            T::SmbusReadWordData(0x8D, 0xF0D5),
+        ]));
+        assert_eq!(
+            pmbus.status_word().unwrap(),
+            StatusWord::VOUT
+                | StatusWord::POWER_GOOD_N
+                | StatusWord::OFF
+                | StatusWord::IOUT_OC_FAULT
+        );
+        assert_eq!(
+            pmbus.status_vout().unwrap(),
+            StatusVout::OV_FAULT | StatusVout::UV_FAULT
+        );
+        pmbus.clear_faults().unwrap();
+        assert_eq!(pmbus.read_string(Command::MfrId).unwrap(), "ACME");
+        assert_eq!(
+            pmbus.read_linear11(Command::ReadTemperature1).unwrap(),
+            53.25
+        );
+        pmbus.into_inner().done();
+    }
+}
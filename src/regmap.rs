@@ -0,0 +1,673 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Typed register access
+//!
+//! Most I2C devices expose their functionality as a set of registers which
+//! are selected by sending a register address and then read or written.  A
+//! `Regmap` takes care of formatting register addresses and values so that
+//! drivers can deal in plain integers.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::linux::LinuxI2CError> {
+//! use i2cdev::linux::LinuxI2CDevice;
+//! use i2cdev::regmap::{Endianness, Regmap, ValueWidth};
+//!
+//! let dev = LinuxI2CDevice::new("/dev/i2c-1", 0x40)?;
+//! let mut regmap = Regmap::new(dev)
+//!     .with_value_width(ValueWidth::Sixteen)
+//!     .with_endianness(Endianness::Big);
+//! let config = regmap.read_reg(0x00)?;
+//! regmap.update_bits(0x00, 0x0007, 0x0005)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::{invalid_input, I2CDevice, SMBUS_BLOCK_MAX};
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+/// Width of the register addresses of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressWidth {
+    /// 8 bit register addresses, the default
+    Eight,
+    /// 16 bit register addresses, sent most significant byte first
+    Sixteen,
+}
+
+impl AddressWidth {
+    fn max(self) -> u16 {
+        match self {
+            AddressWidth::Eight => 0xFF,
+            AddressWidth::Sixteen => 0xFFFF,
+        }
+    }
+}
+
+/// Width of the register values of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueWidth {
+    /// 8 bit values, the default
+    Eight,
+    /// 16 bit values
+    Sixteen,
+    /// 32 bit values
+    ThirtyTwo,
+}
+
+impl ValueWidth {
+    /// Number of bytes in a value
+    pub fn bytes(self) -> usize {
+        match self {
+            ValueWidth::Eight => 1,
+            ValueWidth::Sixteen => 2,
+            ValueWidth::ThirtyTwo => 4,
+        }
+    }
+
+    fn max(self) -> u32 {
+        match self {
+            ValueWidth::Eight => 0xFF,
+            ValueWidth::Sixteen => 0xFFFF,
+            ValueWidth::ThirtyTwo => 0xFFFF_FFFF,
+        }
+    }
+}
+
+/// Byte order of multi-byte register values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    /// Most significant byte first, the default
+    Big,
+    /// Least significant byte first, as in SMBus words
+    Little,
+}
+
+/// Register map over an `I2CDevice`
+///
+/// Registers are addressed with `u16` and hold `u32` values, whatever the
+/// configured widths; addresses and values which do not fit are rejected
+/// with an `InvalidInput` error.  Bulk accesses rely on the device
+/// auto-incrementing its register address by one after each value.
+///
+/// With 8 bit register addresses, single 8 and 16 bit registers are
+/// accessed with the SMBus byte and word data commands, which even
+/// SMBus-only controllers support.  Bulk accesses and 32 bit registers use
+/// the SMBus I2C block commands.  Either way reads select the register with
+/// a repeated start.  With 16 bit register addresses the address is
+/// written, followed by a separate read.
+///
+/// Like the kernel's regcache, an optional cache saves bus traffic for
+/// registers which only change when written, and can restore the device
+/// configuration after a reset.  Registers can be declared volatile,
+/// read-only or write-only, and given default values.
+pub struct Regmap<T> {
+    dev: T,
+    address_width: AddressWidth,
+    value_width: ValueWidth,
+    endianness: Endianness,
+    volatile: HashSet<u16>,
+    read_only: HashSet<u16>,
+    write_only: HashSet<u16>,
+    defaults: HashMap<u16, u32>,
+    cache: Option<HashMap<u16, CachedValue>>,
+    cache_only: bool,
+}
+
+/// Register value held by the cache of a `Regmap`
+struct CachedValue {
+    value: u32,
+    /// Set if the value has not been written to the device
+    dirty: bool,
+}
+
+impl<T> Regmap<T>
+where
+    T: I2CDevice,
+    T::Error: From<io::Error>,
+{
+    /// Wrap a device with 8 bit register addresses and 8 bit values
+    pub fn new(dev: T) -> Regmap<T> {
+        Regmap {
+            dev,
+            address_width: AddressWidth::Eight,
+            value_width: ValueWidth::Eight,
+            endianness: Endianness::Big,
+            volatile: HashSet::new(),
+            read_only: HashSet::new(),
+            write_only: HashSet::new(),
+            defaults: HashMap::new(),
+            cache: None,
+            cache_only: false,
+        }
+    }
+
+    /// Set the register address width
+    pub fn with_address_width(mut self, width: AddressWidth) -> Self {
+        self.address_width = width;
+        self
+    }
+
+    /// Set the register value width
+    pub fn with_value_width(mut self, width: ValueWidth) -> Self {
+        self.value_width = width;
+        self
+    }
+
+    /// Set the byte order of register values
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Get a reference to the wrapped device
+    pub fn inner(&self) -> &T {
+        &self.dev
+    }
+
+    /// Get a mutable reference to the wrapped device
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.dev
+    }
+
+    /// Unwrap the device
+    pub fn into_inner(self) -> T {
+        self.dev
+    }
+
+    /// Read a single register
+    pub fn read_reg(&mut self, register: u16) -> Result<u32, T::Error> {
+        Ok(self.read_regs(register, 1)?[0])
+    }
+
+    /// Write a single register
+    pub fn write_reg(&mut self, register: u16, value: u32) -> Result<(), T::Error> {
+        self.write_regs(register, &[value])
+    }
+
+    /// Read-modify-write the bits of a register selected by `mask`
+    ///
+    /// The register is only written if its value changes, which is
+    /// reported by the return value.
+    pub fn update_bits(&mut self, register: u16, mask: u32, value: u32) -> Result<bool, T::Error> {
+        let old = self.read_reg(register)?;
+        let new = (old & !mask) | (value & mask);
+        if new == old {
+            return Ok(false);
+        }
+        self.write_reg(register, new)?;
+        Ok(true)
+    }
+
+    /// Read `count` consecutive registers starting at `register`
+    ///
+    /// With the cache enabled, the bus is skipped if every register is
+    /// cached.
+    pub fn read_regs(&mut self, register: u16, count: usize) -> Result<Vec<u32>, T::Error> {
+        self.check_range(register, count)?;
+        let registers = register..=register + (count - 1) as u16;
+        let cached: Vec<Option<u32>> = registers.clone().map(|r| self.cached(r)).collect();
+        if cached.iter().all(Option::is_some) {
+            return Ok(cached.into_iter().map(Option::unwrap).collect());
+        }
+        if registers
+            .clone()
+            .zip(&cached)
+            .any(|(r, c)| c.is_none() && self.write_only.contains(&r))
+        {
+            return Err(invalid_input("register is write-only").into());
+        }
+        if self.cache_only {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "register is not cached and the cache is in cache-only mode",
+            )
+            .into());
+        }
+
+        let mut values = self.read_bus(register, count)?;
+        for ((r, value), cached) in registers.zip(values.iter_mut()).zip(cached) {
+            match cached {
+                // a pending cache-only write takes precedence over the bus
+                Some(cached) if self.is_dirty(r) => *value = cached,
+                _ => self.cache_store(r, *value, false),
+            }
+        }
+        Ok(values)
+    }
+
+    /// Write consecutive registers starting at `register`
+    ///
+    /// In cache-only mode the values are only stored in the cache, to be
+    /// written to the device by `sync()`.
+    pub fn write_regs(&mut self, register: u16, values: &[u32]) -> Result<(), T::Error> {
+        self.check_range(register, values.len())?;
+        if values.iter().any(|&v| v > self.value_width.max()) {
+            return Err(invalid_input("value exceeds the register width").into());
+        }
+        let registers = register..=register + (values.len() - 1) as u16;
+        if registers.clone().any(|r| self.read_only.contains(&r)) {
+            return Err(invalid_input("register is read-only").into());
+        }
+        if self.cache_only {
+            for (r, &value) in registers.zip(values) {
+                self.cache_store(r, value, true);
+            }
+            return Ok(());
+        }
+
+        self.write_bus(register, values)?;
+        for (r, &value) in registers.zip(values) {
+            self.cache_store(r, value, false);
+        }
+        Ok(())
+    }
+
+    /// Enable caching of register values
+    ///
+    /// Reads of cached registers are served without accessing the bus,
+    /// except for volatile registers which are never cached.
+    pub fn with_cache(mut self) -> Self {
+        if self.cache.is_none() {
+            self.cache = Some(HashMap::new());
+        }
+        self
+    }
+
+    /// Declare registers whose value may change behind the driver's back,
+    /// such as status registers, so they are never cached
+    pub fn with_volatile<I: IntoIterator<Item = u16>>(mut self, registers: I) -> Self {
+        self.volatile.extend(registers);
+        self
+    }
+
+    /// Declare registers which can only be read
+    pub fn with_read_only<I: IntoIterator<Item = u16>>(mut self, registers: I) -> Self {
+        self.read_only.extend(registers);
+        self
+    }
+
+    /// Declare registers which can only be written
+    ///
+    /// Such registers can still be read back from the cache once written.
+    pub fn with_write_only<I: IntoIterator<Item = u16>>(mut self, registers: I) -> Self {
+        self.write_only.extend(registers);
+        self
+    }
+
+    /// Declare the value a register holds after reset
+    ///
+    /// With the cache enabled, the default is returned until the register
+    /// is written, without reading the device.
+    pub fn with_default(mut self, register: u16, value: u32) -> Self {
+        self.defaults.insert(register, value);
+        self
+    }
+
+    /// Enter or leave cache-only mode
+    ///
+    /// While the device is powered down, writes can be collected in the
+    /// cache and later applied with `sync()`.  Has no effect unless the
+    /// cache is enabled.
+    pub fn set_cache_only(&mut self, cache_only: bool) {
+        self.cache_only = cache_only && self.cache.is_some();
+    }
+
+    /// Mark every cached and defaulted register as dirty
+    ///
+    /// Use this after the device was reset or power cycled, so that the
+    /// next `sync()` restores its whole configuration.
+    pub fn mark_dirty(&mut self) {
+        let defaults = &self.defaults;
+        let volatile = &self.volatile;
+        if let Some(ref mut cache) = self.cache {
+            for (&register, &value) in defaults {
+                if !volatile.contains(&register) {
+                    cache
+                        .entry(register)
+                        .or_insert(CachedValue { value, dirty: true });
+                }
+            }
+            for entry in cache.values_mut() {
+                entry.dirty = true;
+            }
+        }
+    }
+
+    /// Write the dirty registers in the cache to the device
+    ///
+    /// Registers are written in ascending order; if a write fails, the
+    /// remaining registers stay dirty.
+    pub fn sync(&mut self) -> Result<(), T::Error> {
+        let mut dirty: Vec<(u16, u32)> = match self.cache {
+            Some(ref cache) => cache
+                .iter()
+                .filter(|&(r, entry)| entry.dirty && !self.read_only.contains(r))
+                .map(|(&r, entry)| (r, entry.value))
+                .collect(),
+            None => return Ok(()),
+        };
+        dirty.sort();
+        for (register, value) in dirty {
+            self.write_bus(register, &[value])?;
+            self.cache_store(register, value, false);
+        }
+        Ok(())
+    }
+
+    /// Forget all cached values
+    pub fn clear_cache(&mut self) {
+        if let Some(ref mut cache) = self.cache {
+            cache.clear();
+        }
+    }
+
+    /// Cached value of a register, falling back to its default
+    fn cached(&self, register: u16) -> Option<u32> {
+        let cache = self.cache.as_ref()?;
+        if self.volatile.contains(&register) {
+            return None;
+        }
+        cache
+            .get(&register)
+            .map(|entry| entry.value)
+            .or_else(|| self.defaults.get(&register).cloned())
+    }
+
+    fn is_dirty(&self, register: u16) -> bool {
+        match self.cache.as_ref().and_then(|cache| cache.get(&register)) {
+            Some(entry) => entry.dirty,
+            None => false,
+        }
+    }
+
+    fn cache_store(&mut self, register: u16, value: u32, dirty: bool) {
+        if self.volatile.contains(&register) {
+            return;
+        }
+        if let Some(ref mut cache) = self.cache {
+            cache.insert(register, CachedValue { value, dirty });
+        }
+    }
+
+    fn read_bus(&mut self, register: u16, count: usize) -> Result<Vec<u32>, T::Error> {
+        let value_bytes = self.value_width.bytes();
+        let mut raw = vec![0; count * value_bytes];
+        match self.address_width {
+            AddressWidth::Eight if count == 1 && value_bytes == 1 => {
+                raw[0] = self.dev.smbus_read_byte_data(register as u8)?;
+            }
+            AddressWidth::Eight if count == 1 && value_bytes == 2 => {
+                // SMBus words are sent least significant byte first
+                let word = self.dev.smbus_read_word_data(register as u8)?;
+                raw.copy_from_slice(&word.to_le_bytes());
+            }
+            AddressWidth::Eight => {
+                let per_chunk = SMBUS_BLOCK_MAX / value_bytes;
+                for (i, chunk) in raw.chunks_mut(per_chunk * value_bytes).enumerate() {
+                    let chunk_register = register as usize + i * per_chunk;
+                    let data = self
+                        .dev
+                        .smbus_read_i2c_block_data(chunk_register as u8, chunk.len() as u8)?;
+                    if data.len() != chunk.len() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "short register read",
+                        )
+                        .into());
+                    }
+                    chunk.copy_from_slice(&data);
+                }
+            }
+            AddressWidth::Sixteen => {
+                self.dev.write(&register.to_be_bytes())?;
+                self.dev.read(&mut raw)?;
+            }
+        }
+        Ok(raw.chunks(value_bytes).map(|v| self.decode(v)).collect())
+    }
+
+    fn write_bus(&mut self, register: u16, values: &[u32]) -> Result<(), T::Error> {
+        let mut raw = Vec::with_capacity(values.len() * self.value_width.bytes());
+        for &value in values {
+            self.encode(value, &mut raw);
+        }
+        let value_bytes = self.value_width.bytes();
+        match self.address_width {
+            AddressWidth::Eight if values.len() == 1 && value_bytes == 1 => {
+                self.dev.smbus_write_byte_data(register as u8, raw[0])
+            }
+            AddressWidth::Eight if values.len() == 1 && value_bytes == 2 => {
+                let word = u16::from_le_bytes([raw[0], raw[1]]);
+                self.dev.smbus_write_word_data(register as u8, word)
+            }
+            AddressWidth::Eight => {
+                let per_chunk = SMBUS_BLOCK_MAX / value_bytes;
+                for (i, chunk) in raw.chunks(per_chunk * value_bytes).enumerate() {
+                    let chunk_register = register as usize + i * per_chunk;
+                    self.dev
+                        .smbus_write_i2c_block_data(chunk_register as u8, chunk)?;
+                }
+                Ok(())
+            }
+            AddressWidth::Sixteen => {
+                let mut buf = register.to_be_bytes().to_vec();
+                buf.extend_from_slice(&raw);
+                self.dev.write(&buf)
+            }
+        }
+    }
+
+    /// Check that `count` registers from `register` are addressable
+    fn check_range(&self, register: u16, count: usize) -> Result<(), T::Error> {
+        if count == 0 {
+            return Err(invalid_input("no registers to access").into());
+        }
+        if register as usize + count - 1 > self.address_width.max() as usize {
+            return Err(invalid_input("register exceeds the address width").into());
+        }
+        Ok(())
+    }
+
+    fn decode(&self, raw: &[u8]) -> u32 {
+        let fold = |acc: u32, &b: &u8| (acc << 8) | u32::from(b);
+        match self.endianness {
+            Endianness::Big => raw.iter().fold(0, fold),
+            Endianness::Little => raw.iter().rev().fold(0, fold),
+        }
+    }
+
+    fn encode(&self, value: u32, raw: &mut Vec<u8>) {
+        let bytes = value.to_be_bytes();
+        let bytes = &bytes[4 - self.value_width.bytes()..];
+        match self.endianness {
+            Endianness::Big => raw.extend(bytes.iter()),
+            Endianness::Little => raw.extend(bytes.iter().rev()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{MockI2CDevice, MockI2CScript, MockI2CTransaction as T};
+
+    #[test]
+    fn eight_bit_registers() {
+        let mut regmap = Regmap::new(MockI2CDevice::new());
+        regmap.write_reg(0x10, 0xA5).unwrap();
+        assert_eq!(regmap.read_reg(0x10).unwrap(), 0xA5);
+        assert!(regmap.write_reg(0x10, 0x100).is_err());
+        assert!(regmap.read_reg(0x100).is_err());
+    }
+
+    #[test]
+    fn value_width_and_endianness() {
+        let mut regmap = Regmap::new(MockI2CDevice::new())
+            .with_value_width(ValueWidth::Sixteen)
+            .with_endianness(Endianness::Little);
+        regmap.write_reg(0x02, 0x1234).unwrap();
+        assert_eq!(regmap.inner().regmap.read_regs(0x02, 2), vec![0x34, 0x12]);
+        assert_eq!(regmap.read_reg(0x02).unwrap(), 0x1234);
+
+        let mut regmap = Regmap::new(regmap.into_inner())
+            .with_value_width(ValueWidth::ThirtyTwo)
+            .with_endianness(Endianness::Big);
+        regmap.write_reg(0x20, 0x0102_0304).unwrap();
+        assert_eq!(
+            regmap.inner().regmap.read_regs(0x20, 4),
+            vec![0x01, 0x02, 0x03, 0x04]
+        );
+        assert_eq!(regmap.read_reg(0x20).unwrap(), 0x0102_0304);
+    }
+
+    #[test]
+    fn update_bits_only_writes_changes() {
+        let mut regmap = Regmap::new(MockI2CScript::new(vec![
+            T::SmbusReadByteData(0x05, 0b1010_0000),
+            T::SmbusWriteByteData(0x05, 0b1010_0011),
+            T::SmbusReadByteData(0x05, 0b1010_0011),
+        ]));
+        assert!(regmap.update_bits(0x05, 0x0F, 0x03).unwrap());
+        assert!(!regmap.update_bits(0x05, 0x0F, 0x03).unwrap());
+        regmap.into_inner().done();
+    }
+
+    #[test]
+    fn single_registers_use_byte_and_word_data() {
+        let mut regmap = Regmap::new(MockI2CScript::new(vec![
+            T::SmbusReadByteData(0x01, 0xAB),
+            T::SmbusWriteByteData(0x01, 0xCD),
+            T::SmbusReadWordData(0x02, 0x1234),
+            T::SmbusWriteWordData(0x02, 0x5678),
+            T::SmbusReadWordData(0x04, 0x1234),
+            T::SmbusWriteWordData(0x04, 0x7856),
+            T::SmbusReadI2cBlockData(0x06, vec![0x01, 0x02, 0x03, 0x04]),
+        ]));
+        assert_eq!(regmap.read_reg(0x01).unwrap(), 0xAB);
+        regmap.write_reg(0x01, 0xCD).unwrap();
+
+        let mut regmap = Regmap::new(regmap.into_inner())
+            .with_value_width(ValueWidth::Sixteen)
+            .with_endianness(Endianness::Little);
+        assert_eq!(regmap.read_reg(0x02).unwrap(), 0x1234);
+        regmap.write_reg(0x02, 0x5678).unwrap();
+
+        let mut regmap = Regmap::new(regmap.into_inner())
+            .with_value_width(ValueWidth::Sixteen)
+            .with_endianness(Endianness::Big);
+        assert_eq!(regmap.read_reg(0x04).unwrap(), 0x3412);
+        regmap.write_reg(0x04, 0x5678).unwrap();
+
+        let mut regmap = Regmap::new(regmap.into_inner()).with_value_width(ValueWidth::Sixteen);
+        assert_eq!(regmap.read_regs(0x06, 2).unwrap(), vec![0x0102, 0x0304]);
+        regmap.into_inner().done();
+    }
+
+    #[test]
+    fn bulk_accesses_are_split_into_smbus_blocks() {
+        let mut dev = MockI2CDevice::new();
+        let data: Vec<u8> = (0..40).collect();
+        dev.regmap.write_regs(0x00, &data);
+        let mut regmap = Regmap::new(dev);
+        let values = regmap.read_regs(0x00, 40).unwrap();
+        assert_eq!(values, (0..40).collect::<Vec<u32>>());
+
+        regmap.write_regs(0x10, &[0xFF; 40]).unwrap();
+        assert_eq!(regmap.read_regs(0x10, 40).unwrap(), vec![0xFF; 40]);
+        assert_eq!(regmap.read_reg(0x0F).unwrap(), 0x0F);
+        assert_eq!(regmap.read_reg(0x38).unwrap(), 0x00);
+    }
+
+    #[test]
+    fn sixteen_bit_addresses() {
+        let mut regmap = Regmap::new(MockI2CScript::new(vec![
+            T::Write(vec![0x12, 0x34, 0xAB]),
+            T::Write(vec![0x12, 0x34]),
+            T::Read(vec![0xAB, 0xCD]),
+        ]))
+        .with_address_width(AddressWidth::Sixteen);
+        regmap.write_reg(0x1234, 0xAB).unwrap();
+        assert_eq!(regmap.read_regs(0x1234, 2).unwrap(), vec![0xAB, 0xCD]);
+        regmap.into_inner().done();
+    }
+
+    #[test]
+    fn cached_reads_skip_the_bus() {
+        let mut regmap = Regmap::new(MockI2CScript::new(vec![
+            T::SmbusReadI2cBlockData(0x01, vec![0x11, 0x22]),
+            T::SmbusReadByteData(0x02, 0x33),
+            T::SmbusWriteByteData(0x03, 0x44),
+        ]))
+        .with_cache()
+        .with_volatile(vec![0x02])
+        .with_default(0x04, 0x55);
+        assert_eq!(regmap.read_regs(0x01, 2).unwrap(), vec![0x11, 0x22]);
+        assert_eq!(regmap.read_reg(0x01).unwrap(), 0x11);
+        assert_eq!(regmap.read_reg(0x02).unwrap(), 0x33);
+        regmap.write_reg(0x03, 0x44).unwrap();
+        assert_eq!(regmap.read_reg(0x03).unwrap(), 0x44);
+        assert_eq!(regmap.read_reg(0x04).unwrap(), 0x55);
+        regmap.into_inner().done();
+    }
+
+    #[test]
+    fn access_policies() {
+        let mut regmap = Regmap::new(MockI2CDevice::new())
+            .with_cache()
+            .with_read_only(0x00..0x02)
+            .with_write_only(vec![0x10]);
+        assert!(regmap.write_reg(0x01, 0x00).is_err());
+        assert!(regmap.write_regs(0x00, &[0, 0]).is_err());
+        assert_eq!(
+            regmap.read_reg(0x10).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        regmap.write_reg(0x10, 0x42).unwrap();
+        assert_eq!(regmap.read_reg(0x10).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn cache_only_writes_are_synced() {
+        let mut regmap = Regmap::new(MockI2CScript::new(vec![
+            T::SmbusWriteByteData(0x05, 0x01),
+            T::SmbusWriteByteData(0x07, 0x02),
+        ]))
+        .with_cache();
+        regmap.set_cache_only(true);
+        regmap.write_reg(0x07, 0x02).unwrap();
+        regmap.write_reg(0x05, 0x01).unwrap();
+        assert_eq!(regmap.read_reg(0x05).unwrap(), 0x01);
+        assert_eq!(
+            regmap.read_reg(0x06).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        regmap.set_cache_only(false);
+        regmap.sync().unwrap();
+        regmap.sync().unwrap();
+        regmap.into_inner().done();
+    }
+
+    #[test]
+    fn mark_dirty_restores_configuration() {
+        let mut regmap = Regmap::new(MockI2CDevice::new())
+            .with_cache()
+            .with_default(0x20, 0x80)
+            .with_volatile(vec![0x21]);
+        regmap.write_reg(0x10, 0xAB).unwrap();
+        regmap.write_reg(0x21, 0xCD).unwrap();
+
+        // simulate a power cycle
+        *regmap.inner_mut() = MockI2CDevice::new();
+        regmap.mark_dirty();
+        regmap.sync().unwrap();
+        assert_eq!(regmap.inner().regmap.read_regs(0x10, 1), vec![0xAB]);
+        assert_eq!(regmap.inner().regmap.read_regs(0x20, 2), vec![0x80, 0x00]);
+    }
+}
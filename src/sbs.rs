@@ -0,0 +1,467 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Smart Battery System driver
+//!
+//! Smart batteries and fuel gauges following the Smart Battery Data
+//! Specification answer at address 0x0B with SMBus word and block reads.
+//! Many of them check the PEC, which is computed in software by wrapping
+//! the bus in a `PecDevice`:
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::smbus::PecError<i2cdev::linux::LinuxI2CError>> {
+//! use i2cdev::linux::LinuxI2CBus;
+//! use i2cdev::sbs::{BatteryStatus, SmartBattery, SBS_ADDRESS};
+//! use i2cdev::smbus::PecDevice;
+//!
+//! let bus = LinuxI2CBus::new("/dev/i2c-1")?;
+//! let mut battery = SmartBattery::new(PecDevice::new(bus, SBS_ADDRESS));
+//! println!("{} {}", battery.manufacturer_name()?, battery.device_name()?);
+//! println!("{} mV, {} mA, {}%",
+//!          battery.voltage()?.0, battery.current()?.0,
+//!          battery.relative_state_of_charge()?);
+//! if battery.battery_status()?.contains(BatteryStatus::OVER_TEMP_ALARM) {
+//!     println!("battery too hot: {:.1} C", battery.temperature()?.celsius());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use core::I2CDevice;
+use std::time::Duration;
+
+/// Address of the smart battery
+pub const SBS_ADDRESS: u16 = 0x0B;
+
+const BATTERY_MODE: u8 = 0x03;
+const TEMPERATURE: u8 = 0x08;
+const VOLTAGE: u8 = 0x09;
+const CURRENT: u8 = 0x0A;
+const AVERAGE_CURRENT: u8 = 0x0B;
+const RELATIVE_STATE_OF_CHARGE: u8 = 0x0D;
+const ABSOLUTE_STATE_OF_CHARGE: u8 = 0x0E;
+const REMAINING_CAPACITY: u8 = 0x0F;
+const FULL_CHARGE_CAPACITY: u8 = 0x10;
+const RUN_TIME_TO_EMPTY: u8 = 0x11;
+const AVERAGE_TIME_TO_EMPTY: u8 = 0x12;
+const AVERAGE_TIME_TO_FULL: u8 = 0x13;
+const CHARGING_CURRENT: u8 = 0x14;
+const CHARGING_VOLTAGE: u8 = 0x15;
+const BATTERY_STATUS: u8 = 0x16;
+const CYCLE_COUNT: u8 = 0x17;
+const DESIGN_CAPACITY: u8 = 0x18;
+const DESIGN_VOLTAGE: u8 = 0x19;
+const MANUFACTURE_DATE: u8 = 0x1B;
+const SERIAL_NUMBER: u8 = 0x1C;
+const MANUFACTURER_NAME: u8 = 0x20;
+const DEVICE_NAME: u8 = 0x21;
+const DEVICE_CHEMISTRY: u8 = 0x22;
+
+/// Time values reported as 65535 are not available
+const TIME_UNAVAILABLE: u16 = 0xFFFF;
+
+bitflags! {
+    /// Status of the battery, as read with `BatteryStatus()`
+    pub struct BatteryStatus: u16 {
+        /// Error code of the last command, see `error_code`
+        const ERROR_CODE = 0x000F;
+        /// The battery is fully discharged
+        const FULLY_DISCHARGED = 0x0010;
+        /// The battery is fully charged
+        const FULLY_CHARGED = 0x0020;
+        /// The battery is discharging
+        const DISCHARGING = 0x0040;
+        /// The fuel gauge is calibrated
+        const INITIALIZED = 0x0080;
+        /// Estimated run time is below `RemainingTimeAlarm()`
+        const REMAINING_TIME_ALARM = 0x0100;
+        /// Remaining capacity is below `RemainingCapacityAlarm()`
+        const REMAINING_CAPACITY_ALARM = 0x0200;
+        /// Discharging should stop
+        const TERMINATE_DISCHARGE_ALARM = 0x0800;
+        /// The battery is too hot
+        const OVER_TEMP_ALARM = 0x1000;
+        /// Charging should stop
+        const TERMINATE_CHARGE_ALARM = 0x4000;
+        /// The battery is overcharged
+        const OVER_CHARGED_ALARM = 0x8000;
+    }
+}
+
+impl BatteryStatus {
+    /// Error code of the last command
+    pub fn error_code(&self) -> ErrorCode {
+        match (*self & BatteryStatus::ERROR_CODE).bits() {
+            0 => ErrorCode::Ok,
+            1 => ErrorCode::Busy,
+            2 => ErrorCode::ReservedCommand,
+            3 => ErrorCode::UnsupportedCommand,
+            4 => ErrorCode::AccessDenied,
+            5 => ErrorCode::OverflowUnderflow,
+            6 => ErrorCode::BadSize,
+            _ => ErrorCode::Unknown,
+        }
+    }
+}
+
+/// Error code in the low bits of `BatteryStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The last command succeeded
+    Ok,
+    /// The battery could not respond
+    Busy,
+    /// The command is reserved
+    ReservedCommand,
+    /// The command is not supported
+    UnsupportedCommand,
+    /// The command is write protected
+    AccessDenied,
+    /// The value written is out of range
+    OverflowUnderflow,
+    /// The transfer had the wrong size
+    BadSize,
+    /// Any other error
+    Unknown,
+}
+
+bitflags! {
+    /// Battery mode, as read with `BatteryMode()`
+    pub struct BatteryMode: u16 {
+        /// The battery can control its charger
+        const INTERNAL_CHARGE_CONTROLLER = 0x0001;
+        /// The battery can act as the primary or secondary battery
+        const PRIMARY_BATTERY_SUPPORT = 0x0002;
+        /// The fuel gauge requests a conditioning cycle
+        const CONDITION_FLAG = 0x0080;
+        /// The internal charge controller is enabled
+        const CHARGE_CONTROLLER_ENABLED = 0x0100;
+        /// The battery operates as the primary battery
+        const PRIMARY_BATTERY = 0x0200;
+        /// Alarm broadcasts to the host are disabled
+        const ALARM_MODE = 0x2000;
+        /// Charging broadcasts to the charger are disabled
+        const CHARGER_MODE = 0x4000;
+        /// Capacities are reported in 10 mWh rather than mAh
+        const CAPACITY_MODE = 0x8000;
+    }
+}
+
+/// A voltage in millivolts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MilliVolts(pub u16);
+
+/// A current in milliamps, positive while charging
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MilliAmps(pub i16);
+
+/// A temperature in tenths of a kelvin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeciKelvin(pub u16);
+
+impl DeciKelvin {
+    /// The temperature in degrees Celsius
+    pub fn celsius(&self) -> f64 {
+        f64::from(self.0) / 10.0 - 273.15
+    }
+}
+
+/// A capacity, in the unit selected by `BatteryMode::CAPACITY_MODE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capacity {
+    /// Charge in mAh
+    MilliAmpHours(u16),
+    /// Energy in units of 10 mWh
+    TenMilliWattHours(u16),
+}
+
+/// Manufacture date of the battery
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManufactureDate {
+    /// Year, from 1980
+    pub year: u16,
+    /// Month, from 1
+    pub month: u8,
+    /// Day of the month, from 1
+    pub day: u8,
+}
+
+impl ManufactureDate {
+    /// Decode a packed `ManufactureDate()` word
+    pub fn from_word(word: u16) -> ManufactureDate {
+        ManufactureDate {
+            year: 1980 + (word >> 9),
+            month: (word >> 5 & 0x0F) as u8,
+            day: (word & 0x1F) as u8,
+        }
+    }
+}
+
+/// Smart battery or fuel gauge following the Smart Battery Data
+/// Specification
+pub struct SmartBattery<T> {
+    dev: T,
+}
+
+impl<T: I2CDevice> SmartBattery<T> {
+    /// Wrap the device at `SBS_ADDRESS`
+    pub fn new(dev: T) -> SmartBattery<T> {
+        SmartBattery { dev }
+    }
+
+    /// Get a reference to the wrapped device
+    pub fn inner(&self) -> &T {
+        &self.dev
+    }
+
+    /// Get a mutable reference to the wrapped device
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.dev
+    }
+
+    /// Unwrap the device
+    pub fn into_inner(self) -> T {
+        self.dev
+    }
+
+    fn read_word(&mut self, command: u8) -> Result<u16, T::Error> {
+        self.dev.smbus_read_word_data(command)
+    }
+
+    fn read_string(&mut self, command: u8) -> Result<String, T::Error> {
+        let block = self.dev.smbus_read_block_data(command)?;
+        let text = String::from_utf8_lossy(&block);
+        Ok(text.trim_end_matches('\0').to_string())
+    }
+
+    fn read_time(&mut self, command: u8) -> Result<Option<Duration>, T::Error> {
+        Ok(match self.read_word(command)? {
+            TIME_UNAVAILABLE => None,
+            minutes => Some(Duration::from_secs(u64::from(minutes) * 60)),
+        })
+    }
+
+    fn read_capacity(&mut self, command: u8) -> Result<Capacity, T::Error> {
+        let mode = self.battery_mode()?;
+        let value = self.read_word(command)?;
+        Ok(if mode.contains(BatteryMode::CAPACITY_MODE) {
+            Capacity::TenMilliWattHours(value)
+        } else {
+            Capacity::MilliAmpHours(value)
+        })
+    }
+
+    /// Read `BatteryMode()`
+    pub fn battery_mode(&mut self) -> Result<BatteryMode, T::Error> {
+        Ok(BatteryMode::from_bits_truncate(
+            self.read_word(BATTERY_MODE)?,
+        ))
+    }
+
+    /// Write `BatteryMode()`
+    pub fn set_battery_mode(&mut self, mode: BatteryMode) -> Result<(), T::Error> {
+        self.dev.smbus_write_word_data(BATTERY_MODE, mode.bits())
+    }
+
+    /// Read `BatteryStatus()`
+    pub fn battery_status(&mut self) -> Result<BatteryStatus, T::Error> {
+        Ok(BatteryStatus::from_bits_truncate(
+            self.read_word(BATTERY_STATUS)?,
+        ))
+    }
+
+    /// Internal temperature of the battery
+    pub fn temperature(&mut self) -> Result<DeciKelvin, T::Error> {
+        Ok(DeciKelvin(self.read_word(TEMPERATURE)?))
+    }
+
+    /// Voltage of the battery
+    pub fn voltage(&mut self) -> Result<MilliVolts, T::Error> {
+        Ok(MilliVolts(self.read_word(VOLTAGE)?))
+    }
+
+    /// Current through the battery, negative while discharging
+    pub fn current(&mut self) -> Result<MilliAmps, T::Error> {
+        Ok(MilliAmps(self.read_word(CURRENT)? as i16))
+    }
+
+    /// Current through the battery, averaged over one minute
+    pub fn average_current(&mut self) -> Result<MilliAmps, T::Error> {
+        Ok(MilliAmps(self.read_word(AVERAGE_CURRENT)? as i16))
+    }
+
+    /// Remaining capacity, in percent of the full charge capacity
+    pub fn relative_state_of_charge(&mut self) -> Result<u8, T::Error> {
+        Ok(self.read_word(RELATIVE_STATE_OF_CHARGE)? as u8)
+    }
+
+    /// Remaining capacity, in percent of the design capacity
+    ///
+    /// May exceed 100%.
+    pub fn absolute_state_of_charge(&mut self) -> Result<u8, T::Error> {
+        Ok(self.read_word(ABSOLUTE_STATE_OF_CHARGE)? as u8)
+    }
+
+    /// Remaining capacity
+    pub fn remaining_capacity(&mut self) -> Result<Capacity, T::Error> {
+        self.read_capacity(REMAINING_CAPACITY)
+    }
+
+    /// Predicted capacity when fully charged
+    pub fn full_charge_capacity(&mut self) -> Result<Capacity, T::Error> {
+        self.read_capacity(FULL_CHARGE_CAPACITY)
+    }
+
+    /// Theoretical capacity of a new battery
+    pub fn design_capacity(&mut self) -> Result<Capacity, T::Error> {
+        self.read_capacity(DESIGN_CAPACITY)
+    }
+
+    /// Theoretical voltage of a new battery
+    pub fn design_voltage(&mut self) -> Result<MilliVolts, T::Error> {
+        Ok(MilliVolts(self.read_word(DESIGN_VOLTAGE)?))
+    }
+
+    /// Predicted run time at the present rate of discharge
+    ///
+    /// `None` while the battery is not discharging.
+    pub fn run_time_to_empty(&mut self) -> Result<Option<Duration>, T::Error> {
+        self.read_time(RUN_TIME_TO_EMPTY)
+    }
+
+    /// Predicted run time at the average rate of discharge
+    pub fn average_time_to_empty(&mut self) -> Result<Option<Duration>, T::Error> {
+        self.read_time(AVERAGE_TIME_TO_EMPTY)
+    }
+
+    /// Predicted time until fully charged at the average charge rate
+    pub fn average_time_to_full(&mut self) -> Result<Option<Duration>, T::Error> {
+        self.read_time(AVERAGE_TIME_TO_FULL)
+    }
+
+    /// Charging current requested from the charger
+    pub fn charging_current(&mut self) -> Result<MilliAmps, T::Error> {
+        Ok(MilliAmps(self.read_word(CHARGING_CURRENT)? as i16))
+    }
+
+    /// Charging voltage requested from the charger
+    pub fn charging_voltage(&mut self) -> Result<MilliVolts, T::Error> {
+        Ok(MilliVolts(self.read_word(CHARGING_VOLTAGE)?))
+    }
+
+    /// Number of charge cycles the battery went through
+    pub fn cycle_count(&mut self) -> Result<u16, T::Error> {
+        self.read_word(CYCLE_COUNT)
+    }
+
+    /// Date the battery was made
+    pub fn manufacture_date(&mut self) -> Result<ManufactureDate, T::Error> {
+        Ok(ManufactureDate::from_word(
+            self.read_word(MANUFACTURE_DATE)?,
+        ))
+    }
+
+    /// Serial number, unique for a given manufacturer and device name
+    pub fn serial_number(&mut self) -> Result<u16, T::Error> {
+        self.read_word(SERIAL_NUMBER)
+    }
+
+    /// Name of the battery manufacturer
+    pub fn manufacturer_name(&mut self) -> Result<String, T::Error> {
+        self.read_string(MANUFACTURER_NAME)
+    }
+
+    /// Name of the battery model
+    pub fn device_name(&mut self) -> Result<String, T::Error> {
+        self.read_string(DEVICE_NAME)
+    }
+
+    /// Battery chemistry, such as "LION"
+    pub fn device_chemistry(&mut self) -> Result<String, T::Error> {
+        self.read_string(DEVICE_CHEMISTRY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{MockI2CScript, MockI2CTransaction as T};
+
+    #[test]
+    fn typed_readings() {
+        let mut battery = SmartBattery::new(MockI2CScript::new(vec![
+            T::SmbusReadWordData(VOLTAGE, 12_450),
+            T::SmbusReadWordData(CURRENT, (-1500i16) as u16),
+            T::SmbusReadWordData(RELATIVE_STATE_OF_CHARGE, 87),
+            T::SmbusReadWordData(CYCLE_COUNT, 312),
+            T::SmbusReadWordData(TEMPERATURE, 2982),
+            T::SmbusReadWordData(AVERAGE_TIME_TO_EMPTY, 95),
+            T::SmbusReadWordData(AVERAGE_TIME_TO_FULL, 0xFFFF),
+            T::SmbusReadWordData(MANUFACTURE_DATE, (41 << 9) | (3 << 5) | 14),
+        ]));
+        assert_eq!(battery.voltage().unwrap(), MilliVolts(12_450));
+        assert_eq!(battery.current().unwrap(), MilliAmps(-1500));
+        assert_eq!(battery.relative_state_of_charge().unwrap(), 87);
+        assert_eq!(battery.cycle_count().unwrap(), 312);
+        assert!((battery.temperature().unwrap().celsius() - 25.05).abs() < 1e-9);
+        assert_eq!(
+            battery.average_time_to_empty().unwrap(),
+            Some(Duration::from_secs(95 * 60))
+        );
+        assert_eq!(battery.average_time_to_full().unwrap(), None);
+        assert_eq!(
+            battery.manufacture_date().unwrap(),
+            ManufactureDate {
+                year: 2021,
+                month: 3,
+                day: 14
+            }
+        );
+        battery.into_inner().done();
+    }
+
+    #[test]
+    fn status_and_capacity_mode() {
+        let mut battery = SmartBattery::new(MockI2CScript::new(vec![
+            T::SmbusReadWordData(BATTERY_STATUS, 0x10C4),
+            T::SmbusReadWordData(BATTERY_MODE, 0x0000),
+            T::SmbusReadWordData(REMAINING_CAPACITY, 4200),
+            T::SmbusReadWordData(BATTERY_MODE, 0x8000),
+            T::SmbusReadWordData(REMAINING_CAPACITY, 5000),
+        ]));
+        let status = battery.battery_status().unwrap();
+        assert!(status.contains(
+            BatteryStatus::OVER_TEMP_ALARM
+                | BatteryStatus::INITIALIZED
+                | BatteryStatus::DISCHARGING
+        ));
+        assert_eq!(status.error_code(), ErrorCode::AccessDenied);
+        assert_eq!(
+            battery.remaining_capacity().unwrap(),
+            Capacity::MilliAmpHours(4200)
+        );
+        assert_eq!(
+            battery.remaining_capacity().unwrap(),
+            Capacity::TenMilliWattHours(5000)
+        );
+        battery.into_inner().done();
+    }
+
+    #[test]
+    fn strings() {
+        let mut battery = SmartBattery::new(MockI2CScript::new(vec![
+            T::SmbusReadBlockData(MANUFACTURER_NAME, b"ACME\0".to_vec()),
+            T::SmbusReadBlockData(DEVICE_NAME, b"bq40z50".to_vec()),
+            T::SmbusReadBlockData(DEVICE_CHEMISTRY, b"LION".to_vec()),
+        ]));
+        assert_eq!(battery.manufacturer_name().unwrap(), "ACME");
+        assert_eq!(battery.device_name().unwrap(), "bq40z50");
+        assert_eq!(battery.device_chemistry().unwrap(), "LION");
+        battery.into_inner().done();
+    }
+}
@@ -0,0 +1,471 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sharing one I2C bus between threads and drivers
+//!
+//! Two `LinuxI2CDevice`s opened on the same bus each change the slave
+//! address of their file descriptor with `I2C_SLAVE`, and nothing keeps
+//! their operations from interleaving.  A `SharedBus` instead owns a single
+//! `I2CTransfer` behind a mutex and hands out `SharedDevice` handles which
+//! address every message explicitly, so no slave address state is shared.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::linux::LinuxI2CError> {
+//! use i2cdev::core::I2CDevice;
+//! use i2cdev::linux::LinuxI2CBus;
+//! use i2cdev::shared::SharedBus;
+//!
+//! let bus = SharedBus::new(LinuxI2CBus::new("/dev/i2c-1")?);
+//! let mut sensor = bus.device(0x48);
+//! let pmic = bus.device(0x60);
+//! std::thread::spawn(move || sensor.smbus_read_word_data(0x00));
+//!
+//! // select a page and read from it without other users interleaving
+//! let mut pmic = pmic.lock();
+//! pmic.smbus_write_byte_data(0x00, 0x01)?;
+//! let status = pmic.smbus_read_byte_data(0x79)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+use core::{
+    check_block_count, check_block_len, I2CDevice, I2CMessage, I2CRegister16, I2CTransfer,
+    SMBUS_BLOCK_MAX,
+};
+use std::error::Error;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// An I2C bus shared between several users
+///
+/// Cloning a `SharedBus` is cheap and yields another handle to the same bus.
+pub struct SharedBus<T> {
+    bus: Arc<Mutex<T>>,
+}
+
+impl<T> Clone for SharedBus<T> {
+    fn clone(&self) -> Self {
+        SharedBus {
+            bus: self.bus.clone(),
+        }
+    }
+}
+
+impl<T> SharedBus<T> {
+    /// Take ownership of a bus to share it
+    pub fn new(bus: T) -> SharedBus<T> {
+        SharedBus {
+            bus: Arc::new(Mutex::new(bus)),
+        }
+    }
+
+    /// Create a handle for the device at the given address
+    pub fn device(&self, address: u16) -> SharedDevice<T> {
+        SharedDevice {
+            bus: self.bus.clone(),
+            address,
+        }
+    }
+
+    /// Lock the bus for a sequence of transfers
+    ///
+    /// Other users of the bus block until the guard is dropped.
+    pub fn lock(&self) -> SharedBusGuard<'_, T> {
+        SharedBusGuard {
+            bus: lock(&self.bus),
+        }
+    }
+}
+
+/// A panic while the bus was locked cannot leave a transfer half done, so
+/// a poisoned lock is simply taken over
+fn lock<T>(bus: &Mutex<T>) -> MutexGuard<'_, T> {
+    bus.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Exclusive access to a `SharedBus`, released when dropped
+pub struct SharedBusGuard<'a, T: 'a> {
+    bus: MutexGuard<'a, T>,
+}
+
+impl<'a, T> Deref for SharedBusGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.bus
+    }
+}
+
+impl<'a, T> DerefMut for SharedBusGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.bus
+    }
+}
+
+/// Handle to a single device on a `SharedBus`
+///
+/// The bus is locked for the duration of each operation.  SMBus commands
+/// are emulated with plain I2C messages, as the kernel does for adapters
+/// without native SMBus support; block reads fetch the full 32 bytes after
+/// the count byte, since a transfer cannot be sized from data it returns.
+pub struct SharedDevice<T> {
+    bus: Arc<Mutex<T>>,
+    address: u16,
+}
+
+impl<T> Clone for SharedDevice<T> {
+    fn clone(&self) -> Self {
+        SharedDevice {
+            bus: self.bus.clone(),
+            address: self.address,
+        }
+    }
+}
+
+impl<T> SharedDevice<T> {
+    /// The slave address of the device
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    /// Lock the bus for a sequence of operations on this device
+    ///
+    /// Other users of the bus block until the guard is dropped.
+    pub fn lock(&self) -> SharedDeviceGuard<'_, T> {
+        SharedDeviceGuard {
+            bus: lock(&self.bus),
+            address: self.address,
+        }
+    }
+}
+
+/// Exclusive access to a device on a `SharedBus`, released when dropped
+pub struct SharedDeviceGuard<'a, T: 'a> {
+    bus: MutexGuard<'a, T>,
+    address: u16,
+}
+
+pub(crate) fn read_message<'x, M: I2CMessage<'x>>(data: &'x mut [u8], address: u16) -> M {
+    M::read(data).with_address(address)
+}
+
+pub(crate) fn write_message<'x, M: I2CMessage<'x>>(data: &'x [u8], address: u16) -> M {
+    M::write(data).with_address(address)
+}
+
+/// Write `write` and then, after a repeated start, fill `read`
+///
+/// The messages are wrapped in `ManuallyDrop`: the messages of a generic
+/// `I2CTransfer` must outlive the borrow handed to `transfer`, which the
+/// borrow checker only accepts if they are never dropped.  Messages are
+/// plain descriptors of borrowed buffers, so nothing is leaked.
+pub(crate) fn write_read<T, E>(
+    bus: &mut T,
+    address: u16,
+    write: &[u8],
+    read: &mut [u8],
+) -> Result<(), E>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+{
+    if read.is_empty() {
+        let mut msgs = ManuallyDrop::new([write_message(write, address)]);
+        bus.transfer(&mut msgs[..])?;
+    } else if write.is_empty() {
+        let mut msgs = ManuallyDrop::new([read_message(read, address)]);
+        bus.transfer(&mut msgs[..])?;
+    } else {
+        let mut msgs =
+            ManuallyDrop::new([write_message(write, address), read_message(read, address)]);
+        bus.transfer(&mut msgs[..])?;
+    }
+    Ok(())
+}
+
+/// Extract the block following the count byte of a block read
+fn block_from_reply(reply: &[u8]) -> io::Result<Vec<u8>> {
+    let count = reply[0] as usize;
+    check_block_count(count)?;
+    Ok(reply[1..=count].to_vec())
+}
+
+impl<'a, T, E> SharedDeviceGuard<'a, T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+{
+    fn write_read(&mut self, write: &[u8], read: &mut [u8]) -> Result<(), E> {
+        write_read(&mut *self.bus, self.address, write, read)
+    }
+}
+
+impl<'a, T, E> I2CDevice for SharedDeviceGuard<'a, T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: Error + From<io::Error>,
+{
+    type Error = E;
+
+    fn read(&mut self, data: &mut [u8]) -> Result<(), E> {
+        self.write_read(&[], data)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), E> {
+        self.write_read(data, &mut [])
+    }
+
+    /// Sent as a zero-length message, which not every adapter supports
+    fn smbus_write_quick(&mut self, bit: bool) -> Result<(), E> {
+        let address = self.address;
+        if bit {
+            let mut msgs = ManuallyDrop::new([read_message(&mut [], address)]);
+            self.bus.transfer(&mut msgs[..])?;
+        } else {
+            let mut msgs = ManuallyDrop::new([write_message(&[], address)]);
+            self.bus.transfer(&mut msgs[..])?;
+        }
+        Ok(())
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> Result<u8, E> {
+        let mut buf = [0];
+        self.write_read(&[register], &mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> Result<u16, E> {
+        let mut buf = [0; 2];
+        self.write_read(&[register], &mut buf)?;
+        Ok(LittleEndian::read_u16(&buf))
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> Result<u16, E> {
+        let mut request = [register, 0, 0];
+        LittleEndian::write_u16(&mut request[1..], value);
+        let mut buf = [0; 2];
+        self.write_read(&request, &mut buf)?;
+        Ok(LittleEndian::read_u16(&buf))
+    }
+
+    /// Clocks all 33 bytes of the largest block whatever the count byte
+    /// says, as a generic `I2CTransfer` cannot size a read from its own
+    /// data.  Some devices misbehave when read past the end of a block;
+    /// a `LinuxI2CDevice` lets the kernel size the read instead.
+    fn smbus_read_block_data(&mut self, register: u8) -> Result<Vec<u8>, E> {
+        let mut reply = [0; SMBUS_BLOCK_MAX + 1];
+        self.write_read(&[register], &mut reply)?;
+        Ok(block_from_reply(&reply)?)
+    }
+
+    fn smbus_read_i2c_block_data(&mut self, register: u8, len: u8) -> Result<Vec<u8>, E> {
+        check_block_len(len as usize)?;
+        let mut buf = vec![0; len as usize];
+        self.write_read(&[register], &mut buf)?;
+        Ok(buf)
+    }
+
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), E> {
+        check_block_len(values.len())?;
+        let mut request = vec![register, values.len() as u8];
+        request.extend_from_slice(values);
+        self.write_read(&request, &mut [])
+    }
+
+    fn smbus_write_i2c_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), E> {
+        check_block_len(values.len())?;
+        let mut request = vec![register];
+        request.extend_from_slice(values);
+        self.write_read(&request, &mut [])
+    }
+
+    /// Clocks all 33 bytes of the largest reply, like
+    /// `smbus_read_block_data`.
+    fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> Result<Vec<u8>, E> {
+        check_block_len(values.len())?;
+        let mut request = vec![register, values.len() as u8];
+        request.extend_from_slice(values);
+        let mut reply = [0; SMBUS_BLOCK_MAX + 1];
+        self.write_read(&request, &mut reply)?;
+        Ok(block_from_reply(&reply)?)
+    }
+}
+
+impl<'a, T, E> I2CRegister16 for SharedDeviceGuard<'a, T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: Error + From<io::Error>,
+{
+    fn read_block16(&mut self, register: u16, data: &mut [u8]) -> Result<(), E> {
+        self.write_read(&[(register >> 8) as u8, register as u8], data)
+    }
+}
+
+impl<T, E> I2CDevice for SharedDevice<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: Error + From<io::Error>,
+{
+    type Error = E;
+
+    fn read(&mut self, data: &mut [u8]) -> Result<(), E> {
+        self.lock().read(data)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), E> {
+        self.lock().write(data)
+    }
+
+    fn smbus_write_quick(&mut self, bit: bool) -> Result<(), E> {
+        self.lock().smbus_write_quick(bit)
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> Result<u8, E> {
+        self.lock().smbus_read_byte_data(register)
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> Result<u16, E> {
+        self.lock().smbus_read_word_data(register)
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> Result<u16, E> {
+        self.lock().smbus_process_word(register, value)
+    }
+
+    /// Reads the full 33 bytes, see `SharedDeviceGuard`
+    fn smbus_read_block_data(&mut self, register: u8) -> Result<Vec<u8>, E> {
+        self.lock().smbus_read_block_data(register)
+    }
+
+    fn smbus_read_i2c_block_data(&mut self, register: u8, len: u8) -> Result<Vec<u8>, E> {
+        self.lock().smbus_read_i2c_block_data(register, len)
+    }
+
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), E> {
+        self.lock().smbus_write_block_data(register, values)
+    }
+
+    fn smbus_write_i2c_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), E> {
+        self.lock().smbus_write_i2c_block_data(register, values)
+    }
+
+    /// Reads the full 33 bytes, see `SharedDeviceGuard`
+    fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> Result<Vec<u8>, E> {
+        self.lock().smbus_process_block(register, values)
+    }
+}
+
+impl<T, E> I2CRegister16 for SharedDevice<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: Error + From<io::Error>,
+{
+    fn read_block16(&mut self, register: u16, data: &mut [u8]) -> Result<(), E> {
+        self.lock().read_block16(register, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{MockI2CBus, MockI2CDevice};
+    use std::thread;
+
+    fn bus_with_targets() -> SharedBus<MockI2CBus> {
+        let mut bus = MockI2CBus::new();
+        bus.add_target(0x20, MockI2CDevice::new());
+        bus.add_target(0x21, MockI2CDevice::new());
+        SharedBus::new(bus)
+    }
+
+    #[test]
+    fn devices_are_addressed_independently() {
+        let bus = bus_with_targets();
+        let mut first = bus.device(0x20);
+        let mut second = bus.device(0x21);
+        first.smbus_write_byte_data(0x10, 0xAA).unwrap();
+        second.smbus_write_word_data(0x10, 0x1234).unwrap();
+        assert_eq!(first.smbus_read_byte_data(0x10).unwrap(), 0xAA);
+        assert_eq!(second.smbus_read_word_data(0x10).unwrap(), 0x1234);
+        assert!(bus.device(0x22).smbus_read_byte().is_err());
+    }
+
+    #[test]
+    fn sixteen_bit_registers() {
+        use mock::I2CRegisterMap;
+
+        let mut bus = MockI2CBus::new();
+        bus.add_target(
+            0x50,
+            MockI2CDevice {
+                regmap: I2CRegisterMap::with_16bit_addresses(),
+            },
+        );
+        let mut eeprom = SharedBus::new(bus).device(0x50);
+        eeprom.write_block16(0x0100, &[1, 2, 3]).unwrap();
+        assert_eq!(eeprom.read_reg16(0x0102).unwrap(), 3);
+    }
+
+    #[test]
+    fn smbus_blocks_are_emulated() {
+        let bus = bus_with_targets();
+        let mut dev = bus.device(0x20);
+        dev.smbus_write_block_data(0x40, &[1, 2, 3]).unwrap();
+        assert_eq!(dev.smbus_read_block_data(0x40).unwrap(), vec![1, 2, 3]);
+        assert_eq!(
+            dev.smbus_read_i2c_block_data(0x40, 4).unwrap(),
+            vec![3, 1, 2, 3]
+        );
+        assert!(dev.smbus_write_block_data(0x40, &[0; 33]).is_err());
+
+        dev.smbus_write_byte_data(0x50, 33).unwrap();
+        assert_eq!(
+            dev.smbus_read_block_data(0x50).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn handles_are_usable_from_threads() {
+        let bus = bus_with_targets();
+        let threads: Vec<_> = (0..4u8)
+            .map(|i| {
+                let dev = bus.device(0x20 + u16::from(i % 2));
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        let mut dev = dev.lock();
+                        dev.smbus_write_byte_data(i, i).unwrap();
+                        assert_eq!(dev.smbus_read_byte_data(i).unwrap(), i);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn bus_guard_exposes_transfers() {
+        use mock::MockI2CMessage;
+
+        let bus = bus_with_targets();
+        bus.device(0x21).smbus_write_byte_data(0x00, 0x5A).unwrap();
+        let mut data = [0];
+        {
+            let mut guard = bus.lock();
+            let mut msgs = [
+                MockI2CMessage::write(&[0x00]).with_address(0x21),
+                MockI2CMessage::read(&mut data).with_address(0x21),
+            ];
+            guard.transfer(&mut msgs).unwrap();
+        }
+        assert_eq!(data, [0x5A]);
+    }
+}
@@ -0,0 +1,567 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! SMBus protocol helpers
+//!
+//! `LinuxI2CDevice::set_smbus_pec` relies on the adapter computing the
+//! Packet Error Code, which many adapters cannot do.  `PecDevice` computes
+//! it in software instead, emulating the SMBus commands with plain I2C
+//! messages.
+//!
+//! Devices signalling SMBALERT# are identified by reading the Alert
+//! Response Address with `read_alerts`, or routed to handlers by an
+//! `AlertDispatcher`.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::smbus::PecError<i2cdev::linux::LinuxI2CError>> {
+//! use i2cdev::core::I2CDevice;
+//! use i2cdev::linux::LinuxI2CBus;
+//! use i2cdev::smbus::PecDevice;
+//!
+//! let bus = LinuxI2CBus::new("/dev/i2c-1")?;
+//! let mut battery = PecDevice::new(bus, 0x0B);
+//! let voltage_mv = battery.smbus_read_word_data(0x09)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+use core::{check_block_count, check_block_len, I2CDevice, I2CTransfer, SMBUS_BLOCK_MAX};
+use libc;
+use shared::write_read;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Compute the SMBus Packet Error Code of `data`
+///
+/// The PEC is a CRC-8 with polynomial x^8 + x^2 + x + 1 (0x07) and an
+/// initial value of zero, computed over every byte of a transaction,
+/// including the address bytes.
+pub fn pec(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Error of a `PecDevice`
+#[derive(Debug)]
+pub enum PecError<E> {
+    /// The underlying transfer failed
+    Bus(E),
+    /// The PEC received from the device does not match the data
+    PecMismatch {
+        /// PEC computed over the received data
+        expected: u8,
+        /// PEC sent by the device
+        received: u8,
+    },
+}
+
+impl<E> From<E> for PecError<E> {
+    fn from(e: E) -> Self {
+        PecError::Bus(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for PecError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PecError::Bus(ref e) => fmt::Display::fmt(e, f),
+            PecError::PecMismatch { expected, received } => write!(
+                f,
+                "SMBus PEC mismatch: expected 0x{:02x}, received 0x{:02x}",
+                expected, received
+            ),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for PecError<E> {
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            PecError::Bus(ref e) => Some(e),
+            PecError::PecMismatch { .. } => None,
+        }
+    }
+}
+
+impl<E: Into<io::Error>> From<PecError<E>> for io::Error {
+    fn from(e: PecError<E>) -> io::Error {
+        match e {
+            PecError::Bus(e) => e.into(),
+            PecError::PecMismatch { expected, received } => io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "SMBus PEC mismatch: expected 0x{:02x}, received 0x{:02x}",
+                    expected, received
+                ),
+            ),
+        }
+    }
+}
+
+/// SMBus device with Packet Error Checking computed in software
+///
+/// Every SMBus command is sent as plain I2C messages with a PEC byte
+/// appended to writes, and the PEC byte of reads is verified.  As a
+/// transfer cannot be sized from the data it returns, block reads fetch
+/// the full 32 bytes after the count byte, followed by one more byte for
+/// the PEC.  `read`, `write` and `smbus_write_quick` carry no PEC.
+pub struct PecDevice<T> {
+    bus: T,
+    address: u16,
+}
+
+impl<T> PecDevice<T> {
+    /// Talk to the device at `address`, a 7-bit address, through `bus`
+    pub fn new(bus: T, address: u16) -> PecDevice<T> {
+        PecDevice { bus, address }
+    }
+
+    /// The slave address of the device
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    /// Get a reference to the underlying bus
+    pub fn inner(&self) -> &T {
+        &self.bus
+    }
+
+    /// Get a mutable reference to the underlying bus
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.bus
+    }
+
+    /// Unwrap the underlying bus
+    pub fn into_inner(self) -> T {
+        self.bus
+    }
+
+    fn write_address(&self) -> u8 {
+        (self.address << 1) as u8
+    }
+
+    fn read_address(&self) -> u8 {
+        (self.address << 1) as u8 | 1
+    }
+}
+
+impl<T, E> PecDevice<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: From<io::Error>,
+{
+    /// Write `data` followed by its PEC
+    pub(crate) fn write_pec(&mut self, data: &[u8]) -> Result<(), PecError<E>> {
+        let mut buf = Vec::with_capacity(data.len() + 1);
+        buf.extend_from_slice(data);
+        let mut covered = vec![self.write_address()];
+        covered.extend_from_slice(data);
+        buf.push(pec(&covered));
+        Ok(write_read(&mut self.bus, self.address, &buf, &mut [])?)
+    }
+
+    /// Optionally write `command`, then read `len` bytes and a PEC byte
+    pub(crate) fn read_pec(&mut self, command: &[u8], len: usize) -> Result<Vec<u8>, PecError<E>> {
+        let mut reply = vec![0; len + 1];
+        write_read(&mut self.bus, self.address, command, &mut reply)?;
+        let received = reply.pop().unwrap();
+        self.check(command, &reply, received)?;
+        Ok(reply)
+    }
+
+    /// Verify the PEC of a read, given the command bytes written before it
+    fn check(&self, command: &[u8], reply: &[u8], received: u8) -> Result<(), PecError<E>> {
+        let mut covered = Vec::with_capacity(command.len() + reply.len() + 2);
+        if !command.is_empty() {
+            covered.push(self.write_address());
+            covered.extend_from_slice(command);
+        }
+        covered.push(self.read_address());
+        covered.extend_from_slice(reply);
+        let expected = pec(&covered);
+        if expected != received {
+            return Err(PecError::PecMismatch { expected, received });
+        }
+        Ok(())
+    }
+
+    /// Read a block reply of count byte, data and PEC
+    ///
+    /// The reply is always read as 34 bytes, whatever the count byte
+    /// says, as a generic `I2CTransfer` cannot size a read from its own
+    /// data.  The PEC is taken from after the counted data.
+    fn read_block_pec(&mut self, command: &[u8]) -> Result<Vec<u8>, PecError<E>> {
+        let mut reply = [0; SMBUS_BLOCK_MAX + 2];
+        write_read(&mut self.bus, self.address, command, &mut reply)?;
+        let count = reply[0] as usize;
+        check_block_count(count).map_err(E::from)?;
+        self.check(command, &reply[..=count], reply[count + 1])?;
+        Ok(reply[1..=count].to_vec())
+    }
+}
+
+impl<T, E> I2CDevice for PecDevice<T>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: Error + From<io::Error> + 'static,
+{
+    type Error = PecError<E>;
+
+    fn read(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(write_read(&mut self.bus, self.address, &[], data)?)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        Ok(write_read(&mut self.bus, self.address, data, &mut [])?)
+    }
+
+    /// Sent as a zero-length message, which not every adapter supports
+    fn smbus_write_quick(&mut self, bit: bool) -> Result<(), Self::Error> {
+        if bit {
+            self.read(&mut [])
+        } else {
+            self.write(&[])
+        }
+    }
+
+    fn smbus_read_byte(&mut self) -> Result<u8, Self::Error> {
+        Ok(self.read_pec(&[], 1)?[0])
+    }
+
+    fn smbus_write_byte(&mut self, value: u8) -> Result<(), Self::Error> {
+        self.write_pec(&[value])
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> Result<u8, Self::Error> {
+        Ok(self.read_pec(&[register], 1)?[0])
+    }
+
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+        self.write_pec(&[register, value])
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> Result<u16, Self::Error> {
+        Ok(LittleEndian::read_u16(&self.read_pec(&[register], 2)?))
+    }
+
+    fn smbus_write_word_data(&mut self, register: u8, value: u16) -> Result<(), Self::Error> {
+        let mut buf = [register, 0, 0];
+        LittleEndian::write_u16(&mut buf[1..], value);
+        self.write_pec(&buf)
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> Result<u16, Self::Error> {
+        let mut buf = [register, 0, 0];
+        LittleEndian::write_u16(&mut buf[1..], value);
+        Ok(LittleEndian::read_u16(&self.read_pec(&buf, 2)?))
+    }
+
+    /// Clocks the full 32 data bytes and PEC whatever the count byte
+    /// says.  Some devices misbehave when read past the end of a block;
+    /// on Linux, `LinuxI2CDevice` with `set_smbus_pec(true)` lets the
+    /// kernel size the read from the count byte instead.
+    fn smbus_read_block_data(&mut self, register: u8) -> Result<Vec<u8>, Self::Error> {
+        self.read_block_pec(&[register])
+    }
+
+    fn smbus_read_i2c_block_data(&mut self, register: u8, len: u8) -> Result<Vec<u8>, Self::Error> {
+        check_block_len(len as usize).map_err(E::from)?;
+        self.read_pec(&[register], len as usize)
+    }
+
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), Self::Error> {
+        check_block_len(values.len()).map_err(E::from)?;
+        let mut buf = vec![register, values.len() as u8];
+        buf.extend_from_slice(values);
+        self.write_pec(&buf)
+    }
+
+    fn smbus_write_i2c_block_data(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), Self::Error> {
+        check_block_len(values.len()).map_err(E::from)?;
+        let mut buf = vec![register];
+        buf.extend_from_slice(values);
+        self.write_pec(&buf)
+    }
+
+    /// Clocks the full 32 data bytes and PEC of the reply, like
+    /// `smbus_read_block_data`.
+    fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        check_block_len(values.len()).map_err(E::from)?;
+        let mut buf = vec![register, values.len() as u8];
+        buf.extend_from_slice(values);
+        self.read_block_pec(&buf)
+    }
+}
+
+/// SMBus Alert Response Address
+pub const SMBUS_ALERT_RESPONSE_ADDRESS: u16 = 0x0C;
+
+/// Upper bound on the responses read while servicing an alert, in case a
+/// misbehaving device keeps the alert line asserted
+const MAX_ALERT_RESPONSES: usize = 0x80;
+
+/// Response of a device to a read of the Alert Response Address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmbusAlert {
+    /// 7-bit address of the alerting device
+    pub address: u16,
+    /// Device specific bit sent along with the address
+    pub flag: bool,
+}
+
+/// Whether the error reports that no device acknowledged the transfer
+pub(crate) fn is_nack(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        Some(libc::ENXIO) => true,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Some(libc::EREMOTEIO) => true,
+        _ => false,
+    }
+}
+
+/// Read the Alert Response Address until no device responds
+///
+/// Each read returns the address of the alerting device with the lowest
+/// address, which then releases SMBALERT#, so repeating the read until it
+/// is not acknowledged services every pending alert.  If the same device
+/// responds twice in a row, it failed to clear its alert and the loop
+/// stops, as in the kernel's smbus-alert driver.
+pub fn read_alerts<T, E>(bus: &mut T) -> Result<Vec<SmbusAlert>, E>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: From<io::Error> + Into<io::Error>,
+{
+    let mut alerts: Vec<SmbusAlert> = Vec::new();
+    while alerts.len() < MAX_ALERT_RESPONSES {
+        let mut response = [0];
+        if let Err(e) = write_read(bus, SMBUS_ALERT_RESPONSE_ADDRESS, &[], &mut response) {
+            let e: io::Error = e.into();
+            if is_nack(&e) {
+                break;
+            }
+            return Err(e.into());
+        }
+        let alert = SmbusAlert {
+            address: u16::from(response[0] >> 1),
+            flag: response[0] & 1 != 0,
+        };
+        let repeated = alerts.last().map(|last| last.address) == Some(alert.address);
+        alerts.push(alert);
+        if repeated {
+            break;
+        }
+    }
+    Ok(alerts)
+}
+
+/// Routes SMBus alerts to handlers registered per device address
+#[derive(Default)]
+pub struct AlertDispatcher {
+    handlers: HashMap<u16, Box<dyn FnMut(SmbusAlert) + Send>>,
+}
+
+impl AlertDispatcher {
+    /// Create a dispatcher without any handlers
+    pub fn new() -> AlertDispatcher {
+        AlertDispatcher {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Call `handler` for alerts from `address`, replacing any previous one
+    pub fn register<F>(&mut self, address: u16, handler: F)
+    where
+        F: FnMut(SmbusAlert) + Send + 'static,
+    {
+        self.handlers.insert(address, Box::new(handler));
+    }
+
+    /// Remove the handler for `address`
+    pub fn unregister(&mut self, address: u16) -> bool {
+        self.handlers.remove(&address).is_some()
+    }
+
+    /// Service pending alerts, to be called when SMBALERT# is asserted
+    ///
+    /// Alerts from addresses without a handler are returned.
+    pub fn dispatch<T, E>(&mut self, bus: &mut T) -> Result<Vec<SmbusAlert>, E>
+    where
+        T: for<'x> I2CTransfer<'x, Error = E>,
+        E: From<io::Error> + Into<io::Error>,
+    {
+        let mut unhandled = Vec::new();
+        for alert in read_alerts(bus)? {
+            match self.handlers.get_mut(&alert.address) {
+                Some(handler) => handler(alert),
+                None => unhandled.push(alert),
+            }
+        }
+        Ok(unhandled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{
+        I2CResult, MockI2CBus, MockI2CMessageOp as Op, MockI2CScript, MockI2CTarget,
+        MockI2CTransaction as T,
+    };
+
+    #[test]
+    fn crc8_check_value() {
+        assert_eq!(pec(b"123456789"), 0xF4);
+        assert_eq!(pec(&[]), 0x00);
+    }
+
+    #[test]
+    fn writes_append_pec() {
+        let mut dev = PecDevice::new(
+            MockI2CScript::new(vec![
+                T::Transfer(vec![Op::Write(vec![
+                    0x10,
+                    0x34,
+                    0x12,
+                    pec(&[0x16, 0x10, 0x34, 0x12]),
+                ])]),
+                T::Transfer(vec![Op::Write(vec![
+                    0x20,
+                    0x02,
+                    0xAA,
+                    0xBB,
+                    pec(&[0x16, 0x20, 0x02, 0xAA, 0xBB]),
+                ])]),
+            ]),
+            0x0B,
+        );
+        dev.smbus_write_word_data(0x10, 0x1234).unwrap();
+        dev.smbus_write_block_data(0x20, &[0xAA, 0xBB]).unwrap();
+        dev.into_inner().done();
+    }
+
+    #[test]
+    fn reads_verify_pec() {
+        let good = pec(&[0x16, 0x09, 0x17, 0x34, 0x12]);
+        let mut block = vec![2, 0xAA, 0xBB, pec(&[0x16, 0x20, 0x17, 2, 0xAA, 0xBB])];
+        block.resize(SMBUS_BLOCK_MAX + 2, 0xFF);
+        let mut dev = PecDevice::new(
+            MockI2CScript::new(vec![
+                T::Transfer(vec![
+                    Op::Write(vec![0x09]),
+                    Op::Read(vec![0x34, 0x12, good]),
+                ]),
+                T::Transfer(vec![
+                    Op::Write(vec![0x09]),
+                    Op::Read(vec![0x34, 0x12, good ^ 1]),
+                ]),
+                T::Transfer(vec![Op::Write(vec![0x20]), Op::Read(block)]),
+            ]),
+            0x0B,
+        );
+        assert_eq!(dev.smbus_read_word_data(0x09).unwrap(), 0x1234);
+        match dev.smbus_read_word_data(0x09) {
+            Err(PecError::PecMismatch { expected, received }) => {
+                assert_eq!(expected, good);
+                assert_eq!(received, good ^ 1);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(dev.smbus_read_block_data(0x20).unwrap(), vec![0xAA, 0xBB]);
+        dev.into_inner().done();
+    }
+
+    #[test]
+    fn mismatch_converts_to_io_error() {
+        let e: PecError<io::Error> = PecError::PecMismatch {
+            expected: 1,
+            received: 2,
+        };
+        assert_eq!(io::Error::from(e).kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// Devices waiting to answer the Alert Response Address
+    struct AlertLine {
+        pending: Vec<u8>,
+    }
+
+    impl MockI2CTarget for AlertLine {
+        fn handle_write(&mut self, _data: &[u8]) -> I2CResult<()> {
+            Err(io::Error::from_raw_os_error(libc::ENXIO))
+        }
+
+        fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+            if self.pending.is_empty() {
+                return Err(io::Error::from_raw_os_error(libc::ENXIO));
+            }
+            data[0] = self.pending.remove(0);
+            Ok(())
+        }
+    }
+
+    fn alert_bus(pending: Vec<u8>) -> MockI2CBus {
+        let mut bus = MockI2CBus::new();
+        bus.add_target(SMBUS_ALERT_RESPONSE_ADDRESS, AlertLine { pending });
+        bus
+    }
+
+    #[test]
+    fn alerts_are_read_until_nack() {
+        let mut bus = alert_bus(vec![0x48 << 1, 0x4C << 1 | 1]);
+        assert_eq!(
+            read_alerts(&mut bus).unwrap(),
+            vec![
+                SmbusAlert {
+                    address: 0x48,
+                    flag: false
+                },
+                SmbusAlert {
+                    address: 0x4C,
+                    flag: true
+                },
+            ]
+        );
+        assert!(read_alerts(&mut bus).unwrap().is_empty());
+        assert!(read_alerts(&mut MockI2CBus::new()).unwrap().is_empty());
+
+        // a device which does not release the alert line
+        let mut bus = alert_bus(vec![0x90; 10]);
+        assert_eq!(read_alerts(&mut bus).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn dispatcher_routes_alerts() {
+        use std::sync::mpsc;
+
+        let (sender, receiver) = mpsc::channel();
+        let mut dispatcher = AlertDispatcher::new();
+        dispatcher.register(0x48, move |alert| sender.send(alert).unwrap());
+
+        let mut bus = alert_bus(vec![0x48 << 1, 0x50 << 1]);
+        let unhandled = dispatcher.dispatch(&mut bus).unwrap();
+        assert_eq!(unhandled.len(), 1);
+        assert_eq!(unhandled[0].address, 0x50);
+        assert_eq!(receiver.try_recv().unwrap().address, 0x48);
+
+        assert!(dispatcher.unregister(0x48));
+        assert!(!dispatcher.unregister(0x48));
+    }
+}
@@ -0,0 +1,856 @@
+// Copyright 2015, Paul Osborne <osbpau@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/license/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option.  This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serial Presence Detect of memory modules
+//!
+//! Each memory module describes itself in an SPD EEPROM at 0x50 to 0x57,
+//! following its slot number.  DDR3 modules hold 256 bytes.  DDR4 modules
+//! hold 512 bytes in two pages, selected by writing to 0x36 or 0x37 on
+//! the bus.  DDR5 modules hold 1024 bytes behind an SPD5118 hub, read in
+//! 128 byte pages selected through its MR11 register.
+//!
+//! Reading requires an adapter supporting plain I2C transfers.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), i2cdev::linux::LinuxI2CError> {
+//! use i2cdev::linux::LinuxI2CBus;
+//! use i2cdev::spd::Spd;
+//!
+//! let mut bus = LinuxI2CBus::new("/dev/i2c-0")?;
+//! for slot in 0..8 {
+//!     if let Ok(spd) = Spd::read(&mut bus, 0x50 + slot) {
+//!         println!("{:?} {} MiB {} MT/s {} {}",
+//!                  spd.memory_type, spd.capacity_mib, spd.speed_mts(),
+//!                  spd.module_manufacturer.name().unwrap_or("unknown"),
+//!                  spd.part_number);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use core::{invalid_data, I2CTransfer};
+use shared::write_read;
+use smbus::is_nack;
+use std::io;
+
+/// Address of the SPD EEPROM of the module in the first slot
+pub const SPD_ADDRESS: u16 = 0x50;
+
+/// Writing to this address selects page 0 of all DDR4 SPD EEPROMs
+pub const SET_PAGE_0_ADDRESS: u16 = 0x36;
+
+/// Writing to this address selects page 1 of all DDR4 SPD EEPROMs
+pub const SET_PAGE_1_ADDRESS: u16 = 0x37;
+
+/// Device type of SPD5118 hubs, in MR0 and MR1
+const SPD5118_DEVICE_TYPE: [u8; 2] = [0x51, 0x18];
+
+/// SPD5118 register selecting the page of the NVM mapped at 0x80
+const SPD5118_MR11: u8 = 0x0B;
+
+/// Memory type in byte 2 of the SPD
+const DDR3: u8 = 0x0B;
+const DDR4: u8 = 0x0C;
+const DDR5: u8 = 0x12;
+
+/// Standard data rates, which the rates computed from tCK are snapped to
+const SPEED_GRADES: [u32; 24] = [
+    800, 1066, 1333, 1600, 1866, 2133, 2400, 2666, 2933, 3200, 3600, 4000, 4400, 4800, 5200, 5600,
+    6000, 6400, 6800, 7200, 7600, 8000, 8400, 8800,
+];
+
+/// Compute the CRC of SPD data
+///
+/// This is CRC-16 with polynomial 0x1021 and an initial value of zero,
+/// also known as CRC-16/XMODEM.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Read `data.len()` bytes from `offset` of an 8-bit addressed EEPROM
+fn read_at<T, E>(bus: &mut T, address: u16, offset: u8, data: &mut [u8]) -> Result<(), E>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+{
+    write_read(bus, address, &[offset], data)
+}
+
+/// Write the DDR4 set page address, ignoring a missing acknowledge
+///
+/// On buses without DDR4 modules, nothing answers at these addresses.
+fn set_ddr4_page<T, E>(bus: &mut T, page: u8) -> Result<(), E>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: From<io::Error> + Into<io::Error>,
+{
+    let address = if page == 0 {
+        SET_PAGE_0_ADDRESS
+    } else {
+        SET_PAGE_1_ADDRESS
+    };
+    if let Err(e) = write_read(bus, address, &[0], &mut []) {
+        let e: io::Error = e.into();
+        if !is_nack(&e) {
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// Read the whole SPD of the module at `address`
+///
+/// The memory type is detected from the first bytes of the SPD, or from
+/// the device type registers of a DDR5 SPD hub.  Reading a DDR4 SPD
+/// changes the page of every DDR4 EEPROM on the bus, leaving page 0
+/// selected.
+pub fn read_spd<T, E>(bus: &mut T, address: u16) -> Result<Vec<u8>, E>
+where
+    T: for<'x> I2CTransfer<'x, Error = E>,
+    E: From<io::Error> + Into<io::Error>,
+{
+    set_ddr4_page(bus, 0)?;
+    let mut id = [0; 3];
+    read_at(bus, address, 0, &mut id)?;
+
+    if id[..2] == SPD5118_DEVICE_TYPE {
+        let mut spd = vec![0; 1024];
+        for (page, chunk) in spd.chunks_mut(128).enumerate() {
+            write_read(bus, address, &[SPD5118_MR11, page as u8], &mut [])?;
+            read_at(bus, address, 0x80, chunk)?;
+        }
+        write_read(bus, address, &[SPD5118_MR11, 0], &mut [])?;
+        return Ok(spd);
+    }
+
+    match id[2] {
+        DDR3 => {
+            let mut spd = vec![0; 256];
+            read_at(bus, address, 0, &mut spd[..128])?;
+            read_at(bus, address, 128, &mut spd[128..])?;
+            Ok(spd)
+        }
+        DDR4 => {
+            let mut spd = vec![0; 512];
+            for (page, half) in spd.chunks_mut(256).enumerate() {
+                set_ddr4_page(bus, page as u8)?;
+                read_at(bus, address, 0, &mut half[..128])?;
+                read_at(bus, address, 128, &mut half[128..])?;
+            }
+            set_ddr4_page(bus, 0)?;
+            Ok(spd)
+        }
+        _ => Err(invalid_data("unsupported SPD memory type").into()),
+    }
+}
+
+/// DRAM generation of a module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryType {
+    /// DDR3 SDRAM
+    Ddr3,
+    /// DDR4 SDRAM
+    Ddr4,
+    /// DDR5 SDRAM
+    Ddr5,
+}
+
+/// Form factor of a module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleType {
+    /// Registered DIMM
+    Rdimm,
+    /// Unbuffered DIMM
+    Udimm,
+    /// Small outline DIMM
+    SoDimm,
+    /// Load reduced DIMM
+    Lrdimm,
+    /// Any other module type, as encoded in byte 3
+    Other(u8),
+}
+
+impl ModuleType {
+    fn from_spd(memory_type: MemoryType, byte: u8) -> ModuleType {
+        match (memory_type, byte & 0x0F) {
+            (_, 1) => ModuleType::Rdimm,
+            (_, 2) => ModuleType::Udimm,
+            (_, 3) => ModuleType::SoDimm,
+            (MemoryType::Ddr3, 0x0B) => ModuleType::Lrdimm,
+            (MemoryType::Ddr4, 4) | (MemoryType::Ddr5, 4) => ModuleType::Lrdimm,
+            (_, other) => ModuleType::Other(other),
+        }
+    }
+}
+
+/// JEP106 manufacturer identification code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JedecId {
+    /// Bank of the code, from 1
+    pub bank: u8,
+    /// Code within the bank, including its parity bit
+    pub code: u8,
+}
+
+impl JedecId {
+    /// Decode the continuation count and code bytes of the SPD
+    fn from_spd(bytes: &[u8]) -> JedecId {
+        JedecId {
+            bank: (bytes[0] & 0x7F) + 1,
+            code: bytes[1],
+        }
+    }
+
+    /// Name of the manufacturer, for well-known memory vendors
+    pub fn name(&self) -> Option<&'static str> {
+        Some(match (self.bank, self.code) {
+            (1, 0x2C) => "Micron Technology",
+            (1, 0xAD) => "SK Hynix",
+            (1, 0xCE) => "Samsung",
+            (2, 0x98) => "Kingston",
+            (3, 0x9E) => "Corsair",
+            (4, 0x0B) => "Nanya Technology",
+            (5, 0xCD) => "G.Skill",
+            (6, 0x9B) => "Crucial Technology",
+            _ => return None,
+        })
+    }
+}
+
+/// Minimum SDRAM timings, in picoseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timings {
+    /// Minimum clock cycle time
+    pub t_ck: u32,
+    /// Minimum CAS latency time
+    pub t_aa: u32,
+    /// Minimum RAS to CAS delay
+    pub t_rcd: u32,
+    /// Minimum row precharge time
+    pub t_rp: u32,
+    /// Minimum active to precharge time
+    pub t_ras: u32,
+    /// Minimum active to active/refresh time
+    pub t_rc: u32,
+}
+
+/// Decoded SPD
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spd {
+    /// DRAM generation
+    pub memory_type: MemoryType,
+    /// Form factor
+    pub module_type: ModuleType,
+    /// Total capacity in MiB
+    pub capacity_mib: u64,
+    /// Number of package ranks
+    pub ranks: u8,
+    /// Width of the SDRAM devices in bits
+    pub device_width: u8,
+    /// Width of the data bus in bits, without ECC
+    pub bus_width: u16,
+    /// Whether the module has ECC
+    pub ecc: bool,
+    /// Minimum timings
+    pub timings: Timings,
+    /// Supported CAS latencies, in clock cycles
+    pub cas_latencies: Vec<u8>,
+    /// Manufacturer of the module
+    pub module_manufacturer: JedecId,
+    /// Manufacturer of the SDRAM devices, if set
+    pub dram_manufacturer: Option<JedecId>,
+    /// Year and week of manufacture, if set
+    pub manufacture_date: Option<(u16, u8)>,
+    /// Serial number of the module
+    pub serial_number: u32,
+    /// Part number of the module
+    pub part_number: String,
+}
+
+impl Spd {
+    /// Read and decode the SPD of the module at `address`
+    pub fn read<T, E>(bus: &mut T, address: u16) -> Result<Spd, E>
+    where
+        T: for<'x> I2CTransfer<'x, Error = E>,
+        E: From<io::Error> + Into<io::Error>,
+    {
+        Ok(Spd::parse(&read_spd(bus, address)?)?)
+    }
+
+    /// Decode an SPD, such as one read with `read_spd`, verifying its CRCs
+    pub fn parse(data: &[u8]) -> io::Result<Spd> {
+        if data.len() < 3 {
+            return Err(invalid_data("SPD too short"));
+        }
+        match data[2] {
+            DDR3 => parse_ddr3(data),
+            DDR4 => parse_ddr4(data),
+            DDR5 => parse_ddr5(data),
+            _ => Err(invalid_data("unsupported SPD memory type")),
+        }
+    }
+
+    /// Data rate in MT/s at the minimum clock cycle time
+    pub fn speed_mts(&self) -> u32 {
+        if self.timings.t_ck == 0 {
+            return 0;
+        }
+        let rate = 2_000_000.0 / f64::from(self.timings.t_ck);
+        for &grade in SPEED_GRADES.iter() {
+            if (rate - f64::from(grade)).abs() <= f64::from(grade) * 0.005 {
+                return grade;
+            }
+        }
+        rate.round() as u32
+    }
+}
+
+fn check_len(data: &[u8], len: usize) -> io::Result<()> {
+    if data.len() < len {
+        return Err(invalid_data("SPD too short for its memory type"));
+    }
+    Ok(())
+}
+
+/// Verify the little endian CRC stored at `data[end..end + 2]`
+fn check_crc(data: &[u8], start: usize, covered_end: usize, end: usize) -> io::Result<()> {
+    if crc16(&data[start..covered_end]) != LittleEndian::read_u16(&data[end..]) {
+        return Err(invalid_data("SPD CRC mismatch"));
+    }
+    Ok(())
+}
+
+/// Decode a BCD year and week, zero meaning unset
+fn bcd_date(year: u8, week: u8) -> Option<(u16, u8)> {
+    let bcd = |b: u8| (b >> 4) * 10 + (b & 0x0F);
+    if year == 0 && week == 0 {
+        return None;
+    }
+    Some((2000 + u16::from(bcd(year)), bcd(week)))
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches(&[' ', '\0'][..])
+        .to_string()
+}
+
+/// Supported CAS latencies from a bitmap, bit n meaning `first + n * step`
+fn cas_latencies(bitmap: &[u8], first: u8, step: u8) -> Vec<u8> {
+    (0..bitmap.len() * 8)
+        .filter(|&bit| bitmap[bit / 8] & (1 << (bit % 8)) != 0)
+        .map(|bit| first + bit as u8 * step)
+        .collect()
+}
+
+/// Timing from its medium and fine timebase parts, in picoseconds
+fn mtb_timing(mtb_units: u16, fine: u8, mtb_ps: u32, ftb_ps: f64) -> u32 {
+    let ps = f64::from(mtb_units) * f64::from(mtb_ps) + f64::from(fine as i8) * ftb_ps;
+    ps.round() as u32
+}
+
+/// DRAM manufacturer, which modules may leave unset
+fn dram_manufacturer(bytes: &[u8]) -> Option<JedecId> {
+    if bytes[..2] == [0, 0] {
+        None
+    } else {
+        Some(JedecId::from_spd(bytes))
+    }
+}
+
+/// Width in bits of an SDRAM device, from its SPD code
+fn device_width(code: u8) -> io::Result<u8> {
+    match code {
+        0..=3 => Ok(4 << code),
+        _ => Err(invalid_data("reserved SDRAM device width")),
+    }
+}
+
+/// Width in bits of a data bus, from its SPD code
+fn bus_width(code: u8) -> io::Result<u16> {
+    match code {
+        0..=3 => Ok(8 << code),
+        _ => Err(invalid_data("reserved memory bus width")),
+    }
+}
+
+/// Capacity in MiB from a per-die density in Mbit
+fn capacity_mib(density_mbit: u64, bus_width: u16, device_width: u8, dies: u64) -> u64 {
+    density_mbit / 8 * (u64::from(bus_width) / u64::from(device_width)) * dies
+}
+
+fn parse_ddr3(data: &[u8]) -> io::Result<Spd> {
+    check_len(data, 256)?;
+    let covered = if data[0] & 0x80 != 0 { 117 } else { 126 };
+    check_crc(data, 0, covered, 126)?;
+
+    let mtb_ps = match (data[10], data[11]) {
+        (dividend, divisor) if dividend != 0 && divisor != 0 => {
+            1000 * u32::from(dividend) / u32::from(divisor)
+        }
+        _ => return Err(invalid_data("invalid DDR3 medium timebase")),
+    };
+    let ftb_ps = match data[9] & 0x0F {
+        0 => 0.0,
+        divisor => f64::from(data[9] >> 4) / f64::from(divisor),
+    };
+    let timing = |mtb: u16, fine: u8| mtb_timing(mtb, fine, mtb_ps, ftb_ps);
+
+    let device_width = device_width(data[7] & 0x07)?;
+    let ranks = (data[7] >> 3 & 0x07) + 1;
+    let bus_width = bus_width(data[8] & 0x07)?;
+    let density = match data[4] & 0x0F {
+        n @ 0..=6 => 256 << n,
+        _ => return Err(invalid_data("reserved DDR3 SDRAM density")),
+    };
+    Ok(Spd {
+        memory_type: MemoryType::Ddr3,
+        module_type: ModuleType::from_spd(MemoryType::Ddr3, data[3]),
+        capacity_mib: capacity_mib(density, bus_width, device_width, u64::from(ranks)),
+        ranks,
+        device_width,
+        bus_width,
+        ecc: data[8] >> 3 & 0x03 != 0,
+        timings: Timings {
+            t_ck: timing(u16::from(data[12]), data[34]),
+            t_aa: timing(u16::from(data[16]), data[35]),
+            t_rcd: timing(u16::from(data[18]), data[36]),
+            t_rp: timing(u16::from(data[20]), data[37]),
+            t_ras: timing(u16::from(data[21] & 0x0F) << 8 | u16::from(data[22]), 0),
+            t_rc: timing(
+                u16::from(data[21] >> 4) << 8 | u16::from(data[23]),
+                data[38],
+            ),
+        },
+        cas_latencies: cas_latencies(&data[14..16], 4, 1),
+        module_manufacturer: JedecId::from_spd(&data[117..]),
+        dram_manufacturer: dram_manufacturer(&data[148..]),
+        manufacture_date: bcd_date(data[120], data[121]),
+        serial_number: BigEndian::read_u32(&data[122..]),
+        part_number: ascii(&data[128..146]),
+    })
+}
+
+fn parse_ddr4(data: &[u8]) -> io::Result<Spd> {
+    check_len(data, 512)?;
+    check_crc(data, 0, 126, 126)?;
+    check_crc(data, 128, 254, 254)?;
+
+    // the timebases are fixed at 125 ps and 1 ps
+    let timing = |mtb: u16, fine: u8| mtb_timing(mtb, fine, 125, 1.0);
+
+    let density = match data[4] & 0x0F {
+        8 => 12 * 1024,
+        9 => 24 * 1024,
+        n @ 0..=7 => 256 << n,
+        _ => return Err(invalid_data("reserved DDR4 SDRAM density")),
+    };
+    // 3DS packages stack several dies per rank
+    let dies = if data[6] & 0x03 == 0x02 {
+        u64::from((data[6] >> 4 & 0x07) + 1)
+    } else {
+        1
+    };
+    let device_width = device_width(data[12] & 0x07)?;
+    let ranks = (data[12] >> 3 & 0x07) + 1;
+    let bus_width = bus_width(data[13] & 0x07)?;
+    let first_cl = if data[23] & 0x80 != 0 { 23 } else { 7 };
+    let mut cl_bitmap = data[20..24].to_vec();
+    cl_bitmap[3] &= 0x3F;
+    Ok(Spd {
+        memory_type: MemoryType::Ddr4,
+        module_type: ModuleType::from_spd(MemoryType::Ddr4, data[3]),
+        capacity_mib: capacity_mib(density, bus_width, device_width, u64::from(ranks) * dies),
+        ranks,
+        device_width,
+        bus_width,
+        ecc: data[13] >> 3 & 0x03 != 0,
+        timings: Timings {
+            t_ck: timing(u16::from(data[18]), data[125]),
+            t_aa: timing(u16::from(data[24]), data[123]),
+            t_rcd: timing(u16::from(data[25]), data[122]),
+            t_rp: timing(u16::from(data[26]), data[121]),
+            t_ras: timing(u16::from(data[27] & 0x0F) << 8 | u16::from(data[28]), 0),
+            t_rc: timing(
+                u16::from(data[27] >> 4) << 8 | u16::from(data[29]),
+                data[120],
+            ),
+        },
+        cas_latencies: cas_latencies(&cl_bitmap, first_cl, 1),
+        module_manufacturer: JedecId::from_spd(&data[320..]),
+        dram_manufacturer: dram_manufacturer(&data[350..]),
+        manufacture_date: bcd_date(data[323], data[324]),
+        serial_number: BigEndian::read_u32(&data[325..]),
+        part_number: ascii(&data[329..349]),
+    })
+}
+
+fn parse_ddr5(data: &[u8]) -> io::Result<Spd> {
+    check_len(data, 1024)?;
+    check_crc(data, 0, 510, 510)?;
+
+    let density = match data[4] & 0x1F {
+        0 => return Err(invalid_data("DDR5 SPD without SDRAM density")),
+        1 => 4 * 1024,
+        2 => 8 * 1024,
+        3 => 12 * 1024,
+        4 => 16 * 1024,
+        5 => 24 * 1024,
+        6 => 32 * 1024,
+        7 => 48 * 1024,
+        8 => 64 * 1024,
+        _ => return Err(invalid_data("unknown DDR5 SDRAM density")),
+    };
+    let dies = match data[4] >> 5 {
+        0 => 1,
+        n @ 2..=5 => 1 << (n - 1),
+        _ => return Err(invalid_data("reserved DDR5 die count")),
+    };
+    let device_width = device_width(data[6] >> 5)?;
+    let ranks = (data[234] >> 3 & 0x07) + 1;
+    let channels = u16::from((data[235] >> 5 & 0x03) + 1);
+    let bus_width = bus_width(data[235] & 0x07)? * channels;
+    // DDR5 timings are in picoseconds
+    let ps = |offset: usize| u32::from(LittleEndian::read_u16(&data[offset..]));
+    Ok(Spd {
+        memory_type: MemoryType::Ddr5,
+        module_type: ModuleType::from_spd(MemoryType::Ddr5, data[3]),
+        capacity_mib: capacity_mib(density, bus_width, device_width, u64::from(ranks) * dies),
+        ranks,
+        device_width,
+        bus_width,
+        ecc: data[235] >> 3 & 0x03 != 0,
+        timings: Timings {
+            t_ck: ps(20),
+            t_aa: ps(30),
+            t_rcd: ps(32),
+            t_rp: ps(34),
+            t_ras: ps(36),
+            t_rc: ps(38),
+        },
+        cas_latencies: cas_latencies(&data[24..29], 20, 2),
+        module_manufacturer: JedecId::from_spd(&data[512..]),
+        dram_manufacturer: dram_manufacturer(&data[552..]),
+        manufacture_date: bcd_date(data[515], data[516]),
+        serial_number: BigEndian::read_u32(&data[517..]),
+        part_number: ascii(&data[521..551]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{I2CResult, MockI2CBus, MockI2CTarget};
+    use std::sync::{Arc, Mutex};
+
+    fn set_crc(spd: &mut [u8], start: usize, covered_end: usize, end: usize) {
+        let crc = crc16(&spd[start..covered_end]);
+        LittleEndian::write_u16(&mut spd[end..], crc);
+    }
+
+    fn put(spd: &mut [u8], offset: usize, bytes: &[u8]) {
+        spd[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// 4 GiB DDR3-1600 SO-DIMM
+    fn ddr3_spd() -> Vec<u8> {
+        let mut spd = vec![0; 256];
+        put(
+            &mut spd,
+            0,
+            &[0x92, 0x13, DDR3, 0x03, 0x04, 0, 0, 0x01, 0x03],
+        );
+        put(
+            &mut spd,
+            9,
+            &[0x11, 1, 8, 0x0A, 0, 0xFC, 0x00, 0x69, 0, 0x69],
+        );
+        put(&mut spd, 20, &[0x69, 0x11, 0x18, 0x85]);
+        put(
+            &mut spd,
+            117,
+            &[0x80, 0x2C, 0, 0x15, 0x30, 0xDE, 0xAD, 0xBE, 0xEF],
+        );
+        put(&mut spd, 128, b"MT8KTF51264HZ-1G6 ");
+        put(&mut spd, 148, &[0x80, 0x2C]);
+        set_crc(&mut spd, 0, 117, 126);
+        spd
+    }
+
+    /// 8 GiB DDR4-2400 UDIMM
+    fn ddr4_spd() -> Vec<u8> {
+        let mut spd = vec![0; 512];
+        put(&mut spd, 0, &[0x23, 0x11, DDR4, 0x02, 0x45, 0x21, 0x00]);
+        put(
+            &mut spd,
+            12,
+            &[0x01, 0x03, 0, 0, 0, 0, 0x07, 0, 0xF8, 0x07, 0, 0],
+        );
+        put(&mut spd, 24, &[0x6E, 0x6E, 0x6E, 0x11, 0x00, 0x6E]);
+        put(&mut spd, 125, &[0xD6]);
+        put(
+            &mut spd,
+            320,
+            &[0x80, 0xCE, 0, 0x21, 0x15, 0x12, 0x34, 0x56, 0x78],
+        );
+        put(&mut spd, 329, b"M378A1K43CB2-CRC    ");
+        put(&mut spd, 350, &[0x80, 0xCE]);
+        set_crc(&mut spd, 0, 126, 126);
+        set_crc(&mut spd, 128, 254, 254);
+        spd
+    }
+
+    /// 16 GiB DDR5-4800 UDIMM
+    fn ddr5_spd() -> Vec<u8> {
+        let mut spd = vec![0; 1024];
+        put(&mut spd, 0, &[0x30, 0x10, DDR5, 0x02, 0x04, 0, 0x20]);
+        put(&mut spd, 20, &[0xA0, 0x01, 0, 0, 0x54, 0x05, 0, 0, 0]);
+        put(&mut spd, 30, &[0x80, 0x3E, 0x80, 0x3E, 0x80, 0x3E]);
+        put(&mut spd, 36, &[0x00, 0x7D, 0x80, 0xBB]);
+        put(&mut spd, 234, &[0x00, 0x22]);
+        put(
+            &mut spd,
+            512,
+            &[0x80, 0xAD, 0, 0x22, 0x40, 0x01, 0x02, 0x03, 0x04],
+        );
+        put(&mut spd, 521, b"HMCG78AEBUA081N");
+        put(&mut spd, 552, &[0x80, 0xAD]);
+        set_crc(&mut spd, 0, 510, 510);
+        spd
+    }
+
+    /// EEPROM with 8-bit offsets, paged in 256 bytes for DDR4
+    struct Eeprom {
+        data: Vec<u8>,
+        page: Arc<Mutex<usize>>,
+        offset: usize,
+    }
+
+    impl MockI2CTarget for Eeprom {
+        fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+            self.offset = data[0] as usize;
+            Ok(())
+        }
+
+        fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+            let base = *self.page.lock().unwrap() * 256;
+            for byte in data.iter_mut() {
+                *byte = self.data[base + self.offset];
+                self.offset = (self.offset + 1) % 256;
+            }
+            Ok(())
+        }
+    }
+
+    struct SetPage(Arc<Mutex<usize>>, usize);
+
+    impl MockI2CTarget for SetPage {
+        fn handle_write(&mut self, _data: &[u8]) -> I2CResult<()> {
+            *self.0.lock().unwrap() = self.1;
+            Ok(())
+        }
+
+        fn handle_read(&mut self, _data: &mut [u8]) -> I2CResult<()> {
+            Err(io::Error::from_raw_os_error(::libc::ENXIO))
+        }
+    }
+
+    /// SPD5118 hub in legacy 1 byte addressing mode
+    struct Hub {
+        nvm: Vec<u8>,
+        mr11: u8,
+        offset: u8,
+    }
+
+    impl MockI2CTarget for Hub {
+        fn handle_write(&mut self, data: &[u8]) -> I2CResult<()> {
+            self.offset = data[0];
+            if data.len() == 2 && data[0] == SPD5118_MR11 {
+                self.mr11 = data[1];
+            }
+            Ok(())
+        }
+
+        fn handle_read(&mut self, data: &mut [u8]) -> I2CResult<()> {
+            for byte in data.iter_mut() {
+                *byte = match self.offset {
+                    0 => SPD5118_DEVICE_TYPE[0],
+                    1 => SPD5118_DEVICE_TYPE[1],
+                    SPD5118_MR11 => self.mr11,
+                    offset if offset >= 0x80 => {
+                        self.nvm[(self.mr11 & 0x07) as usize * 128 + (offset - 0x80) as usize]
+                    }
+                    _ => 0,
+                };
+                self.offset = self.offset.wrapping_add(1);
+            }
+            Ok(())
+        }
+    }
+
+    fn eeprom_bus(data: Vec<u8>, ddr4: bool) -> MockI2CBus {
+        let page = Arc::new(Mutex::new(0));
+        let mut bus = MockI2CBus::new();
+        if ddr4 {
+            bus.add_target(SET_PAGE_0_ADDRESS, SetPage(page.clone(), 0));
+            bus.add_target(SET_PAGE_1_ADDRESS, SetPage(page.clone(), 1));
+        }
+        bus.add_target(
+            SPD_ADDRESS + 1,
+            Eeprom {
+                data,
+                page,
+                offset: 0,
+            },
+        );
+        bus
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+    }
+
+    #[test]
+    fn ddr3() {
+        let mut bus = eeprom_bus(ddr3_spd(), false);
+        assert_eq!(read_spd(&mut bus, SPD_ADDRESS + 1).unwrap(), ddr3_spd());
+
+        let spd = Spd::read(&mut bus, SPD_ADDRESS + 1).unwrap();
+        assert_eq!(spd.memory_type, MemoryType::Ddr3);
+        assert_eq!(spd.module_type, ModuleType::SoDimm);
+        assert_eq!(spd.capacity_mib, 4096);
+        assert_eq!((spd.ranks, spd.device_width, spd.bus_width), (1, 8, 64));
+        assert!(!spd.ecc);
+        assert_eq!(spd.speed_mts(), 1600);
+        assert_eq!(
+            spd.timings,
+            Timings {
+                t_ck: 1250,
+                t_aa: 13125,
+                t_rcd: 13125,
+                t_rp: 13125,
+                t_ras: 35000,
+                t_rc: 48625,
+            }
+        );
+        assert_eq!(spd.cas_latencies, vec![6, 7, 8, 9, 10, 11]);
+        assert_eq!(spd.module_manufacturer.name(), Some("Micron Technology"));
+        assert_eq!(spd.dram_manufacturer, Some(spd.module_manufacturer));
+        assert_eq!(spd.manufacture_date, Some((2015, 30)));
+        assert_eq!(spd.serial_number, 0xDEAD_BEEF);
+        assert_eq!(spd.part_number, "MT8KTF51264HZ-1G6");
+
+        let mut unset = ddr3_spd();
+        put(&mut unset, 148, &[0, 0]);
+        assert_eq!(Spd::parse(&unset).unwrap().dram_manufacturer, None);
+    }
+
+    #[test]
+    fn ddr4_pages() {
+        let mut bus = eeprom_bus(ddr4_spd(), true);
+        assert_eq!(read_spd(&mut bus, SPD_ADDRESS + 1).unwrap(), ddr4_spd());
+
+        let spd = Spd::read(&mut bus, SPD_ADDRESS + 1).unwrap();
+        assert_eq!(spd.memory_type, MemoryType::Ddr4);
+        assert_eq!(spd.module_type, ModuleType::Udimm);
+        assert_eq!(spd.capacity_mib, 8192);
+        assert_eq!(spd.timings.t_ck, 833);
+        assert_eq!(spd.speed_mts(), 2400);
+        assert_eq!(spd.timings.t_aa, 13750);
+        assert_eq!(spd.timings.t_ras, 32000);
+        assert_eq!(spd.timings.t_rc, 45750);
+        assert_eq!(spd.cas_latencies, (10..18).collect::<Vec<u8>>());
+        assert_eq!(spd.module_manufacturer.name(), Some("Samsung"));
+        assert_eq!(spd.dram_manufacturer, Some(spd.module_manufacturer));
+        assert_eq!(spd.manufacture_date, Some((2021, 15)));
+        assert_eq!(spd.serial_number, 0x1234_5678);
+        assert_eq!(spd.part_number, "M378A1K43CB2-CRC");
+    }
+
+    #[test]
+    fn ddr5_hub() {
+        let mut bus = MockI2CBus::new();
+        bus.add_target(
+            SPD_ADDRESS,
+            Hub {
+                nvm: ddr5_spd(),
+                mr11: 0,
+                offset: 0,
+            },
+        );
+        assert_eq!(read_spd(&mut bus, SPD_ADDRESS).unwrap(), ddr5_spd());
+
+        let spd = Spd::read(&mut bus, SPD_ADDRESS).unwrap();
+        assert_eq!(spd.memory_type, MemoryType::Ddr5);
+        assert_eq!(spd.capacity_mib, 16384);
+        assert_eq!((spd.ranks, spd.device_width, spd.bus_width), (1, 8, 64));
+        assert_eq!(spd.speed_mts(), 4800);
+        assert_eq!(spd.timings.t_aa, 16000);
+        assert_eq!(spd.timings.t_rc, 48000);
+        assert_eq!(spd.cas_latencies, vec![24, 28, 32, 36, 40]);
+        assert_eq!(spd.module_manufacturer.name(), Some("SK Hynix"));
+        assert_eq!(spd.manufacture_date, Some((2022, 40)));
+        assert_eq!(spd.part_number, "HMCG78AEBUA081N");
+    }
+
+    #[test]
+    fn rejects_bad_crc() {
+        let mut spd = ddr4_spd();
+        spd[200] ^= 0xFF;
+        let err = Spd::parse(&spd).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(Spd::parse(&ddr4_spd()[..256]).is_err());
+
+        let mut spd = ddr3_spd();
+        spd[2] = 0x07;
+        assert!(Spd::parse(&spd).is_err());
+    }
+
+    #[test]
+    fn rejects_reserved_codes() {
+        let mut spd = ddr3_spd();
+        spd[7] = 0x06;
+        set_crc(&mut spd, 0, 117, 126);
+        let err = Spd::parse(&spd).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut spd = ddr4_spd();
+        spd[13] = 0x07;
+        set_crc(&mut spd, 0, 126, 126);
+        assert_eq!(
+            Spd::parse(&spd).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut spd = ddr5_spd();
+        spd[4] = 0xE4;
+        set_crc(&mut spd, 0, 510, 510);
+        assert_eq!(
+            Spd::parse(&spd).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut spd = ddr5_spd();
+        spd[6] = spd[6] & 0x1F | 0x80;
+        set_crc(&mut spd, 0, 510, 510);
+        assert_eq!(
+            Spd::parse(&spd).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}